@@ -0,0 +1,40 @@
+// Compare two CSV strings with the WebAssembly build of csvdiff in Node.js (18 or later).
+//
+// Build the module from the repository root, then run this script:
+//
+//   rustup target add wasm32-unknown-unknown
+//   cargo rustc --lib --release --target wasm32-unknown-unknown --no-default-features --features wasm --crate-type cdylib
+//   node examples/wasm/compare.mjs
+//
+// In a browser, load the same file with `WebAssembly.instantiateStreaming(fetch("csvdiff.wasm"))`.
+
+import { readFile } from "node:fs/promises";
+
+const wasmPath = new URL("../../target/wasm32-unknown-unknown/release/csvdiff.wasm", import.meta.url);
+const { instance } = await WebAssembly.instantiate(await readFile(wasmPath));
+const { memory, csvdiff_alloc, csvdiff_free, csvdiff_compare } = instance.exports;
+
+// Copy a string into the module's memory; returns its address and length
+function copyIn(text) {
+  const bytes = new TextEncoder().encode(text);
+  const ptr = csvdiff_alloc(bytes.length);
+  new Uint8Array(memory.buffer, ptr, bytes.length).set(bytes);
+  return [ptr, bytes.length];
+}
+
+export function compareCsvStrings(csv1, csv2, config) {
+  const inputs = [csv1, csv2, JSON.stringify(config)].map(copyIn);
+  const result = csvdiff_compare(...inputs.flat());
+  // The result starts with its length as a little-endian u32
+  const length = new DataView(memory.buffer).getUint32(result, true);
+  const json = new TextDecoder().decode(new Uint8Array(memory.buffer, result + 4, length));
+  csvdiff_free(result, length + 4);
+  for (const [ptr, len] of inputs) {
+    csvdiff_free(ptr, len);
+  }
+  return JSON.parse(json);
+}
+
+const before = "id,name,price\n1,Ada,10.00\n2,Grace,12.50\n";
+const after = "id,name,price\n1,Ada,10.01\n3,Linus,9.99\n";
+console.log(compareCsvStrings(before, after, { key: "id", tolerance: 0.005 }));
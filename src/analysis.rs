@@ -0,0 +1,543 @@
+//! Post-processing analyses that look for patterns across the collected differences.
+
+use std::collections::{HashMap, HashSet};
+
+use csv::StringRecord;
+
+use crate::DiffRow;
+use crate::datetime::{self, Timezone};
+
+/// A numeric column whose changed values are consistently off by the same factor.
+pub struct ScalingFinding {
+    pub column: String,
+    pub factor: f64,
+    pub matching: usize,
+    pub total: usize,
+}
+
+/// Minimum number of numeric changes a column needs before a pattern is reported
+const MIN_NUMERIC_CHANGES: usize = 3;
+/// Share of a column's numeric changes that must agree for a pattern to be reported
+const MIN_AGREEMENT: f64 = 0.8;
+
+/// Collect `(file1, file2)` pairs for changed values that parse as numbers on both sides, by column.
+fn numeric_changes(diffs: &[DiffRow]) -> HashMap<&str, Vec<(f64, f64)>> {
+    let mut changes: HashMap<&str, Vec<(f64, f64)>> = HashMap::new();
+    for diff in diffs.iter().filter(|diff| diff.is_value_change()) {
+        if let (Ok(v1), Ok(v2)) = (diff.file1.trim().parse::<f64>(), diff.file2.trim().parse::<f64>())
+            && v1.is_finite()
+            && v2.is_finite()
+        {
+            changes.entry(diff.column.as_str()).or_default().push((v1, v2));
+        }
+    }
+    changes
+}
+
+/// Find the largest group of values lying within `tolerance` (relative) of each other.
+///
+/// Returns the group's mean and size.
+fn dominant_cluster(mut values: Vec<f64>, tolerance: f64) -> Option<(f64, usize)> {
+    values.sort_by(f64::total_cmp);
+    let mut best: Option<(usize, usize)> = None;
+    let mut start = 0;
+    for end in 0..values.len() {
+        while values[end] - values[start] > tolerance * values[start].abs().max(values[end].abs()) {
+            start += 1;
+        }
+        if best.is_none_or(|(s, e)| end - start > e - s) {
+            best = Some((start, end));
+        }
+    }
+    best.map(|(start, end)| {
+        let cluster = &values[start..=end];
+        (cluster.iter().sum::<f64>() / cluster.len() as f64, cluster.len())
+    })
+}
+
+/// Detect numeric columns where file2 values are a constant multiple of file1 values.
+pub fn detect_scaling(diffs: &[DiffRow], tolerance: f64) -> Vec<ScalingFinding> {
+    let mut findings: Vec<ScalingFinding> = numeric_changes(diffs)
+        .into_iter()
+        .filter_map(|(column, pairs)| {
+            let ratios: Vec<f64> = pairs.iter().filter(|(v1, _)| *v1 != 0.0).map(|(v1, v2)| v2 / v1).collect();
+            let total = ratios.len();
+            if total < MIN_NUMERIC_CHANGES {
+                return None;
+            }
+            let (factor, matching) = dominant_cluster(ratios, tolerance)?;
+            // A factor of 1 means the numbers are equal and only their formatting differs
+            let is_identity = (factor - 1.0).abs() <= tolerance;
+            (matching as f64 / total as f64 > MIN_AGREEMENT && !is_identity).then(|| ScalingFinding {
+                column: column.to_string(),
+                factor,
+                matching,
+                total,
+            })
+        })
+        .collect();
+    findings.sort_by(|a, b| a.column.cmp(&b.column));
+    findings
+}
+
+/// A numeric column whose changed values are consistently shifted by the same amount.
+pub struct OffsetFinding {
+    pub column: String,
+    pub offset: f64,
+    pub matching: usize,
+    pub total: usize,
+}
+
+/// Detect numeric columns where file2 values are file1 values plus a constant.
+///
+/// Differences are bucketed after rounding to six decimal places, which keeps floating-point
+/// noise such as `0.30000000000000004` from splitting a bucket.
+pub fn detect_offsets(diffs: &[DiffRow]) -> Vec<OffsetFinding> {
+    let mut findings: Vec<OffsetFinding> = numeric_changes(diffs)
+        .into_iter()
+        .filter_map(|(column, pairs)| {
+            let total = pairs.len();
+            if total < MIN_NUMERIC_CHANGES {
+                return None;
+            }
+            let mut histogram: HashMap<i64, usize> = HashMap::new();
+            for (v1, v2) in &pairs {
+                *histogram.entry(((v2 - v1) * 1e6).round() as i64).or_default() += 1;
+            }
+            let (bucket, matching) = histogram.into_iter().max_by_key(|&(bucket, count)| (count, -bucket.abs()))?;
+            (matching as f64 / total as f64 > MIN_AGREEMENT && bucket != 0).then(|| OffsetFinding {
+                column: column.to_string(),
+                offset: bucket as f64 / 1e6,
+                matching,
+                total,
+            })
+        })
+        .collect();
+    findings.sort_by(|a, b| a.column.cmp(&b.column));
+    findings
+}
+
+/// Relative change buckets of the numeric change histogram, with their upper bounds.
+pub const HISTOGRAM_BUCKETS: [(&str, f64); 4] = [("0-1%", 0.01), ("1-5%", 0.05), ("5-10%", 0.10), (">10%", f64::INFINITY)];
+
+/// Changed numeric values counted by the size of their relative change.
+pub struct NumericHistogram {
+    /// One count per entry of [`HISTOGRAM_BUCKETS`]
+    pub counts: [usize; 4],
+}
+
+impl NumericHistogram {
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+/// Bucket every changed numeric value by `|file2 - file1| / |file1|`; a change away from zero
+/// counts as over 10%.
+pub fn numeric_histogram(diffs: &[DiffRow]) -> NumericHistogram {
+    let mut counts = [0; 4];
+    for (v1, v2) in numeric_changes(diffs).into_values().flatten() {
+        let change = if v1 == v2 { 0.0 } else { (v2 - v1).abs() / v1.abs() };
+        let bucket = HISTOGRAM_BUCKETS.iter().position(|&(_, bound)| change <= bound).unwrap_or(HISTOGRAM_BUCKETS.len() - 1);
+        counts[bucket] += 1;
+    }
+    NumericHistogram { counts }
+}
+
+/// Format a factor compactly, e.g. `100` or `0.001`.
+pub fn format_factor(factor: f64) -> String {
+    let formatted = format!("{:.6}", factor);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Suffix marking a merged transposition row's column label.
+pub const TRANSPOSITION_LABEL: &str = "(values swapped)";
+
+/// Merge pairs of changes where a row's two changed values were simply swapped.
+///
+/// A row qualifies when it has exactly two changed columns and each file1 value reappears in
+/// the other column in file2. The pair becomes one row labelled `a ↔ b (values swapped)`;
+/// returns the rewritten differences and the number of transpositions found.
+pub fn merge_transpositions(diffs: &[DiffRow]) -> (Vec<DiffRow>, usize) {
+    let mut changes_by_key: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, diff) in diffs.iter().enumerate().filter(|(_, diff)| diff.is_value_change()) {
+        changes_by_key.entry(diff.key.as_str()).or_default().push(i);
+    }
+
+    // First index of each swapped pair → second index
+    let mut pairs: HashMap<usize, usize> = HashMap::new();
+    for indexes in changes_by_key.values() {
+        if let [i, j] = indexes[..] {
+            let (a, b) = (&diffs[i], &diffs[j]);
+            if a.file1 == b.file2 && a.file2 == b.file1 {
+                pairs.insert(i.min(j), i.max(j));
+            }
+        }
+    }
+    let second: HashSet<usize> = pairs.values().copied().collect();
+
+    let merged = diffs
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !second.contains(i))
+        .map(|(i, diff)| match pairs.get(&i) {
+            Some(&j) => {
+                // Name the columns alphabetically so the label is stable across runs
+                let (diff, other) = if diff.column <= diffs[j].column { (diff, &diffs[j]) } else { (&diffs[j], diff) };
+                DiffRow {
+                    column: format!("{} ↔ {} {}", diff.column, other.column, TRANSPOSITION_LABEL),
+                    file1: format!("{} | {}", diff.file1, other.file1),
+                    file2: format!("{} | {}", diff.file2, other.file2),
+                    edit_distance: None,
+                    ..diff.clone()
+                }
+            }
+            None => diff.clone(),
+        })
+        .collect();
+    (merged, pairs.len())
+}
+
+/// Whether one row became several (split) or several rows became one (merge).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegroupKind {
+    Split,
+    Merge,
+}
+
+/// An unmatched row whose values are spread over, or gathered from, several unmatched rows.
+pub struct Regrouping {
+    pub kind: RegroupKind,
+    /// The key on the "one row" side: file1 for a split, file2 for a merge
+    pub key: String,
+    /// The keys on the "several rows" side, sorted
+    pub parts: Vec<String>,
+    /// Compared columns whose values are consistent with the regrouping
+    pub matching: usize,
+    pub total: usize,
+}
+
+/// Whether `candidate` extends `key` with a separator, e.g. `100-1` or `100_a` for `100`.
+fn extends_key(candidate: &str, key: &str) -> bool {
+    candidate
+        .strip_prefix(key)
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(|c| !c.is_alphanumeric())
+}
+
+/// Whether a value from the single row is explained by the values of the part rows: shared
+/// by all of them, carried by one of them, or (for numbers) their sum.
+fn value_regrouped(single: &str, parts: &[&str]) -> bool {
+    let single = single.trim();
+    if parts.iter().any(|part| part.trim() == single) {
+        return true;
+    }
+    let Ok(target) = single.parse::<f64>() else { return false };
+    let mut sum = 0.0;
+    for part in parts {
+        match part.trim().parse::<f64>() {
+            Ok(v) => sum += v,
+            Err(_) => return false,
+        }
+    }
+    (sum - target).abs() <= 1e-9 * target.abs().max(1.0)
+}
+
+/// Keys of `this` missing from `other`, sorted.
+fn unmatched<'a>(this: &'a HashMap<String, StringRecord>, other: &HashMap<String, StringRecord>) -> Vec<&'a String> {
+    let mut keys: Vec<&String> = this.keys().filter(|key| !other.contains_key(*key)).collect();
+    keys.sort();
+    keys
+}
+
+/// Look for unmatched rows whose key prefixes at least two unmatched keys in the other file,
+/// and whose values are consistent with having been split into (or merged from) those rows.
+///
+/// `columns` pairs the file1 and file2 indexes of the columns to check.
+pub fn detect_splits_merges(
+    map1: &HashMap<String, StringRecord>,
+    map2: &HashMap<String, StringRecord>,
+    columns: &[(usize, usize)],
+) -> Vec<Regrouping> {
+    let unmatched1 = unmatched(map1, map2);
+    let unmatched2 = unmatched(map2, map1);
+
+    let mut findings = Vec::new();
+    for (kind, singles, single_map, candidates, part_map) in [
+        (RegroupKind::Split, &unmatched1, map1, &unmatched2, map2),
+        (RegroupKind::Merge, &unmatched2, map2, &unmatched1, map1),
+    ] {
+        for &key in singles {
+            // Keys extending `key` sort directly after it
+            let start = candidates.partition_point(|candidate| candidate.as_str() <= key.as_str());
+            let parts: Vec<&String> = candidates[start..]
+                .iter()
+                .take_while(|candidate| candidate.starts_with(key.as_str()))
+                .filter(|candidate| extends_key(candidate, key))
+                .copied()
+                .collect();
+            if parts.len() < 2 || columns.is_empty() {
+                continue;
+            }
+
+            let single = &single_map[key];
+            let part_records: Vec<&StringRecord> = parts.iter().map(|part| &part_map[*part]).collect();
+            let matching = columns
+                .iter()
+                .filter(|&&(i1, i2)| {
+                    let (single_index, part_index) = if kind == RegroupKind::Split { (i1, i2) } else { (i2, i1) };
+                    let values: Vec<&str> = part_records.iter().map(|r| r.get(part_index).unwrap_or("")).collect();
+                    value_regrouped(single.get(single_index).unwrap_or(""), &values)
+                })
+                .count();
+
+            if matching as f64 / columns.len() as f64 >= MIN_AGREEMENT {
+                findings.push(Regrouping {
+                    kind,
+                    key: key.clone(),
+                    parts: parts.into_iter().cloned().collect(),
+                    matching,
+                    total: columns.len(),
+                });
+            }
+        }
+    }
+    findings
+}
+
+/// Minimum number of rows in which both columns of a pair changed before it is reported
+const MIN_SHARED_CHANGES: usize = 3;
+/// Share of each column's changed rows that must also change the other column
+const MIN_CO_OCCURRENCE: f64 = 0.8;
+
+/// Two columns that change in the same rows.
+pub struct CoOccurrence {
+    pub column1: String,
+    pub column2: String,
+    /// Rows where both columns changed
+    pub both: usize,
+    /// Rows where each column changed
+    pub changes1: usize,
+    pub changes2: usize,
+}
+
+impl CoOccurrence {
+    /// Share of each column's changed rows in which the other column changed too.
+    pub fn shares(&self) -> (f64, f64) {
+        (self.both as f64 / self.changes1 as f64, self.both as f64 / self.changes2 as f64)
+    }
+}
+
+/// Find column pairs that change together: in more than 80% of the rows where either one
+/// changed (and at least 3 rows), the other changed as well.
+///
+/// Pairs are sorted by how consistently they change together, then by column names.
+pub fn detect_co_occurrences(diffs: &[DiffRow]) -> Vec<CoOccurrence> {
+    let mut columns_by_key: HashMap<&str, Vec<&str>> = HashMap::new();
+    for diff in diffs.iter().filter(|diff| diff.is_value_change()) {
+        columns_by_key.entry(diff.key.as_str()).or_default().push(diff.column.as_str());
+    }
+
+    let mut changes: HashMap<&str, usize> = HashMap::new();
+    let mut pairs: HashMap<(&str, &str), usize> = HashMap::new();
+    for columns in columns_by_key.values_mut() {
+        columns.sort_unstable();
+        columns.dedup();
+        for (i, &column1) in columns.iter().enumerate() {
+            *changes.entry(column1).or_default() += 1;
+            for &column2 in &columns[i + 1..] {
+                *pairs.entry((column1, column2)).or_default() += 1;
+            }
+        }
+    }
+
+    let mut findings: Vec<CoOccurrence> = pairs
+        .into_iter()
+        .filter(|&(_, both)| both >= MIN_SHARED_CHANGES)
+        .map(|((column1, column2), both)| CoOccurrence {
+            column1: column1.to_string(),
+            column2: column2.to_string(),
+            both,
+            changes1: changes[column1],
+            changes2: changes[column2],
+        })
+        .filter(|finding| {
+            let (share1, share2) = finding.shares();
+            share1.min(share2) > MIN_CO_OCCURRENCE
+        })
+        .collect();
+    let consistency = |finding: &CoOccurrence| {
+        let (share1, share2) = finding.shares();
+        share1.min(share2)
+    };
+    findings.sort_by(|a, b| {
+        consistency(b)
+            .total_cmp(&consistency(a))
+            .then_with(|| b.both.cmp(&a.both))
+            .then_with(|| (&a.column1, &a.column2).cmp(&(&b.column1, &b.column2)))
+    });
+    findings
+}
+
+/// How a column's values moved across the changes of one time series.
+///
+/// The sequence is the earliest changed point's file1 value followed by every changed
+/// point's file2 value, in time order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The sequence never falls and rises at least once
+    Increase,
+    /// The sequence never rises and falls at least once
+    Decrease,
+    /// The sequence both rises and falls, so it is not monotonic
+    Oscillating,
+    /// Every value in the sequence is the same, e.g. `1.0` and `1`
+    Constant,
+}
+
+impl Direction {
+    pub fn label(self) -> &'static str {
+        match self {
+            Direction::Increase => "INCREASE",
+            Direction::Decrease => "DECREASE",
+            Direction::Oscillating => "OSCILLATING",
+            Direction::Constant => "CONSTANT",
+        }
+    }
+}
+
+/// The direction of the changes to one column of one series (a key without its time part).
+pub struct Trend {
+    /// Key columns other than the time column, joined with the key separator
+    pub series: String,
+    pub column: String,
+    pub direction: Direction,
+    pub changes: usize,
+    /// Earliest and latest time value with a change
+    pub first: String,
+    pub last: String,
+}
+
+/// A number, or a date or datetime as seconds since the epoch (read as UTC without an offset).
+fn ordinal(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if let Ok(number) = value.parse::<f64>() {
+        return number.is_finite().then_some(number);
+    }
+    let utc = Timezone::Fixed(0);
+    datetime::to_utc(value, Some(&utc))
+        .or_else(|| datetime::to_utc(&format!("{}T00:00", value), Some(&utc)))
+        .map(|(seconds, nanos)| seconds as f64 + f64::from(nanos) / 1e9)
+}
+
+/// One change in a series: its time value and the file1 and file2 values.
+type Point<'a> = (&'a str, f64, f64);
+
+/// Classify the numeric and date changes of each series by direction.
+///
+/// Keys are split on `key_separator`; the part at `time_index` orders the changes and the
+/// remaining parts name the series.
+pub fn detect_trends(diffs: &[DiffRow], time_index: usize, key_separator: &str) -> Vec<Trend> {
+    let mut series: HashMap<(String, &str), Vec<Point>> = HashMap::new();
+    for diff in diffs.iter().filter(|diff| diff.is_value_change()) {
+        let (Some(old), Some(new)) = (ordinal(&diff.file1), ordinal(&diff.file2)) else { continue };
+        let mut parts: Vec<&str> = diff.key.split(key_separator).collect();
+        if time_index >= parts.len() {
+            continue;
+        }
+        let time = parts.remove(time_index);
+        series.entry((parts.join(key_separator), diff.column.as_str())).or_default().push((time, old, new));
+    }
+
+    let mut trends: Vec<Trend> = series
+        .into_iter()
+        .map(|((series, column), mut points)| {
+            // Times that are numbers or dates sort by value, ahead of any that are not
+            points.sort_by(|a, b| match (ordinal(a.0), ordinal(b.0)) {
+                (Some(x), Some(y)) => x.total_cmp(&y),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => a.0.cmp(b.0),
+            });
+            let sequence: Vec<f64> = std::iter::once(points[0].1).chain(points.iter().map(|&(_, _, new)| new)).collect();
+            let rising = sequence.windows(2).any(|pair| pair[1] > pair[0]);
+            let falling = sequence.windows(2).any(|pair| pair[1] < pair[0]);
+            let direction = match (rising, falling) {
+                (true, true) => Direction::Oscillating,
+                (true, false) => Direction::Increase,
+                (false, true) => Direction::Decrease,
+                (false, false) => Direction::Constant,
+            };
+            Trend {
+                series,
+                column: column.to_string(),
+                direction,
+                changes: points.len(),
+                first: points[0].0.to_string(),
+                last: points[points.len() - 1].0.to_string(),
+            }
+        })
+        .collect();
+    trends.sort_by(|a, b| (&a.series, &a.column).cmp(&(&b.series, &b.column)));
+    trends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(key: &str, file1: &str, file2: &str) -> DiffRow {
+        DiffRow { key: key.to_string(), column: "price".to_string(), file1: file1.to_string(), file2: file2.to_string(), ..DiffRow::default() }
+    }
+
+    fn direction(diffs: &[DiffRow]) -> Direction {
+        let trends = detect_trends(diffs, 1, "|");
+        assert_eq!(trends.len(), 1);
+        trends[0].direction
+    }
+
+    #[test]
+    fn points_are_ordered_by_time_value() {
+        // Listed out of order, and "10" sorts before "9" as text
+        let diffs = [change("A|10", "5", "30"), change("A|8", "1", "10"), change("A|9", "2", "20")];
+        assert_eq!(direction(&diffs), Direction::Increase);
+        let trends = detect_trends(&diffs, 1, "|");
+        assert_eq!((trends[0].first.as_str(), trends[0].last.as_str()), ("8", "10"));
+    }
+
+    #[test]
+    fn direction_follows_the_whole_sequence() {
+        // Every change raises its own value, but the series falls back in the middle
+        let diffs = [change("A|1", "1", "10"), change("A|2", "2", "5"), change("A|3", "3", "20")];
+        assert_eq!(direction(&diffs), Direction::Oscillating);
+        let diffs = [change("A|2024-01-02", "9", "8"), change("A|2024-01-01", "10", "9")];
+        assert_eq!(direction(&diffs), Direction::Decrease);
+        assert_eq!(direction(&[change("A|1", "1.0", "1")]), Direction::Constant);
+    }
+
+    #[test]
+    fn columns_changing_together_are_paired() {
+        let mut diffs = Vec::new();
+        for key in ["1", "2", "3"] {
+            for column in ["unit_price", "total", "note"] {
+                diffs.push(DiffRow { key: key.to_string(), column: column.to_string(), file1: "a".into(), file2: "b".into(), ..DiffRow::default() });
+            }
+        }
+        // "note" also changes alone in two more rows, so it changes with the others in only 60%
+        for key in ["4", "5"] {
+            diffs.push(DiffRow { key: key.to_string(), column: "note".to_string(), file1: "a".into(), file2: "b".into(), ..DiffRow::default() });
+        }
+        let findings = detect_co_occurrences(&diffs);
+        let pairs: Vec<(&str, &str, usize)> = findings.iter().map(|f| (f.column1.as_str(), f.column2.as_str(), f.both)).collect();
+        assert_eq!(pairs, [("total", "unit_price", 3)]);
+        assert!(detect_co_occurrences(&diffs[..6]).is_empty());
+    }
+
+    #[test]
+    fn series_are_split_by_the_other_key_parts() {
+        let diffs = [change("A|1", "1", "2"), change("B|1", "2", "1")];
+        let trends = detect_trends(&diffs, 1, "|");
+        let directions: Vec<(&str, Direction)> = trends.iter().map(|trend| (trend.series.as_str(), trend.direction)).collect();
+        assert_eq!(directions, [("A", Direction::Increase), ("B", Direction::Decrease)]);
+    }
+}
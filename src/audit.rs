@@ -0,0 +1,119 @@
+//! The audit log output format: one JSON object per line, ready for SIEM ingestion.
+//!
+//! Every difference becomes a `csvdiff.difference` event carrying who ran the comparison,
+//! where, when, and against which file versions; a final `csvdiff.summary` event records the
+//! totals, so a run without differences still leaves a trace.
+
+use std::error::Error;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::json::Json;
+use crate::output::{Settings, report};
+use crate::{DiffRow, timestamp};
+
+/// Who ran the comparison and on which inputs.
+pub struct AuditContext<'a> {
+    pub files1: &'a [PathBuf],
+    pub files2: &'a [PathBuf],
+    pub key_columns: &'a [String],
+    pub operator: Option<&'a str>,
+}
+
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn username() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn describe_files(paths: &[PathBuf]) -> Json {
+    Json::Array(
+        paths
+            .iter()
+            .map(|path| {
+                let modified = std::fs::metadata(path)
+                    .and_then(|meta| meta.modified())
+                    .map(|time| Json::String(timestamp::iso8601(time)))
+                    .unwrap_or(Json::Null);
+                Json::Object(vec![
+                    ("path".to_string(), Json::String(path.display().to_string())),
+                    ("modified".to_string(), modified),
+                ])
+            })
+            .collect(),
+    )
+}
+
+/// The kind of change a row describes.
+fn diff_kind(diff: &DiffRow) -> &'static str {
+    match (diff.column.as_str(), diff.file1.as_str(), diff.file2.as_str()) {
+        ("[missing in file2]", _, _) => "row_removed",
+        ("[missing in file1]", _, _) => "row_added",
+        (_, "[column not in file1]", _) => "column_added",
+        (_, _, "[column not in file2]") => "column_removed",
+        _ => "value_changed",
+    }
+}
+
+/// Write the differences as an audit log.
+pub fn write_audit_log(path: &str, context: &AuditContext, diffs: &[DiffRow], settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let (mut out, path) = settings.create_file(path)?;
+
+    // Fields shared by every event of this run
+    let common = [
+        ("timestamp".to_string(), Json::String(timestamp::now())),
+        ("host".to_string(), Json::String(hostname())),
+        ("user".to_string(), Json::String(username())),
+        ("operator".to_string(), context.operator.map_or(Json::Null, |name| Json::String(name.to_string()))),
+        ("file1".to_string(), describe_files(context.files1)),
+        ("file2".to_string(), describe_files(context.files2)),
+    ];
+    let event = |name: &str, fields: Vec<(String, Json)>| {
+        let mut entries = vec![("event".to_string(), Json::String(name.to_string()))];
+        entries.extend(common.iter().cloned());
+        entries.extend(fields);
+        Json::Object(entries)
+    };
+
+    let string = |value: &str| Json::String(value.to_string());
+    for diff in diffs {
+        let entry = event(
+            "csvdiff.difference",
+            vec![
+                ("kind".to_string(), string(diff_kind(diff))),
+                ("key".to_string(), string(&diff.key)),
+                ("column".to_string(), string(&diff.column)),
+                ("old".to_string(), string(&diff.file1)),
+                ("new".to_string(), string(&diff.file2)),
+            ],
+        );
+        writeln!(out, "{}", entry)?;
+    }
+
+    let summary = event(
+        "csvdiff.summary",
+        vec![
+            (
+                "key_columns".to_string(),
+                Json::Array(context.key_columns.iter().map(|key| string(key)).collect()),
+            ),
+            ("differences".to_string(), Json::Number(diffs.len().to_string())),
+            ("csvdiff_version".to_string(), string(env!("CARGO_PKG_VERSION"))),
+        ],
+    );
+    writeln!(out, "{}", summary)?;
+    out.finish()?;
+
+    report!(settings, "🧾 Audit log written: {}", path);
+    Ok(())
+}
@@ -0,0 +1,211 @@
+//! `--browse`: page through the differences at a command prompt.
+//!
+//! This is a line-based prompt, not a full-screen terminal UI: each command is typed and
+//! confirmed with Enter, so it works in any terminal and over plain pipes, and there is no
+//! arrow-key navigation. Filtering, paging, a detail view and exporting the current view are
+//! available; type `h` for the command list.
+
+use std::error::Error;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use rust_xlsxwriter::{Format, Workbook};
+
+use crate::output::Settings;
+use crate::{DATA_SHEET, DiffRow, ExcelPalette, create_data_sheet, truncate_string};
+
+const PAGE_SIZE: usize = 20;
+
+const HELP: &str = "\
+Commands:
+  n, Enter       next page
+  p              previous page
+  /TEXT          show only rows whose key or column contains TEXT (/ alone clears)
+  NUMBER         show the full values of row NUMBER
+  export PATH    write the current view to PATH (.csv or .xlsx)
+  h              show this help
+  q              quit";
+
+/// How a difference is colored in the listing.
+fn color_code(diff: &DiffRow) -> &'static str {
+    match diff.column.as_str() {
+        "[missing in file2]" => "31", // red: row removed
+        "[missing in file1]" => "32", // green: row added
+        _ => "33",                    // yellow: value changed
+    }
+}
+
+struct Session<'a> {
+    diffs: &'a [DiffRow],
+    /// Indexes into `diffs` matching the current filter
+    view: Vec<usize>,
+    filter: String,
+    page: usize,
+    max_cell_width: usize,
+    color: bool,
+    palette: ExcelPalette<'a>,
+    /// Field names for exported files
+    settings: &'a Settings,
+}
+
+impl Session<'_> {
+    fn apply_filter(&mut self, filter: &str) {
+        let needle = filter.to_lowercase();
+        self.filter = filter.to_string();
+        self.view = (0..self.diffs.len())
+            .filter(|&i| {
+                let diff = &self.diffs[i];
+                needle.is_empty()
+                    || diff.key.to_lowercase().contains(&needle)
+                    || diff.column.to_lowercase().contains(&needle)
+            })
+            .collect();
+        self.page = 0;
+    }
+
+    fn page_count(&self) -> usize {
+        self.view.len().div_ceil(PAGE_SIZE).max(1)
+    }
+
+    fn render_page(&self) {
+        println!();
+        if self.view.is_empty() {
+            println!("No rows match '{}'", self.filter);
+            return;
+        }
+        let start = self.page * PAGE_SIZE;
+        let width = self.max_cell_width;
+        for (offset, &index) in self.view.iter().enumerate().skip(start).take(PAGE_SIZE) {
+            let diff = &self.diffs[index];
+            let line = format!(
+                "{:>5}  {}  {}: {} → {}",
+                offset + 1,
+                truncate_string(&diff.key, width),
+                truncate_string(&diff.column, width),
+                truncate_string(&diff.file1, width),
+                truncate_string(&diff.file2, width)
+            );
+            if self.color {
+                println!("\x1b[{}m{}\x1b[0m", color_code(diff), line);
+            } else {
+                println!("{}", line);
+            }
+        }
+        let filter = if self.filter.is_empty() { String::new() } else { format!(", filter '{}'", self.filter) };
+        println!(
+            "\nPage {}/{} ({} of {} rows{})",
+            self.page + 1,
+            self.page_count(),
+            self.view.len(),
+            self.diffs.len(),
+            filter
+        );
+    }
+
+    fn render_detail(&self, number: usize) {
+        let Some(diff) = number.checked_sub(1).and_then(|i| self.view.get(i)).map(|&i| &self.diffs[i]) else {
+            println!("No row {} in the current view", number);
+            return;
+        };
+        println!();
+        println!("Key:    {}", diff.key);
+        println!("Column: {}", diff.column);
+        println!("File 1: {}", diff.file1);
+        println!("File 2: {}", diff.file2);
+        if let Some(similarity) = diff.similarity {
+            println!("Similarity: {:.1}%", similarity * 100.0);
+        }
+        if let Some(distance) = diff.edit_distance {
+            println!("Edit distance: {}", distance);
+        }
+        if diff.file1_line.is_some() || diff.file2_line.is_some() {
+            let line = |line: Option<u64>| line.map_or_else(|| "-".to_string(), |line| line.to_string());
+            println!("Lines:  file 1 {}, file 2 {}", line(diff.file1_line), line(diff.file2_line));
+        }
+    }
+
+    fn export(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let rows: Vec<DiffRow> = self.view.iter().map(|&i| self.diffs[i].clone()).collect();
+        if path.to_lowercase().ends_with(".xlsx") {
+            let mut workbook = Workbook::new();
+            let header_format = self.palette.header_format();
+            let title_format = Format::new().set_bold().set_font_size(14);
+            let sheet = workbook.add_worksheet();
+            sheet.set_name(DATA_SHEET)?;
+            create_data_sheet(sheet, &rows, self.palette, Some(&title_format), &header_format, self.settings)?;
+            workbook.save(path)?;
+        } else {
+            let mut writer = csv::Writer::from_path(path)?;
+            writer.write_record(self.settings.field_names())?;
+            for row in &rows {
+                writer.write_record([&row.key, &row.column, &row.file1, &row.file2])?;
+            }
+            writer.flush()?;
+        }
+        println!("✅ Exported {} rows to {}", rows.len(), path);
+        Ok(())
+    }
+}
+
+/// Run the browsing prompt until the user quits or input ends.
+pub fn run(diffs: &[DiffRow], max_cell_width: usize, palette: ExcelPalette, settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let mut session = Session {
+        diffs,
+        view: Vec::new(),
+        filter: String::new(),
+        page: 0,
+        max_cell_width,
+        color: io::stdout().is_terminal(),
+        palette,
+        settings,
+    };
+    session.apply_filter("");
+
+    println!("🔍 Browsing {} differences (type h for help)", diffs.len());
+    session.render_page();
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let Some(line) = lines.next() else { break };
+        let line = line?;
+        let command = line.trim();
+
+        match command {
+            "q" | "quit" => break,
+            "h" | "help" | "?" => println!("{}", HELP),
+            "" | "n" => {
+                if session.page + 1 < session.page_count() {
+                    session.page += 1;
+                    session.render_page();
+                } else {
+                    println!("Already on the last page");
+                }
+            }
+            "p" => {
+                if session.page > 0 {
+                    session.page -= 1;
+                    session.render_page();
+                } else {
+                    println!("Already on the first page");
+                }
+            }
+            _ if command.starts_with('/') => {
+                session.apply_filter(command[1..].trim());
+                session.render_page();
+            }
+            _ if command.starts_with("export ") => {
+                if let Err(e) = session.export(command["export ".len()..].trim()) {
+                    eprintln!("❌ Export failed: {}", e);
+                }
+            }
+            _ => match command.parse::<usize>() {
+                Ok(number) => session.render_detail(number),
+                Err(_) => println!("Unknown command '{}' (type h for help)", command),
+            },
+        }
+    }
+
+    Ok(())
+}
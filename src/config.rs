@@ -0,0 +1,543 @@
+//! TOML option files for `--config` and saved profiles.
+//!
+//! Keys are the long option names (`file1`, `key`, `no-truncate`, ...), so a config file
+//! reads like the command line it replaces:
+//!
+//! ```toml
+//! file1 = "yesterday.csv"
+//! file2 = "today.csv"
+//! key = ["id"]
+//! ignore = ["updated_at"]
+//! no-truncate = true
+//! ```
+//!
+//! Only the subset of TOML these files need is supported: comments, `[table]` headers,
+//! strings, numbers, booleans and single-line arrays.
+
+use std::any::TypeId;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, Command};
+
+use crate::json::quote;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    /// The number exactly as written
+    Number(String),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+pub type Table = Vec<(String, Value)>;
+
+/// A parsed TOML document: top-level keys followed by named tables, in file order.
+#[derive(Debug, Default)]
+pub struct Document {
+    pub root: Table,
+    pub tables: Vec<(String, Table)>,
+}
+
+impl Document {
+    pub fn parse(text: &str) -> Result<Document, String> {
+        let mut document = Document::default();
+        for (index, line) in text.lines().enumerate() {
+            let mut cursor = Cursor { chars: line.chars().collect(), pos: 0 };
+            let error = |message: String| format!("line {}: {}", index + 1, message);
+            cursor.skip_whitespace();
+            if cursor.at_end_of_line() {
+                continue;
+            }
+
+            if cursor.peek() == Some('[') {
+                cursor.pos += 1;
+                cursor.skip_whitespace();
+                let name = cursor.parse_key().map_err(error)?;
+                cursor.skip_whitespace();
+                if cursor.peek() != Some(']') {
+                    return Err(error("expected ']'".into()));
+                }
+                cursor.pos += 1;
+                cursor.expect_end().map_err(error)?;
+                document.tables.push((name, Vec::new()));
+                continue;
+            }
+
+            let key = cursor.parse_key().map_err(error)?;
+            cursor.skip_whitespace();
+            if cursor.peek() != Some('=') {
+                return Err(error(format!("expected '=' after '{}'", key)));
+            }
+            cursor.pos += 1;
+            cursor.skip_whitespace();
+            let value = cursor.parse_value().map_err(error)?;
+            cursor.expect_end().map_err(error)?;
+
+            let table = match document.tables.last_mut() {
+                Some((_, table)) => table,
+                None => &mut document.root,
+            };
+            if table.iter().any(|(existing, _)| *existing == key) {
+                return Err(error(format!("duplicate key '{}'", key)));
+            }
+            table.push((key, value));
+        }
+        Ok(document)
+    }
+
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.iter().find(|(table, _)| table == name).map(|(_, table)| table)
+    }
+}
+
+fn format_key(key: &str) -> String {
+    let bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if bare { key.to_string() } else { quote(key) }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{}", quote(s)),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(Value::to_string).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in &self.root {
+            writeln!(f, "{} = {}", format_key(key), value)?;
+        }
+        for (i, (name, table)) in self.tables.iter().enumerate() {
+            if i > 0 || !self.root.is_empty() {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", format_key(name))?;
+            for (key, value) in table {
+                writeln!(f, "{} = {}", format_key(key), value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Cursor {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn at_end_of_line(&self) -> bool {
+        matches!(self.peek(), None | Some('#'))
+    }
+
+    fn expect_end(&mut self) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.at_end_of_line() { Ok(()) } else { Err("unexpected characters after value".into()) }
+    }
+
+    fn parse_key(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('"') => self.parse_basic_string(),
+            Some('\'') => self.parse_literal_string(),
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                    self.pos += 1;
+                }
+                if start == self.pos {
+                    return Err("expected a key".into());
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => self.parse_basic_string().map(Value::String),
+            Some('\'') => self.parse_literal_string().map(Value::String),
+            Some('[') => self.parse_array(),
+            Some(_) => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_')) {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ if !word.is_empty() && word.replace('_', "").parse::<f64>().is_ok() => Ok(Value::Number(word)),
+                    _ => Err(format!("invalid value '{}'", word)),
+                }
+            }
+            None => Err("missing value".into()),
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Value, String> {
+        self.pos += 1; // '['
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {}
+                _ => return Err("expected ',' or ']' in array (arrays must fit on one line)".into()),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String, String> {
+        self.pos += 1; // opening quote
+        let start = self.pos;
+        while !matches!(self.peek(), Some('\'') | None) {
+            self.pos += 1;
+        }
+        if self.peek().is_none() {
+            return Err("unterminated string".into());
+        }
+        let value = self.chars[start..self.pos].iter().collect();
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String, String> {
+        self.pos += 1; // opening quote
+        let mut out = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    let escaped = self.peek().ok_or("unterminated string")?;
+                    self.pos += 1;
+                    match escaped {
+                        '"' => out.push('"'),
+                        '\\' => out.push('\\'),
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        'u' => {
+                            let hex: String = self.chars.iter().skip(self.pos).take(4).collect();
+                            let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).ok_or("invalid unicode escape")?;
+                            self.pos += 4;
+                            out.push(c);
+                        }
+                        other => return Err(format!("invalid escape '\\{}'", other)),
+                    }
+                }
+                Some(c) => {
+                    out.push(c);
+                    self.pos += 1;
+                }
+                None => return Err("unterminated string".into()),
+            }
+        }
+    }
+}
+
+/// The per-user csvdiff directory, `~/.csvdiff`.
+pub fn data_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .ok_or("Cannot locate the home directory (HOME is not set)")?;
+    Ok(PathBuf::from(home).join(".csvdiff"))
+}
+
+/// Read and parse a TOML file.
+pub fn load(path: &Path) -> Result<Document, Box<dyn Error>> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Document::parse(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+/// Options that make no sense inside an option file.
+const NOT_CONFIGURABLE: &[&str] = &["config", "help", "version", "build-info"];
+
+fn find_long<'a>(command: &'a Command, name: &str) -> Option<&'a Arg> {
+    command.get_arguments().find(|arg| arg.get_long() == Some(name))
+}
+
+fn is_flag(arg: &Arg) -> bool {
+    matches!(arg.get_action(), ArgAction::SetTrue | ArgAction::SetFalse)
+}
+
+fn value_text(value: &Value, key: &str) -> Result<String, String> {
+    match value {
+        Value::String(s) | Value::Number(s) => Ok(s.clone()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Array(_) => Err(format!("'{}' does not accept nested arrays", key)),
+    }
+}
+
+/// Turn option-file entries into command-line arguments, skipping options in `overridden`.
+pub fn to_args(table: &Table, command: &Command, overridden: &HashSet<String>) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for (key, value) in table {
+        let arg = find_long(command, key)
+            .filter(|_| !NOT_CONFIGURABLE.contains(&key.as_str()))
+            .ok_or_else(|| format!("unknown option '{}'", key))?;
+        if overridden.contains(key) {
+            continue;
+        }
+        if is_flag(arg) {
+            match value {
+                Value::Bool(true) => args.push(format!("--{}", key)),
+                Value::Bool(false) => {}
+                _ => return Err(format!("'{}' must be true or false", key)),
+            }
+            continue;
+        }
+        let values = match value {
+            Value::Array(items) => items.iter().map(|item| value_text(item, key)).collect::<Result<Vec<_>, _>>()?,
+            value => vec![value_text(value, key)?],
+        };
+        for value in values {
+            args.push(format!("--{}={}", key, value));
+        }
+    }
+    Ok(args)
+}
+
+/// Walk command-line tokens, yielding each option's long name and its value, if any.
+fn scan_args(argv: &[String], command: &Command) -> Vec<(String, Option<String>)> {
+    let mut found = Vec::new();
+    let mut tokens = argv.iter();
+    while let Some(token) = tokens.next() {
+        let (arg, inline) = if let Some(long) = token.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            (find_long(command, name), inline)
+        } else if let Some(short) = token.strip_prefix('-')
+            && let Some(c) = short.chars().next()
+        {
+            let rest = &short[c.len_utf8()..];
+            let inline = (!rest.is_empty()).then(|| rest.trim_start_matches('=').to_string());
+            (command.get_arguments().find(|arg| arg.get_short() == Some(c)), inline)
+        } else {
+            (None, None)
+        };
+
+        let Some(arg) = arg else { continue };
+        let Some(long) = arg.get_long() else { continue };
+        let value = if is_flag(arg) || !arg.get_action().takes_values() {
+            None
+        } else {
+            inline.or_else(|| tokens.next().cloned())
+        };
+        found.push((long.to_string(), value));
+    }
+    found
+}
+
+/// The long names of the options given in `argv`.
+pub fn given_options(argv: &[String], command: &Command) -> HashSet<String> {
+    scan_args(argv, command).into_iter().map(|(name, _)| name).collect()
+}
+
+/// Capture the options given in `argv` as option-file entries.
+pub fn from_args(argv: &[String], command: &Command) -> Table {
+    let mut table: Table = Vec::new();
+    for (name, value) in scan_args(argv, command) {
+        if NOT_CONFIGURABLE.contains(&name.as_str()) {
+            continue;
+        }
+        let Some(arg) = find_long(command, &name) else { continue };
+        let repeatable = matches!(arg.get_action(), ArgAction::Append);
+        let value = match value {
+            None => Value::Bool(true),
+            Some(text) if !repeatable && text.replace('_', "").parse::<f64>().is_ok() => Value::Number(text),
+            Some(text) => Value::String(text),
+        };
+
+        match table.iter_mut().find(|(key, _)| *key == name) {
+            Some((_, Value::Array(items))) => items.push(value),
+            // A repeated single-value option: the last one wins, as on the command line
+            Some((_, existing)) => *existing = value,
+            None if repeatable => table.push((name, Value::Array(vec![value]))),
+            None => table.push((name, value)),
+        }
+    }
+    table
+}
+
+/// The TOML type an option's values take, judged by its clap value parser.
+fn value_type(arg: &Arg) -> &'static str {
+    let parser = arg.get_value_parser().type_id();
+    let integers = [TypeId::of::<usize>(), TypeId::of::<u64>(), TypeId::of::<u32>(), TypeId::of::<u16>()];
+    if integers.iter().any(|id| parser == *id) {
+        "integer"
+    } else if parser == TypeId::of::<f64>() {
+        "number"
+    } else {
+        "string"
+    }
+}
+
+/// A commented TOML template listing every configurable option of `command`, for
+/// `csvdiff config-schema`.
+pub fn schema(command: &Command) -> String {
+    let mut out = String::new();
+    out.push_str("# csvdiff configuration, for use with --config FILE\n");
+    out.push_str("# Keys are the long option names; options given on the command line take precedence.\n");
+    out.push_str("# Uncomment and edit the options you need.\n");
+
+    for arg in command.get_arguments() {
+        let Some(long) = arg.get_long() else { continue };
+        if arg.is_hide_set() || NOT_CONFIGURABLE.contains(&long) {
+            continue;
+        }
+        out.push('\n');
+        if let Some(help) = arg.get_help() {
+            for line in help.to_string().lines() {
+                out.push_str(&format!("# {}\n", line));
+            }
+        }
+
+        let repeatable = matches!(arg.get_action(), ArgAction::Append);
+        let kind = if is_flag(arg) { "boolean" } else { value_type(arg) };
+        let choices: Vec<String> = arg.get_possible_values().iter().map(|v| v.get_name().to_string()).collect();
+        let mut type_line = if repeatable { format!("array of {}s", kind) } else { kind.to_string() };
+        // Flags list true/false as their possible values
+        if !choices.is_empty() && !is_flag(arg) {
+            type_line.push_str(&format!(", one of: {}", choices.join(", ")));
+        }
+        out.push_str(&format!("# Type: {}\n", type_line));
+
+        let value = |text: &str| match kind {
+            "boolean" => Value::Bool(text == "true"),
+            "integer" | "number" => Value::Number(text.to_string()),
+            _ => Value::String(text.to_string()),
+        };
+        let defaults: Vec<Value> = arg.get_default_values().iter().map(|v| value(&v.to_string_lossy())).collect();
+        let example = match defaults.as_slice() {
+            [] => {
+                let name = arg
+                    .get_value_names()
+                    .and_then(|names| names.first())
+                    .map_or_else(|| long.to_uppercase(), |name| name.to_string());
+                let placeholder = if kind == "string" { quote(&format!("<{}>", name)) } else { format!("<{}>", name) };
+                if repeatable { format!("[{}]", placeholder) } else { placeholder }
+            }
+            [default] if !repeatable => {
+                out.push_str(&format!("# Default: {}\n", default));
+                default.to_string()
+            }
+            defaults => {
+                let array = Value::Array(defaults.to_vec());
+                out.push_str(&format!("# Default: {}\n", array));
+                array.to_string()
+            }
+        };
+        out.push_str(&format!("# {} = {}\n", format_key(long), example));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+    use crate::Args;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn documents_parse_and_print_back() {
+        let text = "# yesterday's export\nfile1 = \"old.csv\" # trailing comment\nkey = ['id', \"region\"]\nno-truncate = true\n\n[\"nightly run\"]\ndiff-limit = 1_000\n";
+        let document = Document::parse(text).unwrap();
+        assert_eq!(document.root[0], ("file1".to_string(), Value::String("old.csv".into())));
+        assert_eq!(document.root[1].1, Value::Array(vec![Value::String("id".into()), Value::String("region".into())]));
+        assert_eq!(document.root[2].1, Value::Bool(true));
+        assert_eq!(document.table("nightly run").unwrap()[0].1, Value::Number("1_000".into()));
+        let printed = "file1 = \"old.csv\"\nkey = [\"id\", \"region\"]\nno-truncate = true\n\n[\"nightly run\"]\ndiff-limit = 1_000\n";
+        assert_eq!(document.to_string(), printed);
+        assert_eq!(Document::parse(printed).unwrap().to_string(), printed);
+    }
+
+    #[test]
+    fn strings_unescape_basic_but_not_literal_quotes() {
+        let document = Document::parse(r#"a = "tab\t\"q\" \u00e9"
+b = 'C:\data\'"#).unwrap();
+        assert_eq!(document.root[0].1, Value::String("tab\t\"q\" é".into()));
+        assert_eq!(document.root[1].1, Value::String(r"C:\data\".into()));
+        assert!(Document::parse(r#"a = "\x""#).unwrap_err().contains("invalid escape"));
+        assert!(Document::parse(r#"a = "\u00""#).unwrap_err().contains("unicode"));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(Document::parse("a = 1\na = 2").unwrap_err(), "line 2: duplicate key 'a'");
+        assert!(Document::parse("key = [\"id\"\n, \"region\"]").unwrap_err().starts_with("line 1: expected ',' or ']'"));
+        assert!(Document::parse("a = \"open").unwrap_err().contains("unterminated"));
+        assert!(Document::parse("a = yes").unwrap_err().contains("invalid value 'yes'"));
+        assert!(Document::parse("a = 1 2").unwrap_err().contains("after value"));
+        assert!(Document::parse("[table").unwrap_err().contains("expected ']'"));
+        assert!(Document::parse("= 1").unwrap_err().contains("expected a key"));
+    }
+
+    #[test]
+    fn tables_become_arguments_unless_overridden() {
+        let command = Args::command();
+        let table = Document::parse("key = [\"id\", \"region\"]\nno-truncate = true\nsort-keys = false\ndiff-limit = 10").unwrap().root;
+        let args = to_args(&table, &command, &HashSet::new()).unwrap();
+        assert_eq!(args, strings(&["--key=id", "--key=region", "--no-truncate", "--diff-limit=10"]));
+        let overridden = HashSet::from(["diff-limit".to_string()]);
+        assert_eq!(to_args(&table, &command, &overridden).unwrap().len(), 3);
+
+        let bad = |text: &str| to_args(&Document::parse(text).unwrap().root, &command, &HashSet::new()).unwrap_err();
+        assert_eq!(bad("no-such-option = 1"), "unknown option 'no-such-option'");
+        assert_eq!(bad("config = \"other.toml\""), "unknown option 'config'");
+        assert_eq!(bad("no-truncate = 1"), "'no-truncate' must be true or false");
+    }
+
+    #[test]
+    fn arguments_become_tables() {
+        let command = Args::command();
+        let argv = strings(&["--key", "id", "--key=region", "-q", "--diff-limit", "10", "--config", "x.toml", "--ignore-index", "3"]);
+        let table = from_args(&argv, &command);
+        let expected: Table = vec![
+            ("key".to_string(), Value::Array(vec![Value::String("id".into()), Value::String("region".into())])),
+            ("quiet".to_string(), Value::Bool(true)),
+            ("diff-limit".to_string(), Value::Number("10".into())),
+            ("ignore-index".to_string(), Value::Array(vec![Value::String("3".into())])),
+        ];
+        assert_eq!(table, expected);
+        assert_eq!(to_args(&table, &command, &HashSet::new()).unwrap(), strings(&["--key=id", "--key=region", "--quiet", "--diff-limit=10", "--ignore-index=3"]));
+        assert!(given_options(&argv, &command).contains("config"));
+    }
+}
@@ -0,0 +1,549 @@
+//! `--timezone-normalize`: compare datetimes as instants, whatever offset they are written in.
+//!
+//! ISO 8601 / RFC 3339 datetimes such as `2024-01-15T10:00:00-05:00` or `2024-01-15 15:00Z`
+//! are parsed by hand. Datetimes without an offset are read in the `--assume-timezone` zone,
+//! looked up in the system time zone database (`/usr/share/zoneinfo`, or `$TZDIR`), so
+//! daylight saving rules come from the same source the operating system uses.
+
+use std::path::PathBuf;
+
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+
+use crate::timestamp::{civil_from_days, days_from_civil, days_in_month, is_leap_year};
+
+/// The zone datetimes are converted to before comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum NormalizeTimezone {
+    #[cfg_attr(feature = "cli", value(name = "UTC"))]
+    Utc,
+}
+
+/// A point in time: seconds since the Unix epoch and the fraction of the second.
+pub type UtcTime = (i64, u32);
+
+/// A parsed datetime: its wall-clock time as seconds since the epoch, and its offset from UTC
+/// in seconds when one was written.
+struct Parsed {
+    local: i64,
+    nanos: u32,
+    offset: Option<i32>,
+}
+
+fn digits(text: &str, len: usize) -> Option<(i64, &str)> {
+    let (head, rest) = (text.get(..len)?, &text[len..]);
+    if !head.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((head.parse().ok()?, rest))
+}
+
+/// Parse `YYYY-MM-DD[T ]HH:MM[:SS[.fraction]]` with an optional `Z`, `UTC` or `±HH[:MM]` offset.
+///
+/// A leap second (`23:59:60`) is read as the first second of the next minute; anything else,
+/// including other date layouts, returns `None` and is compared as text.
+fn parse(value: &str) -> Option<Parsed> {
+    let value = value.trim();
+    let (year, rest) = digits(value, 4)?;
+    let (month, rest) = digits(rest.strip_prefix('-')?, 2)?;
+    let (day, rest) = digits(rest.strip_prefix('-')?, 2)?;
+    let rest = rest.strip_prefix(['T', 't', ' '])?;
+    let (hour, rest) = digits(rest, 2)?;
+    let (minute, mut rest) = digits(rest.strip_prefix(':')?, 2)?;
+    let mut second = 0;
+    if let Some(after) = rest.strip_prefix(':') {
+        (second, rest) = digits(after, 2)?;
+    }
+    let mut nanos = 0;
+    if let Some(after) = rest.strip_prefix(['.', ',']) {
+        let len = after.bytes().take_while(u8::is_ascii_digit).count();
+        if len == 0 || len > 9 {
+            return None;
+        }
+        nanos = after[..len].parse::<u32>().ok()? * 10u32.pow(9 - len as u32);
+        rest = &after[len..];
+    }
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let offset = match rest.trim_start() {
+        "" => None,
+        "Z" | "z" | "UTC" => Some(0),
+        offset => Some(parse_offset(offset)?),
+    };
+    let local = days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second;
+    Some(Parsed { local, nanos, offset })
+}
+
+/// Parse `±HH`, `±HHMM` or `±HH:MM` into seconds east of UTC.
+fn parse_offset(text: &str) -> Option<i32> {
+    let (sign, rest) = match text.as_bytes().first()? {
+        b'+' => (1, &text[1..]),
+        b'-' => (-1, &text[1..]),
+        _ => return None,
+    };
+    let (hours, rest) = digits(rest, 2)?;
+    let minutes = match rest.strip_prefix(':').unwrap_or(rest) {
+        "" => 0,
+        minutes => digits(minutes, 2).filter(|(_, rest)| rest.is_empty())?.0,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3_600 + minutes * 60) as i32)
+}
+
+/// Whether a value is a datetime in the layout [`to_utc`] reads, with or without an offset.
+pub fn is_datetime(value: &str) -> bool {
+    parse(value).is_some()
+}
+
+/// Convert a datetime to UTC, reading one without an offset in `assume` (if given).
+pub fn to_utc(value: &str, assume: Option<&Timezone>) -> Option<UtcTime> {
+    let parsed = parse(value)?;
+    let utc = match parsed.offset {
+        Some(offset) => parsed.local - i64::from(offset),
+        None => assume?.local_to_utc(parsed.local),
+    };
+    Some((utc, parsed.nanos))
+}
+
+/// A zone for `--assume-timezone`: a fixed offset or a time zone database entry.
+#[derive(Debug, Clone)]
+pub enum Timezone {
+    Fixed(i32),
+    Zone(Zone),
+}
+
+impl Timezone {
+    /// `UTC`, a fixed offset such as `+05:30`, or an IANA name such as `America/New_York`.
+    pub fn load(name: &str) -> Result<Timezone, String> {
+        if matches!(name, "UTC" | "Z" | "GMT") {
+            return Ok(Timezone::Fixed(0));
+        }
+        if let Some(offset) = parse_offset(name) {
+            return Ok(Timezone::Fixed(offset));
+        }
+        if name.is_empty() || name.starts_with('/') || name.split('/').any(|part| part == "..") {
+            return Err(format!("invalid time zone '{}'", name));
+        }
+        let dir = std::env::var_os("TZDIR").map_or_else(|| PathBuf::from("/usr/share/zoneinfo"), PathBuf::from);
+        let path = dir.join(name);
+        let data = std::fs::read(&path)
+            .map_err(|e| format!("unknown time zone '{}' ({}: {})", name, path.display(), e))?;
+        Zone::parse(&data)
+            .map(Timezone::Zone)
+            .ok_or_else(|| format!("{} is not a valid time zone file", path.display()))
+    }
+
+    /// The UTC offset in seconds at a point in time.
+    fn offset_at(&self, utc: i64) -> i32 {
+        match self {
+            Timezone::Fixed(offset) => *offset,
+            Timezone::Zone(zone) => zone.offset_at(utc),
+        }
+    }
+
+    /// The instant a wall-clock time denotes in this zone.
+    ///
+    /// A time repeated when clocks go back resolves to its first (daylight saving) occurrence;
+    /// a time skipped when clocks go forward is read with the offset in effect before the jump.
+    fn local_to_utc(&self, local: i64) -> i64 {
+        let offsets: Vec<i32> = match self {
+            Timezone::Fixed(offset) => vec![*offset],
+            Timezone::Zone(zone) => zone.all_offsets(),
+        };
+        offsets
+            .iter()
+            .map(|&offset| local - i64::from(offset))
+            .filter(|&utc| i64::from(self.offset_at(utc)) == local - utc)
+            .min()
+            .unwrap_or_else(|| local - i64::from(self.offset_at(local - 86_400)))
+    }
+}
+
+/// A parsed TZif file: historical transitions plus the POSIX rule for later times.
+#[derive(Debug, Clone)]
+pub struct Zone {
+    transitions: Vec<i64>,
+    /// Index into `offsets` for each transition
+    types: Vec<usize>,
+    offsets: Vec<i32>,
+    /// Offset before the first transition: the first standard-time type
+    initial: i32,
+    rule: Option<Rule>,
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<usize> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize)
+}
+
+impl Zone {
+    fn parse(data: &[u8]) -> Option<Zone> {
+        if data.get(..4)? != b"TZif" {
+            return None;
+        }
+        let counts = |at: usize| -> Option<[usize; 6]> {
+            let mut counts = [0; 6];
+            for (i, count) in counts.iter_mut().enumerate() {
+                *count = be_u32(data, at + 20 + i * 4)?;
+            }
+            Some(counts)
+        };
+        // isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt
+        let [isut, isstd, leap, time, kinds, chars] = counts(0)?;
+        let v1_len = time * 5 + kinds * 6 + chars + leap * 8 + isstd + isut;
+
+        // Version 2+ files repeat the data with 64-bit times, followed by the POSIX rule
+        let (start, time_size, [isut, isstd, leap, time, kinds, chars]) = if *data.get(4)? >= b'2' {
+            let header = 44 + v1_len;
+            (header + 44, 8, counts(header)?)
+        } else {
+            (44, 4, [isut, isstd, leap, time, kinds, chars])
+        };
+
+        let read_time = |at: usize| -> Option<i64> {
+            let bytes = data.get(at..at + time_size)?;
+            Some(if time_size == 8 {
+                i64::from_be_bytes(bytes.try_into().ok()?)
+            } else {
+                i64::from(i32::from_be_bytes(bytes.try_into().ok()?))
+            })
+        };
+        let transitions = (0..time).map(|i| read_time(start + i * time_size)).collect::<Option<Vec<_>>>()?;
+        let types_at = start + time * time_size;
+        let types = (0..time).map(|i| data.get(types_at + i).map(|&t| t as usize)).collect::<Option<Vec<_>>>()?;
+        let info_at = types_at + time;
+        let mut offsets = Vec::with_capacity(kinds);
+        let mut initial = None;
+        for i in 0..kinds {
+            let at = info_at + i * 6;
+            let offset = i32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?);
+            let dst = *data.get(at + 4)? != 0;
+            if !dst && initial.is_none() {
+                initial = Some(offset);
+            }
+            offsets.push(offset);
+        }
+        if offsets.is_empty() || types.iter().any(|&t| t >= offsets.len()) {
+            return None;
+        }
+
+        let end = info_at + kinds * 6 + chars + leap * (time_size + 4) + isstd + isut;
+        let rule = (time_size == 8)
+            .then(|| data.get(end..))
+            .flatten()
+            .and_then(|footer| std::str::from_utf8(footer).ok())
+            .and_then(|footer| footer.trim_matches('\n').lines().next().map(str::to_string))
+            .and_then(|footer| Rule::parse(&footer));
+
+        Some(Zone {
+            transitions,
+            types,
+            initial: initial.unwrap_or(offsets[0]),
+            offsets,
+            rule,
+        })
+    }
+
+    fn offset_at(&self, utc: i64) -> i32 {
+        let index = self.transitions.partition_point(|&t| t <= utc);
+        match (&self.rule, index) {
+            (Some(rule), i) if i == self.transitions.len() => rule.offset_at(utc),
+            (_, 0) => self.initial,
+            (_, i) => self.offsets[self.types[i - 1]],
+        }
+    }
+
+    fn all_offsets(&self) -> Vec<i32> {
+        let mut offsets = self.offsets.clone();
+        if let Some(rule) = &self.rule {
+            offsets.push(rule.std_offset);
+            offsets.extend(rule.dst.as_ref().map(|dst| dst.offset));
+        }
+        offsets.sort_unstable();
+        offsets.dedup();
+        offsets
+    }
+}
+
+/// A POSIX TZ rule such as `EST5EDT,M3.2.0,M11.1.0`, with offsets in seconds east of UTC.
+#[derive(Debug, Clone)]
+struct Rule {
+    std_offset: i32,
+    dst: Option<DstRule>,
+}
+
+#[derive(Debug, Clone)]
+struct DstRule {
+    offset: i32,
+    start: (RuleDay, i64),
+    end: (RuleDay, i64),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RuleDay {
+    /// `Jn`: day 1 to 365, never counting February 29
+    Julian(i64),
+    /// `n`: zero-based day of the year, counting February 29
+    Zero(i64),
+    /// `Mm.w.d`: weekday `d` (0 = Sunday) of week `w` (5 = last) of month `m`
+    Month(i64, i64, i64),
+}
+
+impl RuleDay {
+    /// Days since the epoch of this rule's date in `year`.
+    fn days(self, year: i64) -> i64 {
+        let jan1 = days_from_civil(year, 1, 1);
+        match self {
+            RuleDay::Julian(n) => jan1 + n - 1 + i64::from(is_leap_year(year) && n >= 60),
+            RuleDay::Zero(n) => jan1 + n,
+            RuleDay::Month(month, week, weekday) => {
+                let first = days_from_civil(year, month, 1);
+                // 1970-01-01 was a Thursday
+                let first_weekday = (first + 4).rem_euclid(7);
+                let mut day = 1 + (weekday - first_weekday).rem_euclid(7) + (week - 1) * 7;
+                while day > days_in_month(year, month) {
+                    day -= 7;
+                }
+                first + day - 1
+            }
+        }
+    }
+}
+
+struct RuleParser<'a> {
+    text: &'a str,
+}
+
+impl RuleParser<'_> {
+    fn name(&mut self) -> Option<()> {
+        let len = if let Some(quoted) = self.text.strip_prefix('<') {
+            quoted.find('>')? + 2
+        } else {
+            self.text.bytes().take_while(u8::is_ascii_alphabetic).count()
+        };
+        if len < 3 {
+            return None;
+        }
+        self.text = &self.text[len..];
+        Some(())
+    }
+
+    fn number(&mut self) -> Option<i64> {
+        let len = self.text.bytes().take_while(u8::is_ascii_digit).count();
+        let (number, rest) = self.text.split_at(len);
+        self.text = rest;
+        number.parse().ok()
+    }
+
+    /// `[+-]hh[:mm[:ss]]` in seconds
+    fn time(&mut self) -> Option<i64> {
+        let sign = match self.text.as_bytes().first() {
+            Some(b'-') => -1,
+            Some(b'+') => 1,
+            _ => 0,
+        };
+        if sign != 0 {
+            self.text = &self.text[1..];
+        }
+        let mut seconds = self.number()? * 3_600;
+        for unit in [60, 1] {
+            match self.text.strip_prefix(':') {
+                Some(rest) => {
+                    self.text = rest;
+                    seconds += self.number()? * unit;
+                }
+                None => break,
+            }
+        }
+        Some(if sign < 0 { -seconds } else { seconds })
+    }
+
+    fn day(&mut self) -> Option<RuleDay> {
+        if let Some(rest) = self.text.strip_prefix('J') {
+            self.text = rest;
+            return Some(RuleDay::Julian(self.number()?));
+        }
+        let Some(rest) = self.text.strip_prefix('M') else {
+            return Some(RuleDay::Zero(self.number()?));
+        };
+        self.text = rest;
+        let month = self.number()?;
+        self.text = self.text.strip_prefix('.')?;
+        let week = self.number()?;
+        self.text = self.text.strip_prefix('.')?;
+        let weekday = self.number()?;
+        ((1..=12).contains(&month) && (1..=5).contains(&week) && (0..=6).contains(&weekday))
+            .then_some(RuleDay::Month(month, week, weekday))
+    }
+
+    /// `,date[/time]`, the time defaulting to 02:00
+    fn transition(&mut self) -> Option<(RuleDay, i64)> {
+        self.text = self.text.strip_prefix(',')?;
+        let day = self.day()?;
+        let time = match self.text.strip_prefix('/') {
+            Some(rest) => {
+                self.text = rest;
+                self.time()?
+            }
+            None => 7_200,
+        };
+        Some((day, time))
+    }
+}
+
+impl Rule {
+    fn parse(text: &str) -> Option<Rule> {
+        let mut parser = RuleParser { text };
+        parser.name()?;
+        // POSIX offsets count hours west of UTC
+        let std_offset = -parser.time()? as i32;
+        if parser.text.is_empty() {
+            return Some(Rule { std_offset, dst: None });
+        }
+        parser.name()?;
+        let offset = if parser.text.starts_with(',') { std_offset + 3_600 } else { -parser.time()? as i32 };
+        let start = parser.transition()?;
+        let end = parser.transition()?;
+        parser.text.is_empty().then_some(Rule {
+            std_offset,
+            dst: Some(DstRule { offset, start, end }),
+        })
+    }
+
+    fn offset_at(&self, utc: i64) -> i32 {
+        let Some(dst) = &self.dst else { return self.std_offset };
+        let (year, _, _) = civil_from_days((utc + i64::from(self.std_offset)).div_euclid(86_400));
+        // Transition times are wall-clock times in the offset in effect before each change
+        let start = dst.start.0.days(year) * 86_400 + dst.start.1 - i64::from(self.std_offset);
+        let end = dst.end.0.days(year) * 86_400 + dst.end.1 - i64::from(dst.offset);
+        let in_dst = if start < end {
+            (start..end).contains(&utc)
+        } else {
+            // Southern hemisphere: daylight saving time spans the new year
+            !(end..start).contains(&utc)
+        };
+        if in_dst { dst.offset } else { self.std_offset }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A TZif file with `(time, type)` transitions and `(offset, is_dst)` types. Version 2
+    /// files hold an empty version 1 block, then 64-bit times and the POSIX rule `footer`.
+    fn tzif(version: u8, transitions: &[(i64, u8)], types: &[(i32, bool)], footer: &str) -> Vec<u8> {
+        let header = |counts: [usize; 6]| {
+            let mut header = b"TZif".to_vec();
+            header.push(version);
+            header.extend([0; 15]);
+            for count in counts {
+                header.extend((count as u32).to_be_bytes());
+            }
+            header
+        };
+        let wide = version >= b'2';
+        let mut data = if wide { header([0; 6]) } else { Vec::new() };
+        data.extend(header([0, 0, 0, transitions.len(), types.len(), 0]));
+        for &(time, _) in transitions {
+            if wide {
+                data.extend(time.to_be_bytes());
+            } else {
+                data.extend((time as i32).to_be_bytes());
+            }
+        }
+        data.extend(transitions.iter().map(|&(_, kind)| kind));
+        for &(offset, dst) in types {
+            data.extend(offset.to_be_bytes());
+            data.extend([u8::from(dst), 0]);
+        }
+        if wide {
+            data.extend(format!("\n{}\n", footer).bytes());
+        }
+        data
+    }
+
+    /// Seconds since the epoch of a UTC (or wall-clock) time.
+    fn at(year: i64, month: i64, day: i64, hour: i64, minute: i64) -> i64 {
+        days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60
+    }
+
+    #[test]
+    fn tzif_transitions_give_way_to_the_footer_rule() {
+        let data = tzif(b'2', &[(1_000, 1), (2_000, 0)], &[(3_600, false), (7_200, true)], "CET-1CEST,M3.5.0,M10.5.0/3");
+        let zone = Zone::parse(&data).unwrap();
+        assert_eq!(zone.offset_at(0), 3_600);
+        assert_eq!(zone.offset_at(1_500), 7_200);
+        assert_eq!(zone.offset_at(at(2024, 1, 15, 12, 0)), 3_600);
+        assert_eq!(zone.offset_at(at(2024, 7, 1, 12, 0)), 7_200);
+        assert!(Zone::parse(b"TZxx").is_none());
+        assert!(Zone::parse(&data[..60]).is_none());
+    }
+
+    #[test]
+    fn version_1_files_keep_the_last_offset() {
+        let zone = Zone::parse(&tzif(0, &[(1_000, 1)], &[(3_600, false), (7_200, true)], "")).unwrap();
+        assert!(zone.rule.is_none());
+        assert_eq!(zone.offset_at(999), 3_600);
+        assert_eq!(zone.offset_at(at(2024, 1, 15, 12, 0)), 7_200);
+    }
+
+    #[test]
+    fn posix_rules_switch_at_wall_clock_time() {
+        let rule = Rule::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        // 02:00 EST on the second Sunday of March, and 02:00 EDT on the first Sunday of November
+        let (start, end) = (at(2024, 3, 10, 7, 0), at(2024, 11, 3, 6, 0));
+        assert_eq!(rule.offset_at(start - 1), -18_000);
+        assert_eq!(rule.offset_at(start), -14_400);
+        assert_eq!(rule.offset_at(end - 1), -14_400);
+        assert_eq!(rule.offset_at(end), -18_000);
+
+        let sydney = Rule::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(sydney.offset_at(at(2024, 1, 15, 0, 0)), 39_600);
+        assert_eq!(sydney.offset_at(at(2024, 7, 15, 0, 0)), 36_000);
+
+        assert_eq!(Rule::parse("<+0530>-5:30").unwrap().offset_at(0), 19_800);
+        assert!(Rule::parse("EST5EDT,M13.2.0,M11.1.0").is_none());
+        assert!(Rule::parse("EST5EDT,M3.2.0").is_none());
+    }
+
+    #[test]
+    fn rule_days_count_february_29_as_documented() {
+        assert_eq!(RuleDay::Julian(60).days(2024), days_from_civil(2024, 3, 1));
+        assert_eq!(RuleDay::Zero(59).days(2024), days_from_civil(2024, 2, 29));
+        // The fifth Sunday means the last one
+        assert_eq!(RuleDay::Month(3, 5, 0).days(2024), days_from_civil(2024, 3, 31));
+    }
+
+    #[test]
+    fn repeated_and_skipped_wall_clock_times() {
+        let zone = Timezone::Zone(Zone {
+            transitions: Vec::new(),
+            types: Vec::new(),
+            offsets: vec![-18_000],
+            initial: -18_000,
+            rule: Rule::parse("EST5EDT,M3.2.0,M11.1.0"),
+        });
+        // 01:30 happens twice when clocks go back; the daylight saving one comes first
+        let repeated = at(2024, 11, 3, 1, 30);
+        assert_eq!(zone.local_to_utc(repeated), repeated + 14_400);
+        // 02:30 never happens when clocks go forward; it is read as standard time
+        let skipped = at(2024, 3, 10, 2, 30);
+        assert_eq!(zone.local_to_utc(skipped), skipped + 18_000);
+    }
+
+    #[test]
+    fn written_offsets_win_over_the_assumed_zone() {
+        let utc = to_utc("2024-01-15 15:00Z", None).unwrap();
+        assert_eq!(to_utc("2024-01-15T10:00:00-05:00", None), Some(utc));
+        assert_eq!(to_utc("2024-01-15T10:00:00-05:00", Some(&Timezone::Fixed(3_600))), Some(utc));
+        assert_eq!(to_utc("2024-01-15 10:00", None), None);
+        assert_eq!(to_utc("2024-01-15 10:00", Some(&Timezone::Fixed(-18_000))), Some(utc));
+        assert_eq!(to_utc("2024-01-15 15:00:00.25Z", None), Some((utc.0, 250_000_000)));
+        assert!(!is_datetime("2024-02-30 10:00"));
+    }
+}
@@ -0,0 +1,119 @@
+//! The json and yaml output formats: the whole diff as one document.
+//!
+//! Both formats share the same structure, a `summary` object with the inputs and totals and a
+//! `diffs` list with one entry per difference, so a consumer can switch between them without
+//! changing how it reads the result. A `metadata` object between them holds the
+//! `--report-author`, `--report-date` and `--report-tag` values and, with `--infer-types`, each
+//! file's column types.
+
+use std::error::Error;
+use std::io::Write;
+use std::path::PathBuf;
+
+use csv::StringRecord;
+
+use crate::json::Json;
+use crate::output::{Settings, report};
+use crate::types::ColumnTypes;
+use crate::{DiffRow, KeyCounts, yaml};
+
+/// The inputs a document describes.
+pub struct DocumentSource<'a> {
+    pub files1: &'a [PathBuf],
+    pub files2: &'a [PathBuf],
+    pub key_columns: &'a [String],
+    /// Rows compared by key, for the share of keys with differences
+    pub total_keys: usize,
+    /// File1 and file2 column types, with --infer-types
+    pub column_types: Option<&'a (ColumnTypes, ColumnTypes)>,
+    /// --report-author, --report-date and --report-tag
+    pub author: Option<&'a str>,
+    pub date: Option<&'a str>,
+    pub tag: Option<&'a str>,
+}
+
+fn string(value: &str) -> Json {
+    Json::String(value.to_string())
+}
+
+fn number(value: usize) -> Json {
+    Json::Number(value.to_string())
+}
+
+fn paths(paths: &[PathBuf]) -> Json {
+    Json::Array(paths.iter().map(|path| string(&path.display().to_string())).collect())
+}
+
+fn types(types: &ColumnTypes) -> Json {
+    Json::Object(types.iter().map(|(column, column_type)| (column.clone(), string(column_type.label()))).collect())
+}
+
+fn entry(diff: &DiffRow, settings: &Settings) -> Json {
+    let values = [&diff.key, &diff.column, &diff.file1, &diff.file2];
+    Json::Object(settings.field_names().iter().zip(values).map(|(field, value)| (field.to_string(), string(value))).collect())
+}
+
+/// Build the document for a set of differences.
+pub fn build(source: &DocumentSource, diffs: &[DiffRow], settings: &Settings) -> Json {
+    let counts = KeyCounts::of(diffs);
+    let summary = Json::Object(vec![
+        ("file1".to_string(), paths(source.files1)),
+        ("file2".to_string(), paths(source.files2)),
+        ("key_columns".to_string(), Json::Array(source.key_columns.iter().map(|key| string(key)).collect())),
+        ("total_differences".to_string(), number(diffs.len())),
+        ("total_keys".to_string(), number(source.total_keys)),
+        ("keys_with_differences".to_string(), number(counts.keys)),
+        ("rows_missing_in_file1".to_string(), number(counts.missing_in_file1)),
+        ("rows_missing_in_file2".to_string(), number(counts.missing_in_file2)),
+        ("rows_with_value_changes".to_string(), number(counts.changed)),
+    ]);
+    let entries = diffs.iter().map(|diff| entry(diff, settings)).collect();
+    let mut metadata: Vec<(String, Json)> = [("author", source.author), ("date", source.date), ("tag", source.tag)]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.to_string(), string(value?))))
+        .collect();
+    if let Some((types1, types2)) = source.column_types {
+        metadata.push(("file1_column_types".to_string(), types(types1)));
+        metadata.push(("file2_column_types".to_string(), types(types2)));
+    }
+    let mut document = vec![("summary".to_string(), summary)];
+    if !metadata.is_empty() {
+        document.push(("metadata".to_string(), Json::Object(metadata)));
+    }
+    document.push(("diffs".to_string(), Json::Array(entries)));
+    Json::Object(document)
+}
+
+/// Write a value as JSON, or as YAML with `as_yaml`. Returns the path written.
+fn write_value(path: &str, value: &Json, as_yaml: bool, settings: &Settings) -> Result<String, Box<dyn Error>> {
+    let (mut out, path) = settings.create_file(path)?;
+    if as_yaml {
+        write!(out, "{}", yaml::to_string(value))?;
+    } else {
+        writeln!(out, "{}", value)?;
+    }
+    out.finish()?;
+    Ok(path)
+}
+
+/// `--split-output`: whole rows as a `rows` list of column-to-value objects.
+pub fn write_rows(path: &str, headers: &[String], records: &[&StringRecord], as_yaml: bool, settings: &Settings) -> Result<String, Box<dyn Error>> {
+    let rows = records
+        .iter()
+        .map(|record| Json::Object(headers.iter().cloned().zip(record.iter().map(string)).collect()))
+        .collect();
+    write_value(path, &Json::Object(vec![("rows".to_string(), Json::Array(rows))]), as_yaml, settings)
+}
+
+/// `--split-output`: changed values as a `diffs` list, in the same form as the full document.
+pub fn write_changes(path: &str, diffs: &[&DiffRow], as_yaml: bool, settings: &Settings) -> Result<String, Box<dyn Error>> {
+    let entries = diffs.iter().map(|diff| entry(diff, settings)).collect();
+    write_value(path, &Json::Object(vec![("diffs".to_string(), Json::Array(entries))]), as_yaml, settings)
+}
+
+/// Write the document as JSON, or as YAML with `as_yaml`.
+pub fn write_document(path: &str, source: &DocumentSource, diffs: &[DiffRow], as_yaml: bool, settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let path = write_value(path, &build(source, diffs, settings), as_yaml, settings)?;
+    report!(settings, "🧾 {} diff written: {}", if as_yaml { "YAML" } else { "JSON" }, path);
+    Ok(())
+}
@@ -0,0 +1,258 @@
+//! `--auto-detect-encoding` and `--line-ending`: read text inputs that are not UTF-8 or do not
+//! end their lines with `\n`.
+//!
+//! The encoding is guessed from the first 64 KB of each file: a byte order mark decides it
+//! outright, text that decodes as UTF-8 is UTF-8, a pattern of zero bytes gives away BOM-less
+//! UTF-16, and anything else is taken as Windows-1252, the usual encoding of spreadsheet
+//! exports on Windows. Files are transcoded to UTF-8 in memory before parsing; bytes that are
+//! invalid in the detected encoding become U+FFFD.
+//!
+//! The CSV reader already ends records at `\n`, `\r\n` or a lone `\r`. A declared line ending
+//! is converted to `\n` instead and records then end at `\n` only, so the other line break
+//! characters are kept as data. Both settings travel to the readers in a [`Decoding`].
+
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+use clap::ValueEnum;
+
+/// Bytes examined to detect a file's encoding
+const SAMPLE_SIZE: u64 = 64 * 1024;
+
+/// Bytes examined to detect a file's line endings
+const LINE_ENDING_SAMPLE_SIZE: u64 = 1024;
+
+/// Bytes shown on each side of an invalid UTF-8 sequence
+const HEX_DUMP_CONTEXT: usize = 8;
+
+/// Line endings of text inputs (--line-ending).
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Any of `\n`, `\r\n` and `\r` ends a record
+    Auto,
+    /// Unix: `\n`
+    Lf,
+    /// Windows: `\r\n`
+    Crlf,
+    /// Classic Mac OS: `\r`
+    Cr,
+}
+
+/// How text inputs are turned into UTF-8 with `\n` line endings before parsing.
+#[derive(Debug, Clone, Copy)]
+pub struct Decoding {
+    /// `--auto-detect-encoding`: transcode inputs that are not UTF-8
+    pub auto_detect: bool,
+    pub line_ending: LineEnding,
+}
+
+impl Decoding {
+    /// The record terminator for the CSV reader: line endings have been converted to `\n`
+    /// when one was declared.
+    pub fn terminator(self) -> csv::Terminator {
+        match self.line_ending {
+            LineEnding::Auto => csv::Terminator::CRLF,
+            _ => csv::Terminator::Any(b'\n'),
+        }
+    }
+
+    /// Convert the declared line ending to `\n`.
+    fn normalize_line_endings(self, text: String) -> String {
+        match self.line_ending {
+            LineEnding::Auto | LineEnding::Lf => text,
+            LineEnding::Crlf => text.replace("\r\n", "\n"),
+            LineEnding::Cr => text.replace('\r', "\n"),
+        }
+    }
+
+    /// Whether inputs need to be read whole and converted before parsing.
+    fn converting(self) -> bool {
+        self.auto_detect || !matches!(self.line_ending, LineEnding::Auto | LineEnding::Lf)
+    }
+
+    /// Read a text input as UTF-8, transcoding it under `--auto-detect-encoding` and
+    /// converting a declared `--line-ending`.
+    pub fn read_to_string(self, path: &Path) -> io::Result<String> {
+        let text = if self.auto_detect {
+            let encoding = detect(path)?;
+            decode(&std::fs::read(path)?, encoding)
+        } else {
+            String::from_utf8(std::fs::read(path)?).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, invalid_utf8_message(path, e.as_bytes()).unwrap_or_default())
+            })?
+        };
+        Ok(self.normalize_line_endings(text))
+    }
+
+    /// Open a text input for streaming; inputs that need transcoding or line ending
+    /// conversion are converted in memory first.
+    pub fn open(self, path: &Path) -> io::Result<Box<dyn Read>> {
+        if !self.converting() {
+            return Ok(Box::new(File::open(path)?));
+        }
+        Ok(Box::new(Cursor::new(self.read_to_string(path)?.into_bytes())))
+    }
+}
+
+/// The line endings used in the first [`LINE_ENDING_SAMPLE_SIZE`] bytes of a file: `LF`,
+/// `CRLF`, `CR` or `mixed`, or `None` when the sample has no line break.
+pub fn detect_line_endings(path: &Path) -> io::Result<Option<&'static str>> {
+    let mut sample = Vec::new();
+    File::open(path)?.take(LINE_ENDING_SAMPLE_SIZE).read_to_end(&mut sample)?;
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+    let mut bytes = sample.iter().peekable();
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            b'\r' if bytes.peek() == Some(&&b'\n') => {
+                bytes.next();
+                crlf += 1;
+            }
+            // A \r ending the sample may be the first half of a \r\n
+            b'\r' if bytes.peek().is_some() => cr += 1,
+            b'\n' => lf += 1,
+            _ => {}
+        }
+    }
+    Ok(match (lf > 0, crlf > 0, cr > 0) {
+        (false, false, false) => None,
+        (true, false, false) => Some("LF"),
+        (false, true, false) => Some("CRLF"),
+        (false, false, true) => Some("CR"),
+        _ => Some("mixed"),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Only 7-bit bytes, which read the same in every encoding detected here
+    Ascii,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+}
+
+impl Encoding {
+    pub fn label(self) -> &'static str {
+        match self {
+            Encoding::Ascii => "ASCII",
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Windows1252 => "Windows-1252",
+        }
+    }
+
+    /// Whether two files in these encodings can hold differently encoded text.
+    pub fn conflicts_with(self, other: Encoding) -> bool {
+        self != other && self != Encoding::Ascii && other != Encoding::Ascii
+    }
+}
+
+/// Guess the encoding of the start of a file. `complete` says the sample is the whole file,
+/// so a multi-byte sequence cut off at its end is invalid rather than truncated.
+fn detect_bytes(sample: &[u8], complete: bool) -> Encoding {
+    if sample.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return Encoding::Utf8;
+    }
+    if sample.starts_with(&[0xFF, 0xFE]) {
+        return Encoding::Utf16Le;
+    }
+    if sample.starts_with(&[0xFE, 0xFF]) {
+        return Encoding::Utf16Be;
+    }
+
+    // Mostly-ASCII text in UTF-16 has a zero in every other byte
+    let zeros_at = |parity: usize| sample.iter().skip(parity).step_by(2).filter(|&&b| b == 0).count();
+    let pairs = sample.len() / 2;
+    if pairs > 0 {
+        let (even, odd) = (zeros_at(0), zeros_at(1));
+        if odd * 10 > pairs * 3 && even * 10 < pairs {
+            return Encoding::Utf16Le;
+        }
+        if even * 10 > pairs * 3 && odd * 10 < pairs {
+            return Encoding::Utf16Be;
+        }
+    }
+
+    if sample.is_ascii() {
+        return Encoding::Ascii;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => Encoding::Utf8,
+        Err(e) if e.error_len().is_none() && !complete => Encoding::Utf8,
+        Err(_) => Encoding::Windows1252,
+    }
+}
+
+/// Detect the encoding of a file from its first [`SAMPLE_SIZE`] bytes.
+pub fn detect(path: &Path) -> io::Result<Encoding> {
+    let mut sample = Vec::new();
+    File::open(path)?.take(SAMPLE_SIZE).read_to_end(&mut sample)?;
+    Ok(detect_bytes(&sample, (sample.len() as u64) < SAMPLE_SIZE))
+}
+
+/// Windows-1252 characters for bytes 0x80–0x9F; the five unassigned bytes map to the C1
+/// control characters of the same value, as web browsers do.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Transcode a whole file's bytes to UTF-8, dropping any byte order mark.
+fn decode(bytes: &[u8], encoding: Encoding) -> String {
+    match encoding {
+        Encoding::Ascii | Encoding::Utf8 => {
+            String::from_utf8_lossy(bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes)).into_owned()
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let bom: &[u8] = if encoding == Encoding::Utf16Le { &[0xFF, 0xFE] } else { &[0xFE, 0xFF] };
+            let bytes = bytes.strip_prefix(bom).unwrap_or(bytes);
+            let units = bytes.chunks(2).map(|pair| match (pair, encoding) {
+                ([low, high], Encoding::Utf16Le) => u16::from_le_bytes([*low, *high]),
+                ([high, low], _) => u16::from_be_bytes([*high, *low]),
+                // A dangling odd byte at the end
+                _ => 0xFFFD,
+            });
+            char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
+        }
+        Encoding::Windows1252 => bytes
+            .iter()
+            .map(|&b| match b {
+                0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                _ => b as char,
+            })
+            .collect(),
+    }
+}
+
+/// Describe the first invalid UTF-8 sequence in a file read as UTF-8, with a hex dump of the
+/// bytes around it, or `None` when the bytes are valid UTF-8.
+fn invalid_utf8_message(path: &Path, bytes: &[u8]) -> Option<String> {
+    let error = std::str::from_utf8(bytes).err()?;
+    let offset = error.valid_up_to();
+    let end = offset + error.error_len().unwrap_or(bytes.len() - offset);
+    let start = offset.saturating_sub(HEX_DUMP_CONTEXT);
+    let dump: Vec<String> = bytes[start..(end + HEX_DUMP_CONTEXT).min(bytes.len())]
+        .iter()
+        .zip(start..)
+        .map(|(byte, at)| if (offset..end).contains(&at) { format!("[{:02x}]", byte) } else { format!("{:02x}", byte) })
+        .collect();
+    let line = bytes[..offset].iter().filter(|&&b| b == b'\n').count() + 1;
+    Some(format!(
+        "File '{}' contains invalid UTF-8 at byte offset {} (line {}): {}. \
+         Try --auto-detect-encoding, which also reads Windows-1252 (Latin-1) and UTF-16 files.",
+        path.display(),
+        offset,
+        line,
+        dump.join(" ")
+    ))
+}
+
+/// The invalid UTF-8 in a file, described as in [`invalid_utf8_message`], for errors from
+/// readers that only point at a record.
+pub fn describe_invalid_utf8(path: &Path) -> io::Result<Option<String>> {
+    Ok(invalid_utf8_message(path, &std::fs::read(path)?))
+}
+
@@ -0,0 +1,143 @@
+//! `--error-format`: how a failed run reports its error, and the exit code for each kind.
+//!
+//! Errors travel as `Box<dyn Error>` like everywhere else in the crate; the few a script may
+//! want to tell apart are raised as [`CsvDiffError`] values, and any other error is classified
+//! by its type when it reaches `main`: I/O errors exit with 3, CSV and workbook errors
+//! with 4, and everything else (a bad option or column name) with 2.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use clap::ValueEnum;
+
+use crate::json::Json;
+
+/// How a failed run reports its error on stderr (--error-format).
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// `Error: ` followed by the message
+    Text,
+    /// One JSON object with the error kind, message and the file and column involved
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub enum CsvDiffError {
+    /// A `--key` column missing from an input, with the columns suggested as keys instead
+    KeyColumnNotFound { file: String, column: String, suggestions: Vec<String> },
+    /// An input file that does not exist
+    FileNotFound { file: String },
+    /// Reading or writing a file failed
+    Io { message: String },
+    /// An input could not be parsed
+    Parse { message: String },
+    /// Any other problem with the options or inputs
+    Usage { message: String },
+}
+
+impl CsvDiffError {
+    /// Classify an error raised anywhere in a run.
+    pub fn classify(error: &(dyn Error + 'static)) -> CsvDiffError {
+        let message = error.to_string();
+        if let Some(error) = error.downcast_ref::<CsvDiffError>() {
+            return error.clone();
+        }
+        if let Some(error) = error.downcast_ref::<io::Error>() {
+            // Input that is not valid UTF-8
+            if error.kind() == io::ErrorKind::InvalidData {
+                return CsvDiffError::Parse { message };
+            }
+            return CsvDiffError::Io { message };
+        }
+        if let Some(error) = error.downcast_ref::<csv::Error>() {
+            return match error.kind() {
+                csv::ErrorKind::Io(_) => CsvDiffError::Io { message },
+                _ => CsvDiffError::Parse { message },
+            };
+        }
+        if error.is::<zip::result::ZipError>() {
+            return CsvDiffError::Parse { message };
+        }
+        CsvDiffError::Usage { message }
+    }
+
+    /// The `error` field of the JSON form.
+    fn name(&self) -> &'static str {
+        match self {
+            CsvDiffError::KeyColumnNotFound { .. } => "KeyColumnNotFound",
+            CsvDiffError::FileNotFound { .. } => "FileNotFound",
+            CsvDiffError::Io { .. } => "IoError",
+            CsvDiffError::Parse { .. } => "ParseError",
+            CsvDiffError::Usage { .. } => "UsageError",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CsvDiffError::KeyColumnNotFound { .. } | CsvDiffError::Usage { .. } => 2,
+            CsvDiffError::FileNotFound { .. } | CsvDiffError::Io { .. } => 3,
+            CsvDiffError::Parse { .. } => 4,
+        }
+    }
+
+    /// `{"error": ..., "message": ...}` plus the `file` and `column` involved, when known, and
+    /// any suggested key columns.
+    pub fn to_json(&self) -> Json {
+        let mut entries = vec![
+            ("error".to_string(), Json::String(self.name().to_string())),
+            ("message".to_string(), Json::String(self.to_string())),
+        ];
+        match self {
+            CsvDiffError::KeyColumnNotFound { file, column, suggestions } => {
+                entries.push(("file".to_string(), Json::String(file.clone())));
+                entries.push(("column".to_string(), Json::String(column.clone())));
+                entries.push(("suggestions".to_string(), Json::Array(suggestions.iter().cloned().map(Json::String).collect())));
+            }
+            CsvDiffError::FileNotFound { file } => entries.push(("file".to_string(), Json::String(file.clone()))),
+            _ => {}
+        }
+        Json::Object(entries)
+    }
+}
+
+impl fmt::Display for CsvDiffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvDiffError::KeyColumnNotFound { file, column, .. } => write!(f, "Key column '{}' not found in {}", column, file),
+            CsvDiffError::FileNotFound { file } => write!(f, "File not found: {}", file),
+            CsvDiffError::Io { message } | CsvDiffError::Parse { message } | CsvDiffError::Usage { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for CsvDiffError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_errors_carry_their_suggestions() {
+        let error = CsvDiffError::KeyColumnNotFound {
+            file: "a.csv".to_string(),
+            column: "idd".to_string(),
+            suggestions: vec!["id".to_string(), "name".to_string()],
+        };
+        assert_eq!(error.exit_code(), 2);
+        assert_eq!(
+            error.to_json().to_string(),
+            r#"{"error":"KeyColumnNotFound","message":"Key column 'idd' not found in a.csv","file":"a.csv","column":"idd","suggestions":["id","name"]}"#
+        );
+    }
+
+    #[test]
+    fn errors_are_classified_by_type() {
+        let io = io::Error::new(io::ErrorKind::NotFound, "gone");
+        assert_eq!(CsvDiffError::classify(&io).exit_code(), 3);
+        let utf8 = io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8");
+        assert_eq!(CsvDiffError::classify(&utf8).exit_code(), 4);
+        let usage: Box<dyn Error> = "bad option".into();
+        assert_eq!(CsvDiffError::classify(usage.as_ref()).to_json().to_string(), r#"{"error":"UsageError","message":"bad option"}"#);
+    }
+}
@@ -0,0 +1,109 @@
+//! `--expected-diffs`: check a run against the differences saved by an earlier one, like a
+//! snapshot test for a data pipeline.
+//!
+//! The expected file is a json output document (or any object with a `diffs` list in that
+//! form). The comparison ignores order but not values: each `key`, `column`, `file1`, `file2`
+//! entry must occur as often in the run as in the file. `--update-expected` rewrites the file
+//! from the current run instead.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use crate::DiffRow;
+use crate::error::CsvDiffError;
+use crate::json::Json;
+use crate::output::{DIFF_FIELDS, Settings};
+
+/// One difference: key, column, file1 value and file2 value.
+pub type Entry = [String; 4];
+
+/// The differences only on one side of the check.
+pub struct Mismatch {
+    /// Expected but not produced by this run
+    pub missing: Vec<Entry>,
+    /// Produced by this run but not expected
+    pub unexpected: Vec<Entry>,
+}
+
+impl Mismatch {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+fn entry_of(diff: &DiffRow) -> Entry {
+    [diff.key.clone(), diff.column.clone(), diff.file1.clone(), diff.file2.clone()]
+}
+
+fn parse_entry(value: &Json, index: usize, settings: &Settings) -> Result<Entry, String> {
+    let invalid = || format!("diffs[{}] is not a {} object of strings", index, DIFF_FIELDS.join("/"));
+    let Json::Object(fields) = value else {
+        return Err(invalid());
+    };
+    let field = |name: &'static str| match fields.iter().find(|(key, _)| key == settings.field_name(name)) {
+        Some((_, Json::String(value))) => Ok(value.clone()),
+        _ => Err(invalid()),
+    };
+    Ok([field("key")?, field("column")?, field("file1")?, field("file2")?])
+}
+
+/// Read the differences from an expected diffs file.
+pub fn load(path: &str, settings: &Settings) -> Result<Vec<Entry>, Box<dyn Error>> {
+    if !Path::new(path).exists() {
+        return Err(CsvDiffError::FileNotFound { file: path.to_string() }.into());
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let document = Json::parse(&text).map_err(|e| CsvDiffError::Parse {
+        message: format!("{} is not a JSON diff: {}", path, e),
+    })?;
+    let entries = match &document {
+        Json::Object(fields) => fields.iter().find(|(key, _)| key == "diffs").map(|(_, diffs)| diffs),
+        _ => None,
+    };
+    let Some(Json::Array(entries)) = entries else {
+        return Err(CsvDiffError::Parse {
+            message: format!("{} has no \"diffs\" list; save one with --output-format json or --update-expected", path),
+        }
+        .into());
+    };
+    let entries = entries.iter().enumerate().map(|(index, value)| parse_entry(value, index, settings)).collect::<Result<_, _>>();
+    entries.map_err(|message| CsvDiffError::Parse { message: format!("{}: {}", path, message) }.into())
+}
+
+/// Compare the expected differences with the ones a run produced, ignoring order.
+pub fn check(expected: Vec<Entry>, diffs: &[DiffRow]) -> Mismatch {
+    // How many times each expected entry is still to be matched
+    let mut counts: HashMap<Entry, usize> = HashMap::new();
+    for entry in expected {
+        *counts.entry(entry).or_default() += 1;
+    }
+    let mut unexpected = Vec::new();
+    for entry in diffs.iter().map(entry_of) {
+        match counts.get_mut(&entry) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => unexpected.push(entry),
+        }
+    }
+    let mut missing: Vec<Entry> = counts
+        .into_iter()
+        .flat_map(|(entry, count)| std::iter::repeat_n(entry, count))
+        .collect();
+    missing.sort();
+    unexpected.sort();
+    Mismatch { missing, unexpected }
+}
+
+/// A diff of the diffs: `-` for expected differences that are gone, `+` for new ones.
+pub fn render(mismatch: &Mismatch) -> String {
+    let line = |sign: char, [key, column, file1, file2]: &Entry| {
+        format!("   {} key '{}', column '{}': '{}' → '{}'", sign, key, column, file1, file2)
+    };
+    mismatch
+        .missing
+        .iter()
+        .map(|entry| line('-', entry))
+        .chain(mismatch.unexpected.iter().map(|entry| line('+', entry)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,61 @@
+//! `--fingerprint`: detect whether the diff itself changed since the previous run.
+//!
+//! The fingerprint is the SHA-256 of every difference, sorted by key and column. It is stored
+//! in `~/.csvdiff/fingerprints/<name>.txt`, where the name is the profile being run, the
+//! `--fingerprint-name` value, or one derived from the inputs.
+
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::sha256::Sha256;
+use crate::{DiffRow, config, describe_paths};
+
+/// Hash the differences independently of the order they were found in.
+pub fn compute(diffs: &[DiffRow]) -> String {
+    let mut sorted: Vec<&DiffRow> = diffs.iter().collect();
+    sorted.sort_by(|a, b| (&a.key, &a.column).cmp(&(&b.key, &b.column)));
+
+    let mut hasher = Sha256::new();
+    for diff in sorted {
+        // Length prefixes keep field boundaries unambiguous
+        for field in [&diff.key, &diff.column, &diff.file1, &diff.file2] {
+            hasher.update(&(field.len() as u64).to_be_bytes());
+            hasher.update(field.as_bytes());
+        }
+    }
+    hasher.finish_hex()
+}
+
+/// A stable name for a comparison run without a profile or --fingerprint-name.
+pub fn default_name(files1: &[PathBuf], files2: &[PathBuf], key_columns: &[String], key_separator: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}\0{}\0{}", describe_paths(files1), describe_paths(files2), key_columns.join(key_separator)).as_bytes());
+    let stem = |paths: &[PathBuf]| {
+        paths
+            .first()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    format!("{}-vs-{}-{}", stem(files1), stem(files2), &hasher.finish_hex()[..12])
+}
+
+/// Compare `fingerprint` with the one stored under `name`, then store it.
+///
+/// Returns whether it changed; a first run counts as a change.
+pub fn check_and_store(name: &str, fingerprint: &str) -> Result<bool, Box<dyn Error>> {
+    let file_name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    let dir = config::data_dir()?.join("fingerprints");
+    let path = dir.join(format!("{}.txt", file_name));
+
+    let previous = std::fs::read_to_string(&path).ok();
+    let changed = previous.as_deref().map(str::trim) != Some(fingerprint);
+    if changed {
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(&path, format!("{}\n", fingerprint))?;
+    }
+    Ok(changed)
+}
@@ -0,0 +1,625 @@
+//! Excel (.xlsx) input, read straight from the workbook's XML parts.
+//!
+//! An .xlsx file is a ZIP archive of XML documents: `xl/workbook.xml` lists the sheets,
+//! `xl/sharedStrings.xml` holds the text of string cells and each worksheet stores its cells
+//! row by row. Only the parts needed to recover cell values are parsed. The first row of a
+//! sheet is its header; cells formatted as dates are read as ISO 8601 dates or datetimes, so
+//! they compare equal to a CSV export of the same sheet.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use csv::{Position, StringRecord};
+use zip::ZipArchive;
+
+use crate::error::CsvDiffError;
+use crate::{RecordIter, timestamp};
+
+/// The last column a worksheet can have, XFD
+const MAX_COLUMN: usize = 16_383;
+
+/// One XML tag or the text between two tags.
+enum Event<'a> {
+    Start { name: &'a str, attributes: &'a str, empty: bool },
+    End(&'a str),
+    Text(&'a str),
+}
+
+/// A minimal pull parser: enough for the well-formed XML Excel writes, without DTDs. A tag,
+/// comment or CDATA section left open at the end of the input is an error, after which the
+/// parser stops.
+struct Events<'a> {
+    xml: &'a str,
+    position: usize,
+}
+
+/// The tag name without its namespace prefix.
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+impl<'a> Events<'a> {
+    /// Report an unterminated construct and end the iteration.
+    fn unterminated(&mut self, what: &str) -> Option<Result<Event<'a>, String>> {
+        let error = format!("unterminated {} at byte {}", what, self.position);
+        self.position = self.xml.len();
+        Some(Err(error))
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Result<Event<'a>, String>;
+
+    fn next(&mut self) -> Option<Result<Event<'a>, String>> {
+        loop {
+            let rest = &self.xml[self.position..];
+            if rest.is_empty() {
+                return None;
+            }
+            if !rest.starts_with('<') {
+                let end = rest.find('<').unwrap_or(rest.len());
+                self.position += end;
+                return Some(Ok(Event::Text(&rest[..end])));
+            }
+            if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let Some(end) = cdata.find("]]>") else {
+                    return self.unterminated("CDATA section");
+                };
+                self.position += "<![CDATA[".len() + end + "]]>".len();
+                return Some(Ok(Event::Text(&cdata[..end])));
+            }
+            // Declarations, processing instructions and comments carry no cell data
+            if let Some((_, close)) = [("<?", "?>"), ("<!--", "-->"), ("<!", ">")].iter().find(|(open, _)| rest.starts_with(open)) {
+                let Some(end) = rest.find(close) else {
+                    return self.unterminated("declaration or comment");
+                };
+                self.position += end + close.len();
+                continue;
+            }
+
+            let Some(end) = rest.find('>') else {
+                return self.unterminated("tag");
+            };
+            self.position += end + 1;
+            let tag = &rest[1..end];
+            if let Some(name) = tag.strip_prefix('/') {
+                return Some(Ok(Event::End(local_name(name.trim()))));
+            }
+            let (tag, empty) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+            return Some(Ok(Event::Start { name: local_name(name), attributes, empty }));
+        }
+    }
+}
+
+fn events(xml: &str) -> Events<'_> {
+    Events { xml, position: 0 }
+}
+
+/// The value of attribute `name` (namespace prefix ignored), with entities decoded.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    while let Some(eq) = rest.find('=') {
+        let key = local_name(rest[..eq].trim());
+        let after = rest[eq + 1..].trim_start();
+        let quote = after.chars().next()?;
+        let value_end = after[1..].find(quote)? + 1;
+        if key == name {
+            return Some(unescape(&after[1..value_end]));
+        }
+        rest = &after[value_end + 1..];
+    }
+    None
+}
+
+/// Decode XML entities and Excel's `_xHHHH_` escapes for control characters.
+fn unescape(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        let after = &rest[amp + 1..];
+        let Some(semicolon) = after.find(';') else {
+            decoded.push_str(&rest[amp..]);
+            rest = "";
+            break;
+        };
+        let entity = &after[..semicolon];
+        let character = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse::<u32>))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(character) => decoded.push(character),
+            None => decoded.push_str(&rest[amp..amp + semicolon + 2]),
+        }
+        rest = &after[semicolon + 1..];
+    }
+    decoded.push_str(rest);
+
+    if !decoded.contains("_x") {
+        return decoded;
+    }
+    let mut result = String::with_capacity(decoded.len());
+    let mut rest = decoded.as_str();
+    while let Some(start) = rest.find("_x") {
+        result.push_str(&rest[..start]);
+        let escaped = rest.get(start + 2..start + 7).filter(|e| e.ends_with('_'));
+        match escaped.and_then(|e| u32::from_str_radix(&e[..4], 16).ok()).and_then(char::from_u32) {
+            Some(character) => {
+                result.push(character);
+                rest = &rest[start + 7..];
+            }
+            None => {
+                result.push_str("_x");
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Zero-based column of a cell reference such as `AB12`, or `None` when the reference has no
+/// column letters or lies beyond the last column, XFD.
+fn column_index(reference: &str) -> Option<usize> {
+    let letters = reference.bytes().take_while(u8::is_ascii_alphabetic);
+    let mut index = 0usize;
+    for letter in letters {
+        index = index.checked_mul(26)?.checked_add(usize::from(letter.to_ascii_uppercase() - b'A' + 1))?;
+        if index > MAX_COLUMN + 1 {
+            return None;
+        }
+    }
+    index.checked_sub(1)
+}
+
+/// An XML error in a part of a workbook.
+fn xml_error(path: &str, part: &str, message: String) -> Box<dyn Error> {
+    CsvDiffError::Parse { message: format!("{} is not a valid .xlsx file: {}: {}", path, part, message) }.into()
+}
+
+/// Built-in number formats that display dates or times.
+fn is_builtin_date_format(id: u32) -> bool {
+    matches!(id, 14..=22 | 27..=36 | 45..=47 | 50..=58)
+}
+
+/// Whether a custom number format such as `yyyy-mm-dd` displays a date or time: it has date
+/// or time letters outside quoted text, escapes and `[...]` sections.
+fn is_date_format_code(code: &str) -> bool {
+    let mut in_quotes = false;
+    let mut in_brackets = false;
+    let mut escaped = false;
+    for c in code.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '[' if !in_quotes => in_brackets = true,
+            ']' if !in_quotes => in_brackets = false,
+            'y' | 'm' | 'd' | 'h' | 's' | 'Y' | 'M' | 'D' | 'H' | 'S' if !in_quotes && !in_brackets => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Format an Excel date serial as `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS` or (for a time of day
+/// alone) `HH:MM:SS`.
+fn format_date_serial(serial: f64, date1904: bool) -> Option<String> {
+    if !serial.is_finite() || serial < 0.0 {
+        return None;
+    }
+    let seconds = (serial * 86_400.0).round() as i64;
+    let (days, seconds) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    let time = format!("{:02}:{:02}:{:02}", seconds / 3_600, seconds / 60 % 60, seconds % 60);
+    if days == 0 && !date1904 {
+        return Some(time);
+    }
+    // The 1900 system counts a nonexistent 1900-02-29 as serial 60, as Lotus 1-2-3 did: serials
+    // before it name the day after a plain count from 1899-12-30, and 60 is shown as Excel shows it
+    let date = if !date1904 && days == 60 {
+        "1900-02-29".to_string()
+    } else {
+        // Serial 25569 is 1970-01-01 in the 1900 system; the 1904 system starts 1462 days later
+        let days = if !date1904 && days < 60 { days + 1 } else { days };
+        let unix_days = days - 25_569 + if date1904 { 1_462 } else { 0 };
+        let (year, month, day) = timestamp::civil_from_days(unix_days);
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    };
+    Some(if seconds == 0 { date } else { format!("{} {}", date, time) })
+}
+
+/// Which worksheet of a workbook to read (`--sheet-name`, `--sheet-index`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Sheet {
+    /// Zero-based position in workbook order
+    Index(usize),
+    Name(String),
+}
+
+impl Default for Sheet {
+    fn default() -> Sheet {
+        Sheet::Index(0)
+    }
+}
+
+/// An .xlsx workbook opened for reading.
+pub struct ExcelReader {
+    /// The workbook's path, for error messages
+    path: String,
+    archive: ZipArchive<File>,
+    /// Sheet names with their part names inside the archive, in workbook order
+    sheets: Vec<(String, String)>,
+    shared_strings: Vec<String>,
+    /// Whether each cell style (`s` attribute) displays a date
+    date_styles: Vec<bool>,
+    date1904: bool,
+}
+
+impl ExcelReader {
+    pub fn open(path: &Path) -> Result<ExcelReader, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let mut archive = ZipArchive::new(file).map_err(|e| format!("{} is not a valid .xlsx file: {}", path.display(), e))?;
+
+        let workbook = read_part(&mut archive, "xl/workbook.xml")?
+            .ok_or_else(|| format!("{} is not an .xlsx workbook (no xl/workbook.xml)", path.display()))?;
+        let path_name = path.display().to_string();
+        let relationships = read_part(&mut archive, "xl/_rels/workbook.xml.rels")?.unwrap_or_default();
+        let mut targets: HashMap<String, String> = HashMap::new();
+        for event in events(&relationships) {
+            let event = event.map_err(|e| xml_error(&path_name, "xl/_rels/workbook.xml.rels", e))?;
+            if let Event::Start { name: "Relationship", attributes, .. } = event
+                && let (Some(id), Some(target)) = (attribute(attributes, "Id"), attribute(attributes, "Target"))
+            {
+                targets.insert(id, target);
+            }
+        }
+
+        let mut sheets = Vec::new();
+        let mut date1904 = false;
+        for event in events(&workbook) {
+            match event.map_err(|e| xml_error(&path_name, "xl/workbook.xml", e))? {
+                Event::Start { name: "workbookPr", attributes, .. } => {
+                    date1904 = matches!(attribute(attributes, "date1904").as_deref(), Some("1" | "true"));
+                }
+                Event::Start { name: "sheet", attributes, .. } => {
+                    let name = attribute(attributes, "name").unwrap_or_default();
+                    let Some(target) = attribute(attributes, "id").and_then(|id| targets.get(&id)) else { continue };
+                    // Targets are relative to xl/ unless they start at the archive root
+                    let part = match target.strip_prefix('/') {
+                        Some(absolute) => absolute.to_string(),
+                        None => format!("xl/{}", target),
+                    };
+                    sheets.push((name, part));
+                }
+                _ => {}
+            }
+        }
+        if sheets.is_empty() {
+            return Err(format!("{} has no worksheets", path.display()).into());
+        }
+
+        let shared_strings = match read_part(&mut archive, "xl/sharedStrings.xml")? {
+            Some(xml) => parse_shared_strings(&xml).map_err(|e| xml_error(&path_name, "xl/sharedStrings.xml", e))?,
+            None => Vec::new(),
+        };
+        let date_styles = match read_part(&mut archive, "xl/styles.xml")? {
+            Some(xml) => parse_date_styles(&xml).map_err(|e| xml_error(&path_name, "xl/styles.xml", e))?,
+            None => Vec::new(),
+        };
+
+        Ok(ExcelReader { path: path_name, archive, sheets, shared_strings, date_styles, date1904 })
+    }
+
+    /// Sheet names in workbook order.
+    pub fn sheet_names(&self) -> Vec<&str> {
+        self.sheets.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Read the selected sheet: its first row as the header and every later row as a record
+    /// padded to the header's width. Records carry their sheet row number as their line.
+    pub fn read_sheet(&mut self, sheet: &Sheet) -> Result<(StringRecord, RecordIter), Box<dyn Error>> {
+        let available = || self.sheet_names().iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(", ");
+        let found = match sheet {
+            Sheet::Index(index) => self.sheets.get(*index).ok_or_else(|| {
+                format!("--sheet-index {} is out of range: {} has {} sheets ({})", index, self.path, self.sheets.len(), available())
+            }),
+            Sheet::Name(name) => self
+                .sheets
+                .iter()
+                .find(|(sheet_name, _)| sheet_name == name)
+                .ok_or_else(|| format!("Sheet '{}' not found in {}. Available sheets: {}", name, self.path, available())),
+        };
+        let (name, part) = found?.clone();
+        let xml = read_part(&mut self.archive, &part)?.ok_or_else(|| format!("Sheet '{}' is missing from the workbook ({})", name, part))?;
+        let mut rows = self.parse_rows(&xml).map_err(|e| xml_error(&self.path, &part, e))?.into_iter();
+
+        let Some((_, header)) = rows.next() else {
+            return Ok((StringRecord::new(), Box::new(std::iter::empty())));
+        };
+        let width = header.iter().rposition(|cell| !cell.is_empty()).map_or(0, |last| last + 1);
+        let headers = StringRecord::from(header[..width].to_vec());
+        let records = rows.map(move |(number, mut cells)| {
+            cells.resize(width, String::new());
+            let mut record = StringRecord::from(cells);
+            let mut position = Position::new();
+            position.set_line(number);
+            record.set_position(Some(position));
+            Ok(record)
+        });
+        Ok((headers, Box::new(records)))
+    }
+
+    /// Every non-empty row of a worksheet as (row number, cell values); cells missing from
+    /// the sparse XML are empty strings.
+    fn parse_rows(&self, xml: &str) -> Result<Vec<(u64, Vec<String>)>, String> {
+        let mut rows = Vec::new();
+        let mut cells: Vec<String> = Vec::new();
+        let mut row_number = 0u64;
+        // The open cell's column, type and style, and the text of its <v> or inline <t>
+        let mut cell: Option<(usize, String, usize)> = None;
+        let mut value = String::new();
+        let mut in_value = false;
+
+        for event in events(xml) {
+            match event? {
+                Event::Start { name: "row", attributes, .. } => {
+                    row_number = attribute(attributes, "r").and_then(|r| r.parse().ok()).unwrap_or(row_number + 1);
+                    cells.clear();
+                }
+                Event::End("row") if cells.iter().any(|cell| !cell.is_empty()) => {
+                    rows.push((row_number, std::mem::take(&mut cells)));
+                }
+                Event::Start { name: "c", attributes, empty } => {
+                    let column = match attribute(attributes, "r") {
+                        Some(reference) => column_index(&reference).ok_or_else(|| format!("invalid cell reference '{}'", reference))?,
+                        None => cells.len(),
+                    };
+                    if column > MAX_COLUMN {
+                        return Err(format!("row {} has more than {} columns", row_number, MAX_COLUMN + 1));
+                    }
+                    let kind = attribute(attributes, "t").unwrap_or_default();
+                    let style = attribute(attributes, "s").and_then(|s| s.parse().ok()).unwrap_or(0);
+                    value.clear();
+                    if !empty {
+                        cell = Some((column, kind, style));
+                    }
+                }
+                Event::Start { name: "v" | "t", empty: false, .. } => in_value = cell.is_some(),
+                Event::End("v" | "t") => in_value = false,
+                Event::Text(text) if in_value => value.push_str(text),
+                Event::End("c") => {
+                    if let Some((column, kind, style)) = cell.take() {
+                        if cells.len() <= column {
+                            cells.resize(column + 1, String::new());
+                        }
+                        cells[column] = self.cell_value(&kind, style, &unescape(&value));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(rows)
+    }
+
+    fn cell_value(&self, kind: &str, style: usize, raw: &str) -> String {
+        match kind {
+            "s" => raw.trim().parse::<usize>().ok().and_then(|i| self.shared_strings.get(i)).cloned().unwrap_or_default(),
+            "b" => (if raw.trim() == "1" { "TRUE" } else { "FALSE" }).to_string(),
+            "inlineStr" | "str" | "e" => raw.to_string(),
+            _ => {
+                let Ok(number) = raw.trim().parse::<f64>() else { return raw.to_string() };
+                if self.date_styles.get(style).copied().unwrap_or(false)
+                    && let Some(date) = format_date_serial(number, self.date1904)
+                {
+                    return date;
+                }
+                // The shortest form that reads back as the same number, as a CSV export shows it
+                format!("{}", number)
+            }
+        }
+    }
+}
+
+/// The text of a part of the archive, or `None` when the workbook does not have it.
+fn read_part(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let mut part = match archive.by_name(name) {
+        Ok(part) => part,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut text = String::new();
+    part.read_to_string(&mut text)?;
+    Ok(Some(text))
+}
+
+/// The text of each `<si>` entry; rich text runs are joined and phonetic hints skipped.
+fn parse_shared_strings(xml: &str) -> Result<Vec<String>, String> {
+    let mut strings = Vec::new();
+    let mut current = String::new();
+    let (mut in_text, mut in_phonetic) = (false, false);
+    for event in events(xml) {
+        match event? {
+            Event::Start { name: "si", .. } => current.clear(),
+            Event::End("si") => strings.push(unescape(&current)),
+            Event::Start { name: "rPh", empty: false, .. } => in_phonetic = true,
+            Event::End("rPh") => in_phonetic = false,
+            Event::Start { name: "t", empty: false, .. } => in_text = !in_phonetic,
+            Event::End("t") => in_text = false,
+            Event::Text(text) if in_text => current.push_str(text),
+            _ => {}
+        }
+    }
+    Ok(strings)
+}
+
+/// For each cell style in `<cellXfs>`, whether its number format displays a date.
+fn parse_date_styles(xml: &str) -> Result<Vec<bool>, String> {
+    let mut custom_dates: HashMap<u32, bool> = HashMap::new();
+    let mut styles = Vec::new();
+    let mut in_cell_xfs = false;
+    for event in events(xml) {
+        match event? {
+            Event::Start { name: "numFmt", attributes, .. } => {
+                if let (Some(id), Some(code)) = (attribute(attributes, "numFmtId").and_then(|id| id.parse().ok()), attribute(attributes, "formatCode")) {
+                    custom_dates.insert(id, is_date_format_code(&code));
+                }
+            }
+            Event::Start { name: "cellXfs", empty: false, .. } => in_cell_xfs = true,
+            Event::End("cellXfs") => in_cell_xfs = false,
+            Event::Start { name: "xf", attributes, .. } if in_cell_xfs => {
+                let id: u32 = attribute(attributes, "numFmtId").and_then(|id| id.parse().ok()).unwrap_or(0);
+                styles.push(custom_dates.get(&id).copied().unwrap_or_else(|| is_builtin_date_format(id)));
+            }
+            _ => {}
+        }
+    }
+    Ok(styles)
+}
+
+/// Whether a file looks like an .xlsx workbook: a ZIP archive with `xl/workbook.xml`.
+pub fn is_xlsx(path: &Path) -> bool {
+    File::open(path)
+        .ok()
+        .and_then(|file| ZipArchive::new(file).ok())
+        .is_some_and(|archive| archive.index_for_name("xl/workbook.xml").is_some())
+}
+
+/// Whether a ZIP archive is an OpenDocument spreadsheet, judging by its `mimetype` entry.
+pub fn is_ods(path: &Path) -> bool {
+    File::open(path)
+        .ok()
+        .and_then(|file| ZipArchive::new(file).ok())
+        .and_then(|mut archive| read_part(&mut archive, "mimetype").ok().flatten())
+        .is_some_and(|mimetype| mimetype.trim() == "application/vnd.oasis.opendocument.spreadsheet")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The events of a document as strings, or the parser's error.
+    fn parse(xml: &str) -> Result<Vec<String>, String> {
+        events(xml)
+            .map(|event| {
+                Ok(match event? {
+                    Event::Start { name, attributes, empty } => format!("<{} {}{}>", name, attributes, if empty { "/" } else { "" }).replace(" >", ">").replace(" /", "/"),
+                    Event::End(name) => format!("</{}>", name),
+                    Event::Text(text) => text.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn events_skip_declarations_and_comments() {
+        let xml = "<?xml version=\"1.0\"?><!-- note --><x:row r=\"1\"><c/>a<![CDATA[<b>]]></x:row>";
+        assert_eq!(parse(xml).unwrap(), ["<row r=\"1\">", "<c/>", "a", "<b>", "</row>"]);
+    }
+
+    #[test]
+    fn events_reject_unterminated_input() {
+        assert_eq!(parse("<row>").unwrap(), ["<row>"]);
+        assert_eq!(parse("<row><").unwrap_err(), "unterminated tag at byte 5");
+        assert_eq!(parse("<").unwrap_err(), "unterminated tag at byte 0");
+        assert_eq!(parse("<row r=\"1\"").unwrap_err(), "unterminated tag at byte 0");
+        assert_eq!(parse("<![CDATA[text").unwrap_err(), "unterminated CDATA section at byte 0");
+        assert_eq!(parse("<!-- note").unwrap_err(), "unterminated declaration or comment at byte 0");
+    }
+
+    #[test]
+    fn events_stop_after_an_error() {
+        let mut parser = events("a<");
+        assert!(matches!(parser.next(), Some(Ok(Event::Text("a")))));
+        assert!(matches!(parser.next(), Some(Err(_))));
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn attribute_values_are_unescaped() {
+        let attributes = r#" r="A1" x:t='s' name="a &amp; b""#;
+        assert_eq!(attribute(attributes, "r").as_deref(), Some("A1"));
+        assert_eq!(attribute(attributes, "t").as_deref(), Some("s"));
+        assert_eq!(attribute(attributes, "name").as_deref(), Some("a & b"));
+        assert_eq!(attribute(attributes, "s"), None);
+    }
+
+    #[test]
+    fn unescape_decodes_entities_and_control_escapes() {
+        assert_eq!(unescape("&lt;a&gt; &amp; &quot;b&quot; &apos;c&apos;"), "<a> & \"b\" 'c'");
+        assert_eq!(unescape("&#65;&#x42;"), "AB");
+        assert_eq!(unescape("line_x000D__x000A_break"), "line\r\nbreak");
+        assert_eq!(unescape("&unknown; &#xZZ; & a"), "&unknown; &#xZZ; & a");
+        assert_eq!(unescape("_x00_ _xZZZZ_"), "_x00_ _xZZZZ_");
+    }
+
+    #[test]
+    fn column_index_stops_at_xfd() {
+        assert_eq!(column_index("A1"), Some(0));
+        assert_eq!(column_index("z9"), Some(25));
+        assert_eq!(column_index("AA1"), Some(26));
+        assert_eq!(column_index("XFD1048576"), Some(MAX_COLUMN));
+        assert_eq!(column_index("XFE1"), None);
+        assert_eq!(column_index("ZZZZZZZ2"), None);
+        assert_eq!(column_index(&"Z".repeat(40)), None);
+        assert_eq!(column_index("12"), None);
+    }
+
+    #[test]
+    fn date_serials_in_the_1900_system() {
+        assert_eq!(format_date_serial(0.5, false).as_deref(), Some("12:00:00"));
+        assert_eq!(format_date_serial(1.0, false).as_deref(), Some("1900-01-01"));
+        assert_eq!(format_date_serial(59.0, false).as_deref(), Some("1900-02-28"));
+        assert_eq!(format_date_serial(60.0, false).as_deref(), Some("1900-02-29"));
+        assert_eq!(format_date_serial(61.0, false).as_deref(), Some("1900-03-01"));
+        assert_eq!(format_date_serial(25_569.0, false).as_deref(), Some("1970-01-01"));
+        assert_eq!(format_date_serial(45_306.75, false).as_deref(), Some("2024-01-15 18:00:00"));
+        assert_eq!(format_date_serial(-1.0, false), None);
+        assert_eq!(format_date_serial(f64::NAN, false), None);
+    }
+
+    #[test]
+    fn date_serials_in_the_1904_system() {
+        assert_eq!(format_date_serial(0.0, true).as_deref(), Some("1904-01-01"));
+        assert_eq!(format_date_serial(43_844.0, true).as_deref(), Some("2024-01-15"));
+    }
+
+    #[test]
+    fn date_format_codes() {
+        assert!(is_date_format_code("yyyy-mm-dd"));
+        assert!(is_date_format_code("[$-409]h:mm AM/PM"));
+        assert!(!is_date_format_code("0.00"));
+        assert!(!is_date_format_code("[Red]0.00"));
+        assert!(!is_date_format_code(r#"0 "days""#));
+        assert!(!is_date_format_code(r"0\d"));
+    }
+
+    #[test]
+    fn shared_strings_join_runs_and_skip_phonetics() {
+        let xml = concat!(
+            "<sst><si><t>plain &amp; simple</t></si>",
+            "<si><r><t>rich</t></r><r><rPr/><t xml:space=\"preserve\"> text</t></r></si>",
+            "<si><t>\u{6f22}\u{5b57}</t><rPh sb=\"0\" eb=\"2\"><t>\u{304b}\u{3093}\u{3058}</t></rPh></si>",
+            "<si><t/></si></sst>",
+        );
+        assert_eq!(parse_shared_strings(xml).unwrap(), ["plain & simple", "rich text", "\u{6f22}\u{5b57}", ""]);
+        assert!(parse_shared_strings("<sst><si><t>cut").is_ok());
+        assert!(parse_shared_strings("<sst><si><t").is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Referential integrity checks of file1 columns against reference files.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::PathBuf;
+
+use csv::{ReaderBuilder, StringRecord};
+
+/// A `--foreign-key` definition: values of `column` in file1 must exist in `ref_column` of `ref_file`.
+#[derive(Debug, Clone)]
+pub struct ForeignKey {
+    pub column: String,
+    pub ref_file: PathBuf,
+    pub ref_column: String,
+}
+
+/// Outcome of checking one foreign key.
+pub struct IntegrityResult {
+    pub foreign_key: ForeignKey,
+    pub checked: usize,
+    /// Distinct file1 values with no match in the reference file, sorted
+    pub violations: Vec<String>,
+}
+
+/// Parse `REF_FILE:REF_COL`, or `COLUMN=REF_FILE:REF_COL` when the file1 column is named differently.
+pub fn parse_foreign_key(value: &str) -> Result<ForeignKey, String> {
+    let (column, reference) = match value.split_once('=') {
+        Some((column, reference)) => (Some(column), reference),
+        None => (None, value),
+    };
+    // Split on the last ':' so Windows drive letters stay part of the path
+    let (ref_file, ref_column) = reference
+        .rsplit_once(':')
+        .filter(|(file, col)| !file.is_empty() && !col.is_empty())
+        .ok_or_else(|| format!("invalid foreign key '{}': expected REF_FILE:REF_COL or COLUMN=REF_FILE:REF_COL", value))?;
+    Ok(ForeignKey {
+        column: column.unwrap_or(ref_column).to_string(),
+        ref_file: PathBuf::from(ref_file),
+        ref_column: ref_column.to_string(),
+    })
+}
+
+/// Read the distinct values of a single column, without indexing the rest of the file.
+fn read_column_values(foreign_key: &ForeignKey) -> Result<HashSet<String>, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().from_path(&foreign_key.ref_file)?;
+    let index = rdr
+        .headers()?
+        .iter()
+        .position(|h| h == foreign_key.ref_column)
+        .ok_or_else(|| {
+            format!(
+                "Reference column '{}' not found in {}",
+                foreign_key.ref_column,
+                foreign_key.ref_file.display()
+            )
+        })?;
+
+    let mut values = HashSet::new();
+    let mut record = StringRecord::new();
+    while rdr.read_record(&mut record)? {
+        if let Some(value) = record.get(index) {
+            values.insert(value.to_string());
+        }
+    }
+    Ok(values)
+}
+
+/// Check that every non-empty value of each foreign key column in file1 exists in its reference file.
+pub fn check_foreign_keys(
+    foreign_keys: Vec<ForeignKey>,
+    headers1: &[String],
+    map1: &HashMap<String, StringRecord>,
+) -> Result<Vec<IntegrityResult>, Box<dyn Error>> {
+    let mut results = Vec::new();
+    for foreign_key in foreign_keys {
+        let index = headers1
+            .iter()
+            .position(|h| *h == foreign_key.column)
+            .ok_or_else(|| format!("Foreign key column '{}' not found in file 1", foreign_key.column))?;
+        let reference = read_column_values(&foreign_key)?;
+
+        // Empty cells are treated as null references and are not violations
+        let values: HashSet<&str> = map1
+            .values()
+            .filter_map(|record| record.get(index))
+            .filter(|value| !value.is_empty())
+            .collect();
+        let mut violations: Vec<String> = values
+            .iter()
+            .filter(|value| !reference.contains(**value))
+            .map(|value| value.to_string())
+            .collect();
+        violations.sort();
+
+        results.push(IntegrityResult {
+            foreign_key,
+            checked: values.len(),
+            violations,
+        });
+    }
+    Ok(results)
+}
@@ -0,0 +1,611 @@
+//! Compare two sets of CSV rows already held in memory.
+//!
+//! The `csvdiff` binary reads files into keyed records and calls [`compare_rows`]; callers with
+//! data from elsewhere (a database query, another parser) use [`diff_from_records`] and skip
+//! the round trip through a file.
+//!
+//! ```no_run
+//! use csv::StringRecord;
+//! use csvdiff::{DiffConfig, diff_from_records};
+//!
+//! let headers = vec!["id".to_string(), "name".to_string()];
+//! let before = vec![("1".to_string(), StringRecord::from(vec!["1", "Ada"]))];
+//! let after = vec![("1".to_string(), StringRecord::from(vec!["1", "Grace"]))];
+//! let key = ["id".to_string()];
+//! let config = DiffConfig { key_columns: &key, ..DiffConfig::default() };
+//! for diff in diff_from_records(before, headers.clone(), after, headers, &config) {
+//!     println!("{} {}: {} -> {}", diff.key, diff.column, diff.file1, diff.file2);
+//! }
+//! ```
+
+pub mod datetime;
+pub mod json;
+pub mod normalize;
+pub mod timestamp;
+pub mod wasm;
+pub mod yaml;
+
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use csv::StringRecord;
+
+use normalize::{CompareOptions, compare_values};
+
+/// How --preview-format shows the values of a missing row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum PreviewFormat {
+    /// Values joined with commas, unquoted
+    Plain,
+    /// One CSV line, quoted where needed
+    Csv,
+    /// A JSON object of column names and values
+    Json,
+    /// `column=value` pairs separated by spaces
+    KeyValue,
+}
+
+/// Which differences are collected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum DiffMode {
+    /// Compare every matched row column by column and report missing rows
+    Full,
+    /// Only list keys present in file1 but missing from file2
+    LeftOnly,
+    /// Only list keys present in file2 but missing from file1
+    RightOnly,
+    /// Like full, but only compare columns present in both files
+    CommonColumns,
+    /// Without a key: pair each file1 row with the file2 row it differs from in the fewest columns
+    BestMatch,
+    /// Compare only the header rows, listing every sheet of an Excel input; no rows are compared
+    Headers,
+}
+
+/// Start of the row that replaces a key's differences beyond --diff-limit-per-key
+pub const TRUNCATED_COLUMNS_PREFIX: &str = "... (";
+
+/// One difference: a changed value, or a row missing from one side.
+#[derive(Clone, Default)]
+pub struct DiffRow {
+    pub key: String,
+    pub column: String,
+    pub file1: String,
+    pub file2: String,
+    /// Fraction of the row's shared columns that are identical (`None` for missing rows)
+    pub similarity: Option<f64>,
+    /// Levenshtein distance between the two values, when `--show-edit-distance` is set
+    pub edit_distance: Option<usize>,
+    /// Line each file's row starts on (`None` for formats without lines, or the absent side)
+    pub file1_line: Option<u64>,
+    pub file2_line: Option<u64>,
+}
+
+impl DiffRow {
+    /// Whether this row stands in for the columns left out by --diff-limit-per-key.
+    pub fn is_truncation(&self) -> bool {
+        self.column.starts_with(TRUNCATED_COLUMNS_PREFIX)
+    }
+
+    /// Whether this row is a changed value in a column present in both files.
+    pub fn is_value_change(&self) -> bool {
+        !matches!(self.column.as_str(), "[missing in file1]" | "[missing in file2]")
+            && !self.is_truncation()
+            && self.file1 != "[column not in file1]"
+            && self.file2 != "[column not in file2]"
+    }
+}
+
+/// The line a record starts on; multi-line quoted fields count from their first line.
+pub fn line_of(record: &StringRecord) -> Option<u64> {
+    record.position().map(|position| position.line())
+}
+
+/// A `--rule` that ignores extra columns for rows matching a condition.
+pub struct ComparisonRule {
+    pub column: String,
+    pub value: String,
+    pub ignore: Vec<String>,
+}
+
+impl ComparisonRule {
+    /// Whether the rule's condition holds for a row, looking the column up by name.
+    pub fn matches(&self, headers_map: &HashMap<String, usize>, record: &StringRecord) -> bool {
+        headers_map
+            .get(&self.column)
+            .and_then(|&i| record.get(i))
+            .is_some_and(|value| value == self.value)
+    }
+}
+
+/// Join a row's values for display, cut to `max_len` characters when a limit is given.
+pub fn row_preview(record: &StringRecord, headers: &[String], format: PreviewFormat, max_len: Option<usize>) -> String {
+    let preview = match format {
+        PreviewFormat::Plain => record.iter().collect::<Vec<_>>().join(","),
+        PreviewFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().from_writer(Vec::new());
+            let line = writer.write_record(record).ok().and_then(|_| writer.into_inner().ok()).unwrap_or_default();
+            String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string()
+        }
+        PreviewFormat::Json => {
+            let fields: Vec<String> = headers
+                .iter()
+                .zip(record.iter())
+                .map(|(column, value)| format!("{}:{}", json::quote(column), json::quote(value)))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        PreviewFormat::KeyValue => {
+            // Values that would break the pairs apart are quoted
+            let pair = |(column, value): (&String, &str)| {
+                if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+                    format!("{}={}", column, json::quote(value))
+                } else {
+                    format!("{}={}", column, value)
+                }
+            };
+            headers.iter().zip(record.iter()).map(pair).collect::<Vec<_>>().join(" ")
+        }
+    };
+    match max_len {
+        Some(max_len) if preview.chars().count() >= max_len => {
+            let head: String = preview.chars().take(max_len.saturating_sub(3)).collect();
+            format!("{}...", head)
+        }
+        _ => preview,
+    }
+}
+
+/// How two sets of rows are compared.
+pub struct DiffConfig<'a> {
+    /// Key columns, never compared as values
+    pub key_columns: &'a [String],
+    /// Columns left out of the comparison
+    pub ignore: &'a [String],
+    /// Extra columns to ignore in rows matching a condition
+    pub rules: &'a [ComparisonRule],
+    pub compare_options: &'a CompareOptions,
+    pub diff_mode: DiffMode,
+    /// Record the Levenshtein distance of each changed value
+    pub show_edit_distance: bool,
+    /// Drop rows with changed values where fewer than this percentage of compared columns differ
+    pub min_diff_pct: Option<f64>,
+    /// Stop after this many differences, not counting --diff-limit-per-key summary rows
+    pub diff_limit: Option<usize>,
+    /// Keep at most this many changed columns per key, then one summary row
+    pub diff_limit_per_key: Option<usize>,
+    pub preview_format: PreviewFormat,
+    /// Cut missing-row previews to this many characters
+    pub preview_length: Option<usize>,
+    /// Leave the values of missing rows empty instead of building a preview
+    pub no_missing_row_preview: bool,
+    /// Called every `progress_interval` keys with the number of keys compared so far and the
+    /// number of keys to compare; the library prints nothing itself
+    pub progress: Option<&'a dyn Fn(usize, usize)>,
+    pub progress_interval: usize,
+}
+
+impl Default for DiffConfig<'_> {
+    fn default() -> Self {
+        static COMPARE_OPTIONS: std::sync::OnceLock<CompareOptions> = std::sync::OnceLock::new();
+        DiffConfig {
+            key_columns: &[],
+            ignore: &[],
+            rules: &[],
+            compare_options: COMPARE_OPTIONS.get_or_init(CompareOptions::default),
+            diff_mode: DiffMode::Full,
+            show_edit_distance: false,
+            min_diff_pct: None,
+            diff_limit: None,
+            diff_limit_per_key: None,
+            preview_format: PreviewFormat::Plain,
+            preview_length: Some(50),
+            no_missing_row_preview: false,
+            progress: None,
+            progress_interval: 10_000,
+        }
+    }
+}
+
+/// A source record with the key it is stored under.
+pub type KeyedRecord<'a> = (&'a String, &'a StringRecord);
+
+/// The differences between paired rows, with the source records behind them.
+pub struct Comparison<'a> {
+    pub diffs: Vec<DiffRow>,
+    /// File1 and file2 versions of every row with a changed value
+    pub changed: Vec<(KeyedRecord<'a>, KeyedRecord<'a>)>,
+    /// Rows only in file1
+    pub removed: Vec<KeyedRecord<'a>>,
+    /// Rows only in file2
+    pub added: Vec<KeyedRecord<'a>>,
+    /// Values compared in matched rows, out of matched rows × non-key columns
+    pub cells_compared: usize,
+    pub cells_total: usize,
+    /// Whether --diff-limit left out differences that were found
+    pub truncated: bool,
+}
+
+/// Compare rows paired by key: `(Some(k), Some(k))` for a key in both files, or a pair of
+/// different keys matched some other way; a missing side means the row is missing there.
+pub fn compare_rows<'a, 'k>(
+    row_pairs: impl IntoIterator<Item = (Option<&'k String>, Option<&'k String>), IntoIter: ExactSizeIterator>,
+    map1: &'a HashMap<String, StringRecord>,
+    headers1: &[String],
+    map2: &'a HashMap<String, StringRecord>,
+    headers2: &[String],
+    config: &DiffConfig,
+) -> Comparison<'a> {
+    let headers1_map: HashMap<String, usize> = headers1.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
+    let headers2_map: HashMap<String, usize> = headers2.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
+    // Every column once, in file1's order and then the columns only in file2, so a row's
+    // differences always come out in the same order
+    let mut seen = HashSet::new();
+    let all_columns: Vec<&String> = headers1.iter().chain(headers2.iter()).filter(|h| seen.insert(*h)).collect();
+    let value_columns = all_columns.iter().filter(|h| !config.key_columns.contains(h)).count();
+
+    let mut diffs = Vec::new();
+    let mut changed = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let mut cells_compared = 0;
+    let mut cells_total = 0;
+    // Differences so far, without --diff-limit-per-key summary rows
+    let mut found = 0;
+    let mut truncated = false;
+
+    let row_pairs = row_pairs.into_iter();
+    let total_keys = row_pairs.len();
+    for (index, (key1, key2)) in row_pairs.enumerate() {
+        if let Some(progress) = config.progress
+            && index > 0
+            && index % config.progress_interval.max(1) == 0
+        {
+            progress(index, total_keys);
+        }
+
+        let (row1, row2) = (key1.and_then(|k| map1.get_key_value(k)), key2.and_then(|k| map2.get_key_value(k)));
+        let (record1, record2) = (row1.map(|(_, record)| record), row2.map(|(_, record)| record));
+        let key = match (key1, key2) {
+            (Some(key1), Some(key2)) if key1 != key2 => format!("{} ≈ {}", key1, key2),
+            (Some(key), _) | (None, Some(key)) => key.clone(),
+            (None, None) => unreachable!(),
+        };
+
+        if record1.is_some() && record2.is_some() {
+            cells_total += value_columns;
+        }
+
+        // Existence-only modes skip everything but the requested side's unmatched keys
+        match (config.diff_mode, record1.is_some(), record2.is_some()) {
+            (DiffMode::LeftOnly, true, false)
+            | (DiffMode::RightOnly, false, true)
+            | (DiffMode::Full | DiffMode::CommonColumns | DiffMode::BestMatch, _, _) => {}
+            (DiffMode::LeftOnly | DiffMode::RightOnly | DiffMode::Headers, _, _) => continue,
+        }
+
+        let records_before = (changed.len(), removed.len(), added.len());
+        let pair_diffs = match (row1, row2) {
+            (Some(row1 @ (_, r1)), Some(row2 @ (_, r2))) => {
+                // A rule applies when its condition holds in either file's version of the row
+                let rule_ignores: HashSet<&String> = config
+                    .rules
+                    .iter()
+                    .filter(|rule| rule.matches(&headers1_map, r1) || rule.matches(&headers2_map, r2))
+                    .flat_map(|rule| &rule.ignore)
+                    .collect();
+
+                let mut row_diffs = Vec::new();
+                let mut compared_columns = 0;
+                let mut changed_columns = 0;
+
+                for &col_name in &all_columns {
+                    if config.key_columns.contains(col_name) || config.ignore.contains(col_name) || rule_ignores.contains(col_name) {
+                        continue;
+                    }
+
+                    let v1 = headers1_map.get(col_name).and_then(|&i| r1.get(i)).unwrap_or("");
+                    let v2 = headers2_map.get(col_name).and_then(|&i| r2.get(i)).unwrap_or("");
+
+                    // Only genuine value changes get an edit distance, not placeholder values
+                    let mut edit_distance = None;
+
+                    // Handle cases where column exists in only one file
+                    let (v1_display, v2_display) = match (headers1_map.contains_key(col_name), headers2_map.contains_key(col_name)) {
+                        (true, true) => {
+                            // Column exists in both files, compare values
+                            compared_columns += 1;
+                            if !compare_values(col_name, v1, v2, config.compare_options) {
+                                changed_columns += 1;
+                                edit_distance = config.show_edit_distance.then(|| strsim::levenshtein(v1, v2));
+                                (v1.to_string(), v2.to_string())
+                            } else {
+                                continue; // Values are the same, skip
+                            }
+                        },
+                        (true, false) | (false, true) if config.diff_mode == DiffMode::CommonColumns => {
+                            continue; // Only columns shared by both files are compared
+                        },
+                        (true, false) => {
+                            // Column only exists in file1
+                            (v1.to_string(), "[column not in file2]".to_string())
+                        },
+                        (false, true) => {
+                            // Column only exists in file2
+                            ("[column not in file1]".to_string(), v2.to_string())
+                        },
+                        (false, false) => unreachable!(), // Column came from one of the files
+                    };
+
+                    row_diffs.push(DiffRow {
+                        key: key.clone(),
+                        column: col_name.clone(),
+                        file1: v1_display,
+                        file2: v2_display,
+                        similarity: None,
+                        edit_distance,
+                        file1_line: line_of(r1),
+                        file2_line: line_of(r2),
+                    });
+                }
+
+                cells_compared += compared_columns;
+
+                let similarity = if compared_columns == 0 {
+                    1.0
+                } else {
+                    1.0 - changed_columns as f64 / compared_columns as f64
+                };
+
+                // Drop rows whose share of changed columns is below the noise threshold; rows
+                // that differ only in columns missing from one file are always kept
+                if let Some(min_pct) = config.min_diff_pct
+                    && changed_columns > 0
+                    && (1.0 - similarity) * 100.0 < min_pct
+                {
+                    continue;
+                }
+
+                if changed_columns > 0 {
+                    changed.push((row1, row2));
+                }
+
+                if let Some(limit) = config.diff_limit_per_key
+                    && row_diffs.len() > limit
+                {
+                    // Keep the first columns in file order
+                    let more = row_diffs.len() - limit;
+                    row_diffs.truncate(limit);
+                    row_diffs.push(DiffRow {
+                        key: key.clone(),
+                        column: format!("{}{} more columns changed)", TRUNCATED_COLUMNS_PREFIX, more),
+                        file1_line: line_of(r1),
+                        file2_line: line_of(r2),
+                        ..Default::default()
+                    });
+                }
+                row_diffs.into_iter().map(|diff| DiffRow { similarity: Some(similarity), ..diff }).collect()
+            }
+            (Some(row1 @ (_, r1)), None) => {
+                let preview = if config.no_missing_row_preview {
+                    String::new()
+                } else {
+                    row_preview(r1, headers1, config.preview_format, config.preview_length)
+                };
+                removed.push(row1);
+
+                vec![DiffRow {
+                    key: key.clone(),
+                    column: "[missing in file2]".into(),
+                    file1: preview,
+                    file2: "".into(),
+                    file1_line: line_of(r1),
+                    ..Default::default()
+                }]
+            }
+            (None, Some(row2 @ (_, r2))) => {
+                let preview = if config.no_missing_row_preview {
+                    String::new()
+                } else {
+                    row_preview(r2, headers2, config.preview_format, config.preview_length)
+                };
+                added.push(row2);
+
+                vec![DiffRow {
+                    key: key.clone(),
+                    column: "[missing in file1]".into(),
+                    file1: "".into(),
+                    file2: preview,
+                    file2_line: line_of(r2),
+                    ..Default::default()
+                }]
+            }
+            (None, None) => unreachable!(),
+        };
+
+        // Comparing goes on past the limit until a difference is actually left out
+        let new = pair_diffs.iter().filter(|diff| !diff.is_truncation()).count();
+        if let Some(limit) = config.diff_limit
+            && found + new > limit
+        {
+            let room = limit - found;
+            if room == 0 {
+                changed.truncate(records_before.0);
+                removed.truncate(records_before.1);
+                added.truncate(records_before.2);
+            }
+            diffs.extend(pair_diffs.into_iter().take(room));
+            truncated = true;
+            break;
+        }
+        found += new;
+        diffs.extend(pair_diffs);
+    }
+
+    Comparison {
+        diffs,
+        changed,
+        removed,
+        added,
+        cells_compared,
+        cells_total,
+        truncated,
+    }
+}
+
+/// Compare two sets of keyed records without reading any files.
+///
+/// Each record is paired with the other side's record under the same key; when a key occurs
+/// more than once, its last record is used. Differences follow file1's key order, then keys
+/// only in file2.
+pub fn diff_from_records(
+    records1: Vec<(String, StringRecord)>,
+    headers1: Vec<String>,
+    records2: Vec<(String, StringRecord)>,
+    headers2: Vec<String>,
+    config: &DiffConfig,
+) -> Vec<DiffRow> {
+    let mut order: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    for (key, _) in records1.iter().chain(&records2) {
+        if seen.insert(key.clone()) {
+            order.push(key.clone());
+        }
+    }
+    let map1: HashMap<String, StringRecord> = records1.into_iter().collect();
+    let map2: HashMap<String, StringRecord> = records2.into_iter().collect();
+    let pairs = order.iter().map(|key| (map1.get_key_value(key).map(|(k, _)| k), map2.get_key_value(key).map(|(k, _)| k)));
+    compare_rows(pairs, &map1, &headers1, &map2, &headers2, config).diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(rows: &[&[&str]]) -> Vec<(String, StringRecord)> {
+        rows.iter().map(|row| (row[0].to_string(), StringRecord::from(row.to_vec()))).collect()
+    }
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn columns_follow_file1_then_file2_only() {
+        let key = headers(&["id"]);
+        let config = DiffConfig { key_columns: &key, ..DiffConfig::default() };
+        let before = records(&[&["1", "a", "b", "c", "d", "x"]]);
+        let after = records(&[&["1", "y", "D", "C", "B", "A"]]);
+        for _ in 0..20 {
+            let diffs = diff_from_records(
+                before.clone(),
+                headers(&["id", "a", "b", "c", "d", "file1_only"]),
+                after.clone(),
+                headers(&["id", "file2_only", "d", "c", "b", "a"]),
+                &config,
+            );
+            let columns: Vec<&str> = diffs.iter().map(|diff| diff.column.as_str()).collect();
+            assert_eq!(columns, ["a", "b", "c", "d", "file1_only", "file2_only"]);
+        }
+    }
+
+    #[test]
+    fn missing_rows_follow_key_order() {
+        let key = headers(&["id"]);
+        let config = DiffConfig { key_columns: &key, ..DiffConfig::default() };
+        let diffs = diff_from_records(records(&[&["1", "a"], &["2", "b"]]), headers(&["id", "v"]), records(&[&["3", "c"]]), headers(&["id", "v"]), &config);
+        let rows: Vec<(&str, &str, &str)> = diffs.iter().map(|d| (d.key.as_str(), d.column.as_str(), d.file1.as_str())).collect();
+        assert_eq!(rows, [("1", "[missing in file2]", "1,a"), ("2", "[missing in file2]", "2,b"), ("3", "[missing in file1]", "")]);
+    }
+
+    #[test]
+    fn diff_limit_counts_differences_not_summary_rows() {
+        let key = headers(&["id"]);
+        let rows = |value: &str| records(&[&["1", value, value, value], &["2", value, value, value], &["3", value, value, value]]);
+        let names = || headers(&["id", "a", "b", "c"]);
+        let keys = headers(&["1", "2", "3"]);
+        let pairs = || keys.iter().map(|key| (Some(key), Some(key)));
+        let (map1, map2): (HashMap<_, _>, HashMap<_, _>) = (rows("x").into_iter().collect(), rows("y").into_iter().collect());
+
+        let config = DiffConfig { key_columns: &key, diff_limit: Some(3), diff_limit_per_key: Some(1), ..DiffConfig::default() };
+        let comparison = compare_rows(pairs(), &map1, &names(), &map2, &names(), &config);
+        assert_eq!(comparison.diffs.iter().filter(|diff| !diff.is_truncation()).count(), 3);
+        assert_eq!(comparison.diffs.len(), 6);
+        assert!(!comparison.truncated);
+
+        let config = DiffConfig { key_columns: &key, diff_limit: Some(4), ..DiffConfig::default() };
+        let comparison = compare_rows(pairs(), &map1, &names(), &map2, &names(), &config);
+        let kept: Vec<(&str, &str)> = comparison.diffs.iter().map(|diff| (diff.key.as_str(), diff.column.as_str())).collect();
+        assert_eq!(kept, [("1", "a"), ("1", "b"), ("1", "c"), ("2", "a")]);
+        assert!(comparison.truncated);
+        assert_eq!(comparison.changed.len(), 2);
+
+        let config = DiffConfig { key_columns: &key, diff_limit: Some(9), ..DiffConfig::default() };
+        assert!(!compare_rows(pairs(), &map1, &names(), &map2, &names(), &config).truncated);
+    }
+
+    #[test]
+    fn progress_goes_to_the_callback() {
+        let key = headers(&["id"]);
+        let calls = std::cell::RefCell::new(Vec::new());
+        let progress = |index, total| calls.borrow_mut().push((index, total));
+        let config = DiffConfig { key_columns: &key, progress: Some(&progress), progress_interval: 2, ..DiffConfig::default() };
+        let rows = records(&[&["1"], &["2"], &["3"], &["4"], &["5"]]);
+        diff_from_records(rows.clone(), headers(&["id"]), rows, headers(&["id"]), &config);
+        assert_eq!(calls.into_inner(), [(2, 5), (4, 5)]);
+    }
+
+    #[test]
+    fn edit_distance_only_for_changed_values() {
+        let key = headers(&["id"]);
+        let config = DiffConfig { key_columns: &key, show_edit_distance: true, ..DiffConfig::default() };
+        let diffs = diff_from_records(
+            records(&[&["1", "kitten", "same", "gone"]]),
+            headers(&["id", "name", "other", "old"]),
+            records(&[&["1", "sitting", "same", "new"]]),
+            headers(&["id", "name", "other", "added"]),
+            &config,
+        );
+        let distances: Vec<(&str, Option<usize>)> = diffs.iter().map(|diff| (diff.column.as_str(), diff.edit_distance)).collect();
+        assert_eq!(distances, [("name", Some(3)), ("old", None), ("added", None)]);
+    }
+
+    #[test]
+    fn min_diff_pct_keeps_schema_only_rows() {
+        let key = headers(&["id"]);
+        let config = DiffConfig { key_columns: &key, min_diff_pct: Some(50.0), ..DiffConfig::default() };
+        let diffs = diff_from_records(
+            records(&[&["1", "a", "b", "old"], &["2", "a", "b", "old"], &["3", "a", "b", "old"]]),
+            headers(&["id", "x", "y", "gone"]),
+            records(&[&["1", "a", "b"], &["2", "A", "b"], &["3", "A", "B"]]),
+            headers(&["id", "x", "y"]),
+            &config,
+        );
+        let rows: Vec<(&str, &str)> = diffs.iter().map(|diff| (diff.key.as_str(), diff.column.as_str())).collect();
+        // Key 2 changes one of its two shared columns, which is not below 50%
+        assert_eq!(rows, [("1", "gone"), ("2", "x"), ("2", "gone"), ("3", "x"), ("3", "y"), ("3", "gone")]);
+
+        let config = DiffConfig { min_diff_pct: Some(60.0), ..config };
+        let diffs = diff_from_records(records(&[&["1", "a", "b"]]), headers(&["id", "x", "y"]), records(&[&["1", "A", "b"]]), headers(&["id", "x", "y"]), &config);
+        assert!(diffs.is_empty());
+    }
+
+    #[test]
+    fn diff_limit_per_key_keeps_the_first_columns() {
+        let key = headers(&["id"]);
+        let config = DiffConfig { key_columns: &key, diff_limit_per_key: Some(2), ..DiffConfig::default() };
+        let diffs = diff_from_records(
+            records(&[&["1", "a", "b", "c", "d"]]),
+            headers(&["id", "w", "x", "y", "z"]),
+            records(&[&["1", "A", "B", "C", "D"]]),
+            headers(&["id", "w", "x", "y", "z"]),
+            &config,
+        );
+        let columns: Vec<&str> = diffs.iter().map(|diff| diff.column.as_str()).collect();
+        assert_eq!(columns, ["w", "x", "... (2 more columns changed)"]);
+        assert!(diffs[2].is_truncation());
+    }
+}
@@ -1,32 +1,164 @@
+mod analysis;
+mod anonymize;
+mod audit;
+mod browse;
+mod config;
+mod document;
+mod encoding;
+mod error;
+mod expect;
+mod fingerprint;
+mod input;
+mod integrity;
+mod metrics;
+mod output;
+mod patch;
+mod profile;
+mod sample;
+mod sarif;
+mod sha256;
+mod telemetry;
+mod types;
+
 use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
 use std::error::Error;
-use std::path::PathBuf;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
 use csv::{ReaderBuilder, StringRecord};
+use tabled::settings::Style;
 use tabled::{Table, Tabled};
-use rust_xlsxwriter::{Workbook, Worksheet, Format};
+use rust_xlsxwriter::{Chart, ChartType, DocProperties, Format, TableColumn, TableFunction, Workbook, Worksheet};
+
+use csvdiff::{
+    Comparison, ComparisonRule, DiffConfig, DiffMode, DiffRow, KeyedRecord, PreviewFormat, compare_rows, datetime, json, line_of, normalize,
+    timestamp, yaml,
+};
+use datetime::{NormalizeTimezone, Timezone};
+use encoding::{Decoding, LineEnding};
+use error::{CsvDiffError, ErrorFormat};
+use analysis::{Direction, NumericHistogram};
+use anonymize::Anonymizer;
+use input::Sheet;
+use integrity::ForeignKey;
+use output::{Verbosity, report, warning};
+use normalize::{CompareOptions, NumberLocale, compare_values, parse_phone_country, parse_tolerance};
+use sample::{SampleSize, Sampler};
+use types::ColumnTypes;
+
+/// Version plus the build metadata embedded by `build.rs`.
+const BUILD_INFO: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\ncommit: ",
+    env!("CSVDIFF_GIT_HASH"),
+    "\nbuilt: ",
+    env!("CSVDIFF_BUILD_TIMESTAMP"),
+    "\nrustc: ",
+    env!("CSVDIFF_RUSTC_VERSION"),
+    "\ntarget: ",
+    env!("CSVDIFF_TARGET"),
+);
 
 /// Compare two CSV files based on key column(s), with options to ignore some columns.
-#[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[derive(Parser, Clone, Debug)]
+#[command(author, version, long_version = BUILD_INFO, about)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[command(group(ArgGroup::new("output_destination").args(["output_file", "split_output"]).multiple(true)))]
 struct Args {
-    /// First CSV file path
-    #[arg(long)]
-    file1: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// First CSV file path (repeat to concatenate parts that share a header)
+    #[arg(long, required = true)]
+    file1: Vec<PathBuf>,
+
+    /// Second CSV file path (repeat to concatenate parts that share a header)
+    #[arg(long, required = true)]
+    file2: Vec<PathBuf>,
+
+    /// Input file format (auto-detected from the extension by default)
+    #[arg(long, value_enum, default_value = "auto")]
+    format: InputFormat,
+
+    /// Column layout for --format fixed: "NAME:START:LENGTH,..." with zero-based character positions
+    #[arg(long, value_delimiter = ',', value_parser = parse_fixed_column)]
+    column_widths: Vec<FixedColumn>,
 
-    /// Second CSV file path
+    /// Worksheet of Excel input to read, by name (default: the first sheet)
+    #[arg(long, conflicts_with = "sheet_index")]
+    sheet_name: Option<String>,
+
+    /// Worksheet of Excel input to read, by zero-based position
     #[arg(long)]
-    file2: PathBuf,
+    sheet_index: Option<usize>,
+
+    /// Compare every sheet the two workbooks have in common, matched by name
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = [
+            "sheet_name", "sheet_index", "quiet", "quiet2", "browse", "first_diff", "excel_output", "output_file",
+            "revert_output", "metrics_output", "changed_output_file1", "changed_output_file2", "added_output",
+            "removed_output", "split_output", "anonymize_map", "fingerprint", "check", "expected_diffs",
+        ]
+    )]
+    all_sheets: bool,
+
+    /// Drop the empty last column created by a comma at the end of every CSV line
+    #[arg(long, default_value = "false")]
+    ignore_trailing_delimiter: bool,
+
+    /// Character that quotes CSV fields, e.g. "'" or "`"
+    #[arg(long, default_value = "\"", value_parser = parse_csv_char)]
+    quote_char: u8,
+
+    /// Character that escapes a quote inside a quoted field, e.g. '\' for \" (default: quotes are doubled)
+    #[arg(long, value_parser = parse_csv_char)]
+    escape_char: Option<u8>,
+
+    /// Detect each text input's encoding (UTF-8, UTF-16 or Windows-1252) and transcode it to UTF-8
+    #[arg(long, default_value = "false")]
+    auto_detect_encoding: bool,
+
+    /// Line ending of text inputs; with auto, any of LF, CRLF and CR ends a record
+    #[arg(long, value_enum, default_value = "auto")]
+    line_ending: LineEnding,
 
     /// Key columns (repeat for composite keys)
     #[arg(short, long)]
     key: Vec<String>,
 
-    /// Columns to ignore when comparing
+    /// Warn about key values longer than N characters
+    #[arg(long)]
+    max_key_length: Option<usize>,
+
+    /// Fail instead of warning when a key value exceeds --max-key-length
+    #[arg(long, default_value = "false", requires = "max_key_length")]
+    strict_key_length: bool,
+
+    /// Separator used to join composite key values; pick one that never occurs in the key columns
+    #[arg(long, default_value = "|", value_parser = parse_key_separator)]
+    key_sep: String,
+
+    /// Columns to ignore when comparing (repeat, or separate with commas; quote names containing commas)
     #[arg(short = 'i', long)]
     ignore: Vec<String>,
 
+    /// Zero-based positions of file1 columns to ignore, e.g. 3,7; each is resolved to its column name, so every column sharing that name is ignored
+    #[arg(long, value_delimiter = ',')]
+    ignore_index: Vec<usize>,
+
+    /// Ignore columns for matching rows: "COLUMN=VALUE:ignore=COL1,COL2" (repeatable)
+    #[arg(long)]
+    rule: Vec<String>,
+
+    /// Which differences to report
+    #[arg(long, value_enum, default_value = "full")]
+    diff_mode: DiffMode,
+
     /// Maximum number of rows to display (default: 20)
     #[arg(long, default_value = "20")]
     max_rows: usize,
@@ -35,165 +167,2372 @@ struct Args {
     #[arg(long, default_value = "30")]
     max_cell_width: usize,
 
+    /// How the values of a missing row are shown
+    #[arg(long, value_enum, default_value = "plain")]
+    preview_format: PreviewFormat,
+
+    /// Maximum length of a missing-row preview (left-only and right-only modes show whole rows)
+    #[arg(long, default_value = "50")]
+    preview_length: usize,
+
+    /// Report comparison progress to stderr every N keys
+    #[arg(long, default_value_t = 10_000, value_parser = clap::value_parser!(u64).range(1..), conflicts_with = "no_progress")]
+    progress_interval: u64,
+
+    /// Do not report comparison progress
+    #[arg(long, default_value = "false")]
+    no_progress: bool,
+
+    /// Report rows missing from the other file by key only, without a preview of their values
+    #[arg(long, default_value = "false", conflicts_with_all = ["preview_format", "preview_length"])]
+    no_missing_row_preview: bool,
+
+    /// Border style of the terminal tables (default: unicode in UTF-8 locales, ascii otherwise)
+    #[arg(long, value_enum)]
+    table_style: Option<TableStyle>,
+
+    /// Default to ASCII table borders even in a UTF-8 locale
+    #[arg(long, default_value = "false", conflicts_with = "table_style")]
+    no_unicode: bool,
+
     /// Show all differences without truncation
     #[arg(long, default_value = "false")]
     no_truncate: bool,
 
+    /// Layout of the terminal listing
+    #[arg(long, value_enum, default_value = "table", conflicts_with_all = ["browse", "compact"])]
+    diff_format: DiffFormat,
+
+    /// Total line width of --diff-format side-by-side
+    #[arg(long, default_value = "200", value_parser = clap::value_parser!(u16).range(40..))]
+    side_by_side_width: u16,
+
+    /// Page through the differences at a line-based command prompt instead of printing the table
+    #[arg(long, default_value = "false")]
+    browse: bool,
+
+    /// Print nothing but fatal errors; exit with 1 when differences are found and 2 to 4 on errors
+    #[arg(short, long, default_value = "false", conflicts_with = "browse")]
+    quiet: bool,
+
+    /// Like --quiet, but suppress errors too and rely on the exit code alone
+    #[arg(long, default_value = "false", conflicts_with = "browse")]
+    quiet2: bool,
+
+    /// Compare without printing or writing anything: exit with 0 when the files match, 1 when they differ and 2 to 4 on errors
+    #[arg(long, default_value = "false", conflicts_with = "browse")]
+    check: bool,
+
+    /// How to print a fatal error on stderr: text, or a JSON object for CI pipelines
+    #[arg(long, value_enum, default_value = "text")]
+    error_format: ErrorFormat,
+
     /// Generate Excel report with summary, headers comparison, and data differences
     #[arg(long)]
     excel_output: Option<String>,
+
+    /// Add a Changed Rows sheet with the full file1 and file2 rows side by side, changes highlighted
+    #[arg(long, default_value = "false", requires = "excel_output")]
+    excel_changed_rows: bool,
+
+    /// Write at most N rows to the Excel Data Differences sheet (the Summary notes the truncation)
+    #[arg(long, requires = "excel_output")]
+    excel_max_rows: Option<usize>,
+
+    /// ZIP compression level of the Excel file, from 1 (fastest) to 9 (smallest)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=9), requires = "excel_output")]
+    excel_compress_level: Option<u8>,
+
+    /// Title of the Excel report, shown on the Summary sheet and stored as the workbook's Title property
+    #[arg(long, default_value = DEFAULT_REPORT_TITLE, requires = "excel_output")]
+    report_title: String,
+
+    /// Operator recorded in the Excel report (Summary sheet and Author property) and the json and yaml metadata, e.g. "Jane Smith <jane@example.com>"
+    #[arg(long)]
+    report_author: Option<String>,
+
+    /// Comparison date (YYYY-MM-DD) recorded on the Excel Summary sheet and in the json and yaml metadata
+    #[arg(long, value_parser = parse_report_date)]
+    report_date: Option<String>,
+
+    /// Identifier recorded in the Excel report (Summary sheet and Keywords property) and the json and yaml metadata, e.g. a batch name
+    #[arg(long)]
+    report_tag: Option<String>,
+
+    /// Write the Excel worksheets one after another instead of concurrently
+    #[arg(long, default_value = "false")]
+    no_parallel_excel: bool,
+
+    /// Excel background color for rows only in file2, as a 6-digit hex code
+    #[arg(long, default_value = "#90EE90", value_parser = parse_hex_color)]
+    excel_added_color: String,
+
+    /// Excel background color for rows only in file1, as a 6-digit hex code
+    #[arg(long, default_value = "#FFB6C1", value_parser = parse_hex_color)]
+    excel_removed_color: String,
+
+    /// Excel background color for changed values, as a 6-digit hex code
+    #[arg(long, default_value = "#FFFFE0", value_parser = parse_hex_color)]
+    excel_changed_color: String,
+
+    /// Write the Excel report without background colors: bold headers, changed cells in italics
+    #[arg(
+        long,
+        default_value = "false",
+        requires = "excel_output",
+        conflicts_with_all = ["excel_added_color", "excel_removed_color", "excel_changed_color"]
+    )]
+    no_color_excel: bool,
+
+    /// Excel report layout: standard, or minimal for the smallest file (no colors, titles, tables or charts)
+    #[arg(long, value_enum, default_value = "standard", requires = "excel_output")]
+    excel_style: ExcelStyle,
+
+    /// Additional output format written to --output-file (json and yaml also apply to --split-output)
+    #[arg(long, value_enum, requires = "output_destination")]
+    output_format: Option<OutputFormat>,
+
+    /// File to write the --output-format output to
+    #[arg(long, requires = "output_format")]
+    output_file: Option<String>,
+
+    /// Gzip the --output-file, --revert-output and row export files, adding .gz to their names
+    #[arg(long, default_value = "false")]
+    compress_output: bool,
+
+    /// Gzip level for --compress-output, from 1 (fastest) to 9 (smallest)
+    #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u32).range(1..=9), requires = "compress_output")]
+    compress_level: u32,
+
+    /// Replace key values with the first 12 hex digits of their SHA-256 in every output
+    #[arg(long, default_value = "false", requires = "key", conflicts_with_all = ["fuzzy_keys", "revert_output"])]
+    anonymize_keys: bool,
+
+    /// Write the mapping from anonymized keys back to the original values to this JSON file
+    #[arg(long, requires = "anonymize_keys")]
+    anonymize_map: Option<String>,
+
+    /// Secret mixed into every --anonymize-keys hash, so hashes cannot be reversed by guessing keys
+    #[arg(long, requires = "anonymize_keys")]
+    anonymize_salt: Option<String>,
+
+    /// Operator name recorded in the audit log
+    #[arg(long)]
+    operator: Option<String>,
+
+    /// Write Prometheus text-format metrics about the run to this file ("-" for stderr)
+    #[arg(long)]
+    metrics_output: Option<String>,
+
+    /// Send anonymous usage statistics (version, row and difference counts, output format,
+    /// duration; never file contents or paths) to the endpoint in ~/.csvdiff/telemetry.toml
+    #[arg(long, default_value = "false")]
+    enable_telemetry: bool,
+
+    /// Also write a patch that undoes the diff (file2 → file1), for reverting an applied patch
+    #[arg(long)]
+    revert_output: Option<String>,
+
+    /// Write the file1 version of every row with a changed value to this CSV file
+    #[arg(long)]
+    changed_output_file1: Option<String>,
+
+    /// Write the file2 version of every row with a changed value to this CSV file
+    #[arg(long)]
+    changed_output_file2: Option<String>,
+
+    /// Write the rows only in file2 to this CSV file
+    #[arg(long)]
+    added_output: Option<String>,
+
+    /// Write the rows only in file1 to this CSV file
+    #[arg(long)]
+    removed_output: Option<String>,
+
+    /// Rename the key, column, file1, file2 and similarity fields of the diff outputs, e.g. "file1=expected,file2=actual"
+    #[arg(long, value_delimiter = ',', value_name = "FIELD=NAME")]
+    rename_output_col: Vec<String>,
+
+    /// Write PREFIX_added.csv, PREFIX_removed.csv and PREFIX_changed.csv, one file per kind of difference
+    #[arg(long, value_name = "PREFIX")]
+    split_output: Option<String>,
+
+    /// Treat a file1 column as a file2 column (OLD=NEW, repeat for several columns)
+    #[arg(long)]
+    rename: Vec<String>,
+
+    /// CSV file of file1_column,file2_column pairs lining up two different schemas (a bulk --rename)
+    #[arg(long, conflicts_with = "rename")]
+    header_mapping: Option<PathBuf>,
+
+    /// With --header-mapping, fail when a non-key column is left out of the mapping instead of ignoring it
+    #[arg(long, default_value = "false", requires = "header_mapping")]
+    strict_mapping: bool,
+
+    /// Report columns that look like they were renamed between the two files
+    #[arg(long, default_value = "false")]
+    detect_renames: bool,
+
+    /// Match otherwise unmatched keys whose Jaro-Winkler similarity is at least THRESHOLD (0.0 to 1.0)
+    #[arg(long, value_name = "THRESHOLD")]
+    fuzzy_keys: Option<f64>,
+
+    /// Identify rows by all compared (non-ignored) columns, turning the diff into a set difference
+    #[arg(long, default_value = "false", conflicts_with_all = ["key", "fuzzy_keys"])]
+    no_key: bool,
+
+    /// Show the Levenshtein edit distance for each changed value
+    #[arg(long, default_value = "false")]
+    show_edit_distance: bool,
+
+    /// Show the line each differing row starts on in file1 and file2
+    #[arg(long, default_value = "false")]
+    show_line_numbers: bool,
+
+    /// Leave rows missing from one file out of the terminal table, showing only changed values
+    #[arg(long, alias = "no-missing-rows-in-diff", default_value = "false")]
+    ignore_missing: bool,
+
+    /// Show one row per changed key listing its changed columns, instead of one row per value
+    #[arg(long, default_value = "false")]
+    compact: bool,
+
+    /// With --compact, also show the before and after value of each changed column
+    #[arg(long, default_value = "false", requires = "compact")]
+    verbose: bool,
+
+    /// List every column with its key, ignored and presence status before comparing
+    #[arg(long, default_value = "false")]
+    columns_report: bool,
+
+    /// After the differences, print how many columns, rows and cells were compared
+    #[arg(long, default_value = "false")]
+    coverage_report: bool,
+
+    /// Report columns present in both files at different positions
+    #[arg(long, default_value = "false")]
+    detect_column_reorder: bool,
+
+    /// Infer each column's type (integer, float, boolean, date, datetime or string) for the json and yaml metadata and the Excel Headers Comparison sheet
+    #[arg(long, default_value = "false")]
+    infer_types: bool,
+
+    /// Sort both files' columns by name before comparing, so column order never counts as a difference
+    #[arg(long, default_value = "false", conflicts_with = "revert_output")]
+    normalize_column_order: bool,
+
+    /// Report rows whose two changed columns simply swapped values as one transposition
+    #[arg(long, default_value = "false")]
+    detect_transpositions: bool,
+
+    /// Report unmatched rows that look split into, or merged from, several rows of the other file
+    #[arg(long, default_value = "false")]
+    detect_splits_merges: bool,
+
+    /// Report numeric columns whose changed values are consistently shifted by the same amount
+    #[arg(long, default_value = "false")]
+    detect_offsets: bool,
+
+    /// After the diff, show how many changed numeric values moved by 0-1%, 1-5%, 5-10% and over 10%
+    #[arg(long, default_value = "false")]
+    numeric_histogram: bool,
+
+    /// Report pairs of columns that change in the same rows
+    #[arg(long, default_value = "false")]
+    co_occurrence_report: bool,
+
+    /// Key column holding the time of each row: report whether each series' numeric and date changes go up, down or both
+    #[arg(long, value_name = "COLUMN", requires = "key", conflicts_with_all = ["anonymize_keys", "no_key"])]
+    time_column: Option<String>,
+
+    /// Report numeric columns whose changed values are consistently off by the same factor
+    #[arg(long, default_value = "false")]
+    detect_scaling: bool,
+
+    /// Relative tolerance for treating two scaling ratios as the same factor (non-negative)
+    #[arg(long, default_value = "0.001", value_parser = parse_tolerance)]
+    scaling_tolerance: f64,
+
+    /// Comma-separated values that mean null, e.g. "NULL,N/A,\N" (compared case-insensitively)
+    #[arg(long, value_delimiter = ',')]
+    null_values: Vec<String>,
+
+    /// Treat empty strings as null, so they equal any --null-values marker
+    #[arg(long, default_value = "false")]
+    null_equals_empty: bool,
+
+    /// Treat percentages and their decimal form as equal (50% == 0.5)
+    #[arg(long, default_value = "false")]
+    normalize_percentages: bool,
+
+    /// Compare decimal, hex (0x), binary (0b) and octal (0o; a leading 0 alone stays decimal) integers by value
+    #[arg(long, default_value = "false")]
+    normalize_integers: bool,
+
+    /// Ignore case, braces and hyphens when comparing UUID values
+    #[arg(long, default_value = "false")]
+    normalize_uuids: bool,
+
+    /// Compare IP addresses and CIDR blocks by value (::ffff:192.168.1.1 == 192.168.1.1)
+    #[arg(long, default_value = "false")]
+    normalize_ip_addresses: bool,
+
+    /// Compare these columns as phone numbers, by their digits ("+1 (800) 555-1234" == "1-800-555-1234")
+    #[arg(long, value_delimiter = ',', value_name = "COLUMNS")]
+    normalize_phone_numbers: Vec<String>,
+
+    /// Country whose calling code is added to phone numbers written without one, e.g. US or +44
+    #[arg(long, value_parser = parse_phone_country, requires = "normalize_phone_numbers")]
+    phone_country: Option<String>,
+
+    /// Read numbers with this locale's decimal and grouping separators and compare them by value
+    #[arg(long, value_enum)]
+    locale: Option<NumberLocale>,
+
+    /// Compare numbers in plain and scientific notation by value (1e3 == 1000.0 == 1.0E+3)
+    #[arg(long, default_value = "false")]
+    normalize_scientific: bool,
+
+    /// Treat numbers differing by at most this amount as equal (implies --normalize-scientific)
+    #[arg(long, value_parser = parse_tolerance)]
+    tolerance: Option<f64>,
+
+    /// Convert ISO 8601 datetimes to this zone before comparing them (only UTC is supported)
+    #[arg(long, value_enum, ignore_case = true)]
+    timezone_normalize: Option<NormalizeTimezone>,
+
+    /// Read datetimes without an offset in this zone, e.g. America/New_York or +05:30
+    #[arg(long, value_parser = Timezone::load, requires = "timezone_normalize")]
+    assume_timezone: Option<Timezone>,
+
+    /// Show at most N changed columns per key, then one "... (M more columns changed)" row
+    #[arg(long, conflicts_with = "revert_output")]
+    diff_limit_per_key: Option<usize>,
+
+    /// Stop comparing once N differences have been found, keeping the first ones in key order
+    #[arg(long, conflicts_with = "revert_output")]
+    diff_limit: Option<usize>,
+
+    /// Sort the differences by key, ascending, in every output
+    #[arg(long, default_value = "false", conflicts_with_all = ["sort_keys_desc", "first_diff"])]
+    sort_keys: bool,
+
+    /// Sort the differences by key, descending, in every output
+    #[arg(long, default_value = "false", conflicts_with = "first_diff")]
+    sort_keys_desc: bool,
+
+    /// Exit with code 0 only when the differences match the ones saved in this JSON file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["first_diff", "check", "fingerprint", "compress_output"])]
+    expected_diffs: Option<String>,
+
+    /// Save this run's differences to the --expected-diffs file instead of checking them
+    #[arg(long, default_value = "false", requires = "expected_diffs")]
+    update_expected: bool,
+
+    /// Stop at the first difference in file order, print it and exit with code 1 (0 when identical)
+    #[arg(long, default_value = "false", conflicts_with_all = ["diff_limit", "browse", "excel_output", "output_format", "revert_output"])]
+    first_diff: bool,
+
+    /// Hide changed rows where fewer than this percentage of compared columns differ (0-100)
+    #[arg(long, value_parser = parse_percentage)]
+    min_diff_pct: Option<f64>,
+
+    /// Compare a random sample of keys, as a percentage ("1%") or a row count ("50000")
+    #[arg(long, value_parser = sample::parse_sample_size)]
+    sample: Option<SampleSize>,
+
+    /// Seed for --sample so the same rows are picked on every run
+    #[arg(long, requires = "sample")]
+    sample_seed: Option<u64>,
+
+    /// Stop reading each file after its first N distinct keys, for a quick look at large files
+    #[arg(long)]
+    limit_keys: Option<usize>,
+
+    /// Check that file1 values exist in a reference file: REF_FILE:REF_COL or COLUMN=REF_FILE:REF_COL (repeatable)
+    #[arg(long, value_parser = integrity::parse_foreign_key)]
+    foreign_key: Vec<ForeignKey>,
+
+    /// Abort instead of warning when key columns do not uniquely identify rows
+    #[arg(long, default_value = "false")]
+    strict_keys: bool,
+
+    /// Report rows that are exact copies (all fields identical) within each file
+    #[arg(long, default_value = "false")]
+    check_duplicates: bool,
+
+    /// Remove exact duplicate rows before the diff, or abort on them (implies --check-duplicates)
+    #[arg(long, value_enum)]
+    dedup_strategy: Option<DedupStrategy>,
+
+    /// Print a SHA-256 fingerprint of the diff and exit with status 1 if it changed since the last run
+    #[arg(long, default_value = "false")]
+    fingerprint: bool,
+
+    /// Name the stored fingerprint is kept under (defaults to the profile name or the input paths)
+    #[arg(long)]
+    fingerprint_name: Option<String>,
+
+    /// Read default options from a TOML file; options given on the command line take precedence
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Print version, git commit, build time, compiler and target, then exit
+    #[arg(long, action = clap::ArgAction::Version)]
+    build_info: Option<bool>,
 }
 
-fn read_csv_to_map(
-    path: PathBuf,
-    key_columns: &[String],
-) -> Result<(Vec<String>, HashMap<String, StringRecord>, Vec<String>), Box<dyn Error>> {
-    let mut rdr = ReaderBuilder::new().from_path(path)?;
-    let headers = rdr.headers()?.clone();
+#[derive(Subcommand, Clone, Debug)]
+enum Command {
+    /// Apply a patch produced with --output-format patch to a CSV file
+    Apply(patch::ApplyArgs),
+    /// Save, run and list named comparison profiles
+    Profile(profile::ProfileArgs),
+    /// Print a commented TOML template of every option a --config file accepts
+    ConfigSchema,
+}
 
-    let mut key_indexes = Vec::new();
-    let mut missing_keys = Vec::new();
-    
-    for key in key_columns {
-        if let Some(index) = headers.iter().position(|h| h == key) {
-            key_indexes.push(index);
+/// Layout of the terminal listing (--diff-format).
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DiffFormat {
+    /// One row per difference: key, column, file1 value, file2 value
+    Table,
+    /// Each differing row in full, file1 on the left and file2 on the right, like `diff --side-by-side`
+    SideBySide,
+}
+
+/// Border style of the terminal tables (--table-style).
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum TableStyle {
+    /// `+`, `-` and `|` borders
+    Ascii,
+    /// Box-drawing borders
+    Unicode,
+    /// Box-drawing borders with rounded corners
+    Rounded,
+    /// GitHub-flavored Markdown table
+    Markdown,
+    /// No borders, columns separated by spaces
+    Blank,
+}
+
+impl TableStyle {
+    /// Unicode when the locale says the terminal uses UTF-8 (and --no-unicode is not set).
+    fn detect(no_unicode: bool) -> TableStyle {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+            .unwrap_or_default()
+            .to_lowercase();
+        if !no_unicode && (locale.contains("utf-8") || locale.contains("utf8")) {
+            TableStyle::Unicode
         } else {
-            missing_keys.push(key.clone());
+            TableStyle::Ascii
         }
     }
 
-    let mut map = HashMap::new();
-    
-    // Only process records if we found all key columns
-    if missing_keys.is_empty() {
-        for result in rdr.records() {
-            let record = result?;
-            let key_parts: Vec<&str> = key_indexes
-                .iter()
-                .map(|&i| record.get(i).unwrap_or(""))
-                .collect();
-            let key = key_parts.join("|");
-            map.insert(key, record);
-        }
+    fn render(self, mut table: Table) -> String {
+        match self {
+            TableStyle::Ascii => table.with(Style::ascii()),
+            TableStyle::Unicode => table.with(Style::modern()),
+            TableStyle::Rounded => table.with(Style::rounded()),
+            TableStyle::Markdown => table.with(Style::markdown()),
+            TableStyle::Blank => table.with(Style::blank()),
+        };
+        table.to_string()
     }
+}
 
-    Ok((headers.iter().map(|s| s.to_string()).collect(), map, missing_keys))
+/// Machine-readable formats for --output-file.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// Replayable patch for `csvdiff apply`
+    Patch,
+    /// JSON Lines audit log with timestamp, host, user and input file versions per difference
+    Audit,
+    /// One JSON document: a summary of the inputs and totals, and the list of differences
+    Json,
+    /// The json document in YAML
+    Yaml,
+    /// SARIF 2.1.0 log for code scanning tools, one result per difference
+    Sarif,
 }
 
-#[derive(Tabled, Clone)]
-struct DiffRow {
-    key: String,
-    column: String,
-    file1: String,
-    file2: String,
+/// Layouts of the Excel report (--excel-style).
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ExcelStyle {
+    /// Titles, colored rows, an Excel table and a chart
+    Standard,
+    /// Bold headers and nothing else, without sheet title rows
+    Minimal,
 }
 
-fn truncate_string(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        return s.to_string();
+/// Input file formats accepted by --format.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum InputFormat {
+    /// Detect the format from the file extension
+    Auto,
+    Csv,
+    /// Not supported: recognised only to fail with a clear error instead of misreading the file
+    Parquet,
+    /// Newline-delimited JSON objects (.jsonl / .ndjson)
+    Jsonl,
+    /// Fixed-width columns defined by --column-widths
+    Fixed,
+    /// Excel workbook (.xlsx / .xlsm); the first row of the sheet is the header
+    Xlsx,
+    /// Legacy Excel workbook (.xls)
+    Xls,
+    /// OpenDocument spreadsheet (.ods)
+    Ods,
+}
+
+impl InputFormat {
+    /// Resolve `Auto` using the file extension, or for other extensions the file's first
+    /// bytes, defaulting to CSV.
+    fn resolve(self, path: &Path) -> InputFormat {
+        if self != InputFormat::Auto {
+            return self;
+        }
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("parquet") => InputFormat::Parquet,
+            Some("jsonl" | "ndjson") => InputFormat::Jsonl,
+            Some("xlsx" | "xlsm") => InputFormat::Xlsx,
+            Some("xls") => InputFormat::Xls,
+            Some("ods") => InputFormat::Ods,
+            Some("csv" | "tsv" | "txt") => InputFormat::Csv,
+            _ => InputFormat::sniff(path),
+        }
     }
-    
-    // Find a safe UTF-8 character boundary
-    let mut truncate_at = max_len.saturating_sub(3); // Reserve space for "..."
-    while truncate_at > 0 && !s.is_char_boundary(truncate_at) {
-        truncate_at -= 1;
+
+    /// Recognise workbooks by their magic bytes: a ZIP archive (.xlsx or .ods) or an OLE
+    /// compound file (.xls).
+    fn sniff(path: &Path) -> InputFormat {
+        let mut magic = [0u8; 8];
+        let read = std::fs::File::open(path).and_then(|mut file| std::io::Read::read_exact(&mut file, &mut magic));
+        match magic {
+            _ if read.is_err() => InputFormat::Csv,
+            [b'P', b'K', 3, 4, ..] if input::is_xlsx(path) => InputFormat::Xlsx,
+            [b'P', b'K', 3, 4, ..] if input::is_ods(path) => InputFormat::Ods,
+            [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1] => InputFormat::Xls,
+            _ => InputFormat::Csv,
+        }
     }
-    
-    format!("{}...", &s[..truncate_at])
 }
 
-fn truncate_for_excel(s: &str) -> String {
-    const EXCEL_MAX_CELL_LENGTH: usize = 32767;
-    if s.len() <= EXCEL_MAX_CELL_LENGTH {
-        return s.to_string();
+/// URL schemes of cloud object stores, which csvdiff cannot read, with the command that copies
+/// an object to a local file
+const OBJECT_STORE_SCHEMES: [(&str, &str); 2] = [("s3://", "aws s3 cp"), ("gs://", "gcloud storage cp")];
+
+/// Fail early for formats this build cannot read.
+fn ensure_supported_format(path: &Path, format: InputFormat) -> Result<(), Box<dyn Error>> {
+    let location = path.to_string_lossy();
+    if let Some((scheme, copy)) = OBJECT_STORE_SCHEMES.iter().find(|(scheme, _)| location.starts_with(scheme)) {
+        return Err(format!(
+            "Cannot read {}: {} URLs are not supported; csvdiff only reads local files. \
+             Copy the object to a local file first (e.g. {} {} file.csv).",
+            location, scheme, copy, location
+        )
+        .into());
     }
-    
-    // Find a safe UTF-8 character boundary
-    let mut truncate_at = EXCEL_MAX_CELL_LENGTH.saturating_sub(3); // Reserve space for "..."
-    while truncate_at > 0 && !s.is_char_boundary(truncate_at) {
-        truncate_at -= 1;
+    match format {
+        InputFormat::Parquet => Err(format!(
+            "Cannot read {}: Parquet input is not supported. \
+             Convert the file to CSV first (e.g. with DuckDB: COPY (SELECT * FROM 'file.parquet') TO 'file.csv').",
+            path.display()
+        )
+        .into()),
+        InputFormat::Xls | InputFormat::Ods => Err(format!(
+            "Cannot read {}: only .xlsx workbooks are supported. Save it as .xlsx or export the sheet to CSV first.",
+            path.display()
+        )
+        .into()),
+        InputFormat::Auto | InputFormat::Csv | InputFormat::Jsonl | InputFormat::Fixed | InputFormat::Xlsx => Ok(()),
     }
-    
-    format!("{}...", &s[..truncate_at])
 }
 
-fn check_schema_match(headers1: &[String], headers2: &[String]) -> (bool, bool) {
-    // Check if headers content matches (regardless of order)
-    let set1: HashSet<&String> = headers1.iter().collect();
-    let set2: HashSet<&String> = headers2.iter().collect();
-    let headers_content_match = set1 == set2;
-    
-    // Check if headers and order are identical
-    let schema_identical = headers1 == headers2;
-    
-    (headers_content_match, schema_identical)
+/// What to do with exact duplicate rows.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DedupStrategy {
+    /// Drop later copies; the first copy keeps its place among rows with the same key
+    KeepFirst,
+    /// Drop earlier copies; the last copy keeps its place among rows with the same key
+    KeepLast,
+    /// Abort when any exact duplicate row is found
+    Error,
 }
 
-fn validate_keys_and_suggest_alternatives(
-    _key_columns: &[String],
-    missing_keys1: &[String],
-    missing_keys2: &[String],
-    headers1: &[String],
-    headers2: &[String],
-) -> Result<(), Box<dyn Error>> {
-    if missing_keys1.is_empty() && missing_keys2.is_empty() {
-        return Ok(());
+/// Rows of a CSV file indexed by their composite key.
+struct CsvData {
+    headers: Vec<String>,
+    records: HashMap<String, StringRecord>,
+    /// Key columns that could not be found in the header row
+    missing_keys: Vec<String>,
+    /// Composite keys that occur more than once, with their occurrence counts
+    duplicate_keys: Vec<(String, usize)>,
+    /// Key of every exact copy of an earlier row, in file order (only with --check-duplicates)
+    duplicate_rows: Vec<String>,
+    /// Number of data rows in the file, including rows left out by sampling
+    total_rows: usize,
+    /// Whether reading stopped early at --limit-keys
+    limited: bool,
+    /// The input part of each key read from a second or later part; keys from the first part
+    /// are left out, so a single file costs nothing
+    parts: HashMap<String, u64>,
+}
+
+/// A `--column-widths` entry: `NAME:START:LENGTH`, in characters.
+#[derive(Debug, Clone)]
+struct FixedColumn {
+    name: String,
+    start: usize,
+    length: usize,
+}
+
+fn parse_fixed_column(value: &str) -> Result<FixedColumn, String> {
+    let invalid = || format!("invalid column width '{}': expected NAME:START:LENGTH", value);
+    let mut parts = value.rsplitn(3, ':');
+    let length = parts.next().and_then(|p| p.trim().parse().ok()).ok_or_else(invalid)?;
+    let start: usize = parts.next().and_then(|p| p.trim().parse().ok()).ok_or_else(invalid)?;
+    let name = parts.next().map(str::trim).filter(|n| !n.is_empty()).ok_or_else(invalid)?;
+    if start.checked_add(length).is_none() {
+        return Err(format!("invalid column width '{}': the column ends past the largest supported position", value));
     }
+    Ok(FixedColumn {
+        name: name.to_string(),
+        start,
+        length,
+    })
+}
 
-    eprintln!("❌ Error: Missing key columns detected");
-    
-    if !missing_keys1.is_empty() {
-        eprintln!("   File 1 is missing key columns: {}", missing_keys1.join(", "));
+/// A --quote-char or --escape-char: one ASCII character other than the comma and line breaks.
+/// `\\` is taken as a backslash, for shells that pass it through.
+fn parse_csv_char(value: &str) -> Result<u8, String> {
+    let value = if value == "\\\\" { "\\" } else { value };
+    match value.as_bytes() {
+        [b',' | b'\n' | b'\r'] => Err(format!("'{}' cannot quote or escape CSV fields", value.escape_debug())),
+        [byte] if byte.is_ascii() => Ok(*byte),
+        _ => Err(format!("invalid character '{}': expected a single ASCII character such as ' or \\", value)),
     }
-    
-    if !missing_keys2.is_empty() {
-        eprintln!("   File 2 is missing key columns: {}", missing_keys2.join(", "));
+}
+
+/// A --key-sep: any non-empty text, as an empty separator would run the key values together.
+fn parse_key_separator(value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        return Err("the separator cannot be empty, or keys such as 'ab'+'c' and 'a'+'bc' would match".to_string());
     }
+    Ok(value.to_string())
+}
 
-    // Suggest common columns that exist in both files
-    let common_columns: Vec<&String> = headers1
-        .iter()
-        .filter(|col| headers2.contains(col))
-        .collect();
-    
-    if !common_columns.is_empty() {
-        eprintln!("\n💡 Suggested alternative key columns (present in both files):");
-        for col in common_columns.iter().take(5) { // Limit suggestions to 5
-            eprintln!("   • {}", col);
+fn parse_hex_color(value: &str) -> Result<String, String> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid color '{}': expected a 6-digit hex code such as #90EE90", value));
+    }
+    Ok(format!("#{}", hex.to_uppercase()))
+}
+
+/// Parse --rename-output-col `FIELD=NAME` pairs, which must rename distinct diff fields to
+/// distinct names.
+fn parse_output_renames(values: &[String]) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut renames: Vec<(String, String)> = Vec::new();
+    for value in values {
+        let (field, name) = value
+            .split_once('=')
+            .map(|(field, name)| (field.trim(), name.trim()))
+            .filter(|(_, name)| !name.is_empty())
+            .ok_or_else(|| format!("Invalid --rename-output-col '{}': expected FIELD=NAME, e.g. file1=expected", value))?;
+        if !output::DIFF_FIELDS.contains(&field) && field != output::SIMILARITY_FIELD {
+            return Err(format!("--rename-output-col can rename {}, {}, not '{}'", output::DIFF_FIELDS.join(", "), output::SIMILARITY_FIELD, field).into());
         }
-        if common_columns.len() > 5 {
-            eprintln!("   ... and {} more", common_columns.len() - 5);
+        if renames.iter().any(|(from, _)| from == field) {
+            return Err(format!("--rename-output-col renames '{}' twice", field).into());
+        }
+        renames.push((field.to_string(), name.to_string()));
+    }
+    let names: Vec<&str> = output::DIFF_FIELDS.iter().chain([&output::SIMILARITY_FIELD]).map(|field| renames.iter().find(|(from, _)| from == field).map_or(*field, |(_, to)| to.as_str())).collect();
+    if let Some(name) = names.iter().enumerate().find_map(|(i, name)| names[..i].contains(name).then_some(name)) {
+        return Err(format!("--rename-output-col gives two fields the name '{}'", name).into());
+    }
+    Ok(renames)
+}
+
+/// A --min-diff-pct: a percentage from 0 to 100.
+fn parse_percentage(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(percentage) if (0.0..=100.0).contains(&percentage) => Ok(percentage),
+        _ => Err(format!("invalid percentage '{}': expected a number from 0 to 100", value)),
+    }
+}
+
+fn parse_report_date(value: &str) -> Result<String, String> {
+    if !types::is_date(value) {
+        return Err(format!("invalid date '{}': expected YYYY-MM-DD such as 2024-01-15", value));
+    }
+    Ok(value.to_string())
+}
+
+impl Args {
+    fn dialect(&self) -> CsvDialect {
+        CsvDialect {
+            quote: self.quote_char,
+            escape: self.escape_char,
+            strip_trailing_delimiter: self.ignore_trailing_delimiter,
+            decoding: Decoding { auto_detect: self.auto_detect_encoding, line_ending: self.line_ending },
+        }
+    }
+
+    /// The worksheet selected for Excel input.
+    fn sheet(&self) -> Sheet {
+        match (&self.sheet_name, self.sheet_index) {
+            (Some(name), _) => Sheet::Name(name.clone()),
+            (None, Some(index)) => Sheet::Index(index),
+            (None, None) => Sheet::default(),
+        }
+    }
+
+    fn verbosity(&self) -> Verbosity {
+        // --check is silent unless --quiet asks for its errors
+        match (self.quiet2, self.quiet, self.check) {
+            (true, _, _) | (false, false, true) => Verbosity::Silent,
+            (false, true, _) => Verbosity::Quiet,
+            (false, false, false) => Verbosity::Normal,
+        }
+    }
+
+    /// How the run prints its messages and writes its output files.
+    fn settings(&self) -> Result<output::Settings, Box<dyn Error>> {
+        Ok(output::Settings {
+            verbosity: self.verbosity(),
+            compression: self.compress_output.then_some(self.compress_level),
+            field_renames: parse_output_renames(&self.rename_output_col)?,
+        })
+    }
+
+    fn table_style(&self) -> TableStyle {
+        self.table_style.unwrap_or_else(|| TableStyle::detect(self.no_unicode))
+    }
+}
+
+/// How text inputs are decoded, and how CSV inputs are quoted and delimited.
+#[derive(Clone, Copy)]
+struct CsvDialect {
+    quote: u8,
+    /// Escape character for quotes in quoted fields, instead of doubling them
+    escape: Option<u8>,
+    /// Drop the empty last column created by a comma ending every line
+    strip_trailing_delimiter: bool,
+    decoding: Decoding,
+}
+
+impl CsvDialect {
+    fn reader(self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder.quote(self.quote).escape(self.escape).terminator(self.decoding.terminator());
+        builder
+    }
+}
+
+/// Settings shared by both input files.
+struct ReadOptions<'a> {
+    format: InputFormat,
+    sheet: &'a Sheet,
+    key_columns: &'a [String],
+    fixed_columns: &'a [FixedColumn],
+    sampler: Option<&'a Sampler>,
+    check_duplicates: bool,
+    dedup_strategy: Option<DedupStrategy>,
+    limit_keys: Option<usize>,
+    key_separator: &'a str,
+    dialect: CsvDialect,
+    max_key_length: Option<usize>,
+    strict_key_length: bool,
+    /// Shows key values in warnings by their --anonymize-keys hash
+    anonymizer: Option<&'a Anonymizer>,
+    settings: &'a output::Settings,
+}
+
+type RecordIter = Box<dyn Iterator<Item = Result<StringRecord, Box<dyn Error>>>>;
+
+/// A record built from parsed values, positioned at its source line like a CSV record.
+fn record_at_line(values: Vec<String>, line: u64) -> StringRecord {
+    let mut record = StringRecord::from(values);
+    let mut position = csv::Position::new();
+    position.set_line(line);
+    record.set_position(Some(position));
+    record
+}
+
+/// Records paired with the index of the input part they were read from.
+type PartRecords = Box<dyn Iterator<Item = Result<(u64, StringRecord), Box<dyn Error>>>>;
+
+/// Number each record with the input part it was read from; lines restart at 1 in every
+/// part, so they alone cannot order rows across parts.
+fn in_part(records: RecordIter, part: u64) -> PartRecords {
+    Box::new(records.map(move |record| record.map(|record| (part, record))))
+}
+
+/// Where a keyed record sits in its input: the part it was read from (see
+/// [`CsvData::parts`]), then its line.
+fn input_order(parts: &HashMap<String, u64>, (key, record): KeyedRecord) -> (u64, u64) {
+    (parts.get(key).copied().unwrap_or(0), line_of(record).unwrap_or(0))
+}
+
+/// Read a JSON Lines file, one flat object per line, into CSV-shaped records.
+///
+/// Columns are the union of all object keys in first-seen order; keys missing from a line
+/// become empty strings. Nested values are kept as compact JSON text and invalid lines are
+/// skipped with a warning.
+fn read_jsonl_records(path: &Path, decoding: Decoding, settings: &output::Settings) -> Result<(StringRecord, RecordIter), Box<dyn Error>> {
+    let content = decoding.read_to_string(path)?;
+    let mut columns: Vec<String> = Vec::new();
+    let mut column_index: HashMap<String, usize> = HashMap::new();
+    let mut rows: Vec<(u64, Vec<(usize, String)>)> = Vec::new();
+    let mut skipped = 0;
+
+    for (line_number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entries = match json::Json::parse(line) {
+            Ok(json::Json::Object(entries)) => entries,
+            Ok(_) => {
+                warning!(settings, "⚠️  Skipping {} line {}: not a JSON object", path.display(), line_number + 1);
+                skipped += 1;
+                continue;
+            }
+            Err(e) => {
+                warning!(settings, "⚠️  Skipping {} line {}: invalid JSON ({})", path.display(), line_number + 1, e);
+                skipped += 1;
+                continue;
+            }
+        };
+
+        let row = entries
+            .into_iter()
+            .map(|(key, value)| {
+                let index = *column_index.entry(key.clone()).or_insert_with(|| {
+                    columns.push(key);
+                    columns.len() - 1
+                });
+                let text = match value {
+                    json::Json::Null => String::new(),
+                    json::Json::String(s) | json::Json::Number(s) => s,
+                    json::Json::Bool(b) => b.to_string(),
+                    nested => nested.to_string(),
+                };
+                (index, text)
+            })
+            .collect();
+        rows.push((line_number as u64 + 1, row));
+    }
+
+    if skipped > 0 {
+        warning!(settings, "⚠️  {} invalid lines skipped in {}", skipped, path.display());
+    }
+
+    let width = columns.len();
+    let records = rows.into_iter().map(move |(line, row)| {
+        let mut values = vec![String::new(); width];
+        for (index, text) in row {
+            values[index] = text;
+        }
+        Ok(record_at_line(values, line))
+    });
+    Ok((StringRecord::from(columns), Box::new(records)))
+}
+
+/// Read a fixed-width file by slicing each line at character positions.
+///
+/// Values are trimmed of padding; lines shorter than a column yield a partial or empty value.
+fn read_fixed_width_records(path: &Path, columns: &[FixedColumn], decoding: Decoding) -> Result<(StringRecord, RecordIter), Box<dyn Error>> {
+    if columns.is_empty() {
+        return Err("--format fixed requires --column-widths, e.g. \"id:0:10,name:10:30\"".into());
+    }
+    let content = decoding.read_to_string(path)?;
+    let headers = StringRecord::from(columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>());
+    let columns = columns.to_vec();
+
+    let lines: Vec<(u64, String)> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i as u64 + 1, line.to_string()))
+        .collect();
+    let records = lines.into_iter().map(move |(line_number, line)| {
+        // Character positions, not bytes, so multi-byte UTF-8 text keeps its alignment
+        let chars: Vec<char> = line.chars().collect();
+        let values: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                let start = column.start.min(chars.len());
+                let end = (column.start + column.length).min(chars.len());
+                chars[start..end].iter().collect::<String>().trim().to_string()
+            })
+            .collect();
+        Ok(record_at_line(values, line_number))
+    });
+    Ok((headers, Box::new(records)))
+}
+
+/// Open one input file: its header row and a streaming iterator over its records.
+///
+/// With `dialect.strip_trailing_delimiter`, a comma ending every line is dropped: an unnamed
+/// last header goes, and so does an empty last field beyond the header's width.
+fn open_records(
+    path: &Path,
+    format: InputFormat,
+    sheet: &Sheet,
+    fixed_columns: &[FixedColumn],
+    dialect: CsvDialect,
+    settings: &output::Settings,
+) -> Result<(StringRecord, RecordIter), Box<dyn Error>> {
+    match format.resolve(path) {
+        InputFormat::Jsonl => read_jsonl_records(path, dialect.decoding, settings),
+        InputFormat::Fixed => read_fixed_width_records(path, fixed_columns, dialect.decoding),
+        InputFormat::Xlsx => input::ExcelReader::open(path)?.read_sheet(sheet),
+        _ if dialect.strip_trailing_delimiter => {
+            let mut rdr = dialect.reader().flexible(true).from_reader(dialect.decoding.open(path)?);
+            let mut headers = rdr.headers().map_err(|e| explain_csv_error(path, e))?.clone();
+            if headers.len() > 1 && headers.iter().next_back() == Some("") {
+                headers.truncate(headers.len() - 1);
+            }
+            let width = headers.len();
+            let path = path.to_path_buf();
+            let records = rdr.into_records().map(move |record| {
+                let mut record = record.map_err(|e| explain_csv_error(&path, e))?;
+                if record.len() > width && record.iter().next_back() == Some("") {
+                    record.truncate(record.len() - 1);
+                }
+                Ok(record)
+            });
+            Ok((headers, Box::new(records)))
+        }
+        _ => {
+            let mut rdr = dialect.reader().from_reader(dialect.decoding.open(path)?);
+            let headers = rdr.headers().map_err(|e| explain_csv_error(path, e))?.clone();
+            let path = path.to_path_buf();
+            Ok((headers, Box::new(rdr.into_records().map(move |r| r.map_err(|e| explain_csv_error(&path, e))))))
+        }
+    }
+}
+
+/// The csv crate reports invalid UTF-8 by record and field; point at the bytes instead.
+fn explain_csv_error(path: &Path, error: csv::Error) -> Box<dyn Error> {
+    if let csv::ErrorKind::Utf8 { .. } = error.kind()
+        && let Ok(Some(message)) = encoding::describe_invalid_utf8(path)
+    {
+        return CsvDiffError::Parse { message }.into();
+    }
+    error.into()
+}
+
+/// Print the encoding detected for each text input, warning when file1 and file2 disagree.
+fn report_encodings(file1: &[PathBuf], file2: &[PathBuf], format: InputFormat, settings: &output::Settings) -> Result<(), Box<dyn Error>> {
+    let detect = |paths: &[PathBuf]| -> Result<Vec<(String, encoding::Encoding)>, Box<dyn Error>> {
+        paths
+            .iter()
+            .filter(|path| format.resolve(path) != InputFormat::Xlsx)
+            .map(|path| Ok((path.display().to_string(), encoding::detect(path)?)))
+            .collect()
+    };
+    let (detected1, detected2) = (detect(file1)?, detect(file2)?);
+    for (path, encoding) in detected1.iter().chain(&detected2) {
+        report!(settings, "🔤 Encoding of {}: {}", path, encoding.label());
+    }
+    for (path1, encoding1) in &detected1 {
+        if let Some((path2, encoding2)) = detected2.iter().find(|(_, encoding2)| encoding1.conflicts_with(*encoding2)) {
+            warning!(settings,
+                "⚠️  {} is {} but {} is {}; both are transcoded to UTF-8 before comparing",
+                path1,
+                encoding1.label(),
+                path2,
+                encoding2.label()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Warn when the first parts of file1 and file2 end their lines differently, which often means
+/// one of them went through a conversion the other did not.
+fn warn_line_ending_mismatch(file1: &[PathBuf], file2: &[PathBuf], format: InputFormat, settings: &output::Settings) -> Result<(), Box<dyn Error>> {
+    let (Some(path1), Some(path2)) = (file1.first(), file2.first()) else { return Ok(()) };
+    if format.resolve(path1) == InputFormat::Xlsx || format.resolve(path2) == InputFormat::Xlsx {
+        return Ok(());
+    }
+    if let (Some(endings1), Some(endings2)) = (encoding::detect_line_endings(path1)?, encoding::detect_line_endings(path2)?)
+        && endings1 != endings2
+    {
+        warning!(settings,
+            "⚠️  {} uses {} line endings but {} uses {}; values are unaffected, but this can be a sign that one file was transcoded",
+            path1.display(),
+            endings1,
+            path2.display(),
+            endings2
+        );
+    }
+    Ok(())
+}
+
+/// Paths for display, e.g. `jan.csv` or `jan.csv + feb.csv`.
+fn describe_paths(paths: &[PathBuf]) -> String {
+    paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(" + ")
+}
+
+/// Length of the longest bar in the --numeric-histogram chart
+const HISTOGRAM_BAR_WIDTH: usize = 30;
+
+/// Key values over --max-key-length reported individually per file; the rest are only counted
+const LONG_KEY_WARNINGS: usize = 5;
+
+/// Rows scanned for key values containing the composite key separator
+const KEY_SEPARATOR_SCAN_ROWS: usize = 1000;
+
+/// Warn once per key column when a value contains the separator: joined composite keys
+/// would then be ambiguous (`a|b` + `c` and `a` + `b|c` both become `a|b|c`).
+#[allow(clippy::too_many_arguments)]
+fn warn_separator_in_key(
+    path: &str,
+    headers: &StringRecord,
+    key_indexes: &[usize],
+    key_parts: &[&str],
+    separator: &str,
+    row: usize,
+    warned_columns: &mut HashSet<usize>,
+    settings: &output::Settings,
+) {
+    for (&index, value) in key_indexes.iter().zip(key_parts) {
+        if !separator.is_empty() && value.contains(separator) && warned_columns.insert(index) {
+            warning!(settings,
+                "⚠️  Warning: key separator '{}' found in key column '{}' row {} of {}. Consider using --key-sep to choose a different separator.",
+                separator,
+                headers.get(index).unwrap_or(""),
+                row,
+                path
+            );
+        }
+    }
+}
+
+/// Write source rows to a CSV file under their file's header, in file order, given the
+/// file's [`CsvData::parts`]. Returns the path written, which gains `.gz` under
+/// `--compress-output`.
+fn write_records(
+    path: &str,
+    headers: &[String],
+    mut records: Vec<KeyedRecord>,
+    parts: &HashMap<String, u64>,
+    settings: &output::Settings,
+) -> Result<String, Box<dyn Error>> {
+    records.sort_by_key(|&record| input_order(parts, record));
+    let (file, path) = settings.create_file(path)?;
+    let mut writer = csv::Writer::from_writer(file);
+    writer.write_record(headers)?;
+    // Columns copied by --header-mapping sit past the end of the file's own header
+    for (_, record) in records {
+        writer.write_record(record.iter().take(headers.len()))?;
+    }
+    writer.into_inner()?.finish()?;
+    Ok(path)
+}
+
+/// `--split-output`: the differences in one file per kind, as CSV or as json or yaml documents.
+struct SplitOutput<'a> {
+    prefix: &'a str,
+    /// Json or Yaml for documents, otherwise CSV
+    format: Option<OutputFormat>,
+    /// File1's original header, for the removed rows
+    headers1: &'a [String],
+    headers2: &'a [String],
+    added: Vec<KeyedRecord<'a>>,
+    removed: Vec<KeyedRecord<'a>>,
+    /// Each file's [`CsvData::parts`], to write the rows in file order
+    parts: &'a [HashMap<String, u64>; 2],
+    diffs: &'a [DiffRow],
+    settings: &'a output::Settings,
+}
+
+/// Write `PREFIX_added`, `PREFIX_removed` and `PREFIX_changed`: added and removed rows in full,
+/// changed values as key, column, file1 and file2.
+fn write_split_output(split: SplitOutput) -> Result<(), Box<dyn Error>> {
+    let settings = split.settings;
+    let changed: Vec<&DiffRow> = split.diffs.iter().filter(|diff| diff.is_value_change()).collect();
+    let [parts1, parts2] = split.parts;
+    let rows = [
+        ("added", split.headers2, split.added, parts2, "rows only in file 2"),
+        ("removed", split.headers1, split.removed, parts1, "rows only in file 1"),
+    ];
+    let written = match split.format {
+        Some(format @ (OutputFormat::Json | OutputFormat::Yaml)) => {
+            let (extension, as_yaml) = if format == OutputFormat::Yaml { ("yaml", true) } else { ("json", false) };
+            let mut written = Vec::new();
+            for (category, headers, mut records, parts, description) in rows {
+                records.sort_by_key(|&record| input_order(parts, record));
+                let records: Vec<&StringRecord> = records.into_iter().map(|(_, record)| record).collect();
+                let path = format!("{}_{}.{}", split.prefix, category, extension);
+                written.push((document::write_rows(&path, headers, &records, as_yaml, split.settings)?, description));
+            }
+            let path = format!("{}_changed.{}", split.prefix, extension);
+            written.push((document::write_changes(&path, &changed, as_yaml, split.settings)?, "changed values"));
+            written
+        }
+        _ => {
+            let mut written = Vec::new();
+            for (category, headers, records, parts, description) in rows {
+                written.push((write_records(&format!("{}_{}.csv", split.prefix, category), headers, records, parts, split.settings)?, description));
+            }
+            let (file, path) = split.settings.create_file(&format!("{}_changed.csv", split.prefix))?;
+            let mut writer = csv::Writer::from_writer(file);
+            writer.write_record(split.settings.field_names())?;
+            for diff in &changed {
+                writer.write_record([&diff.key, &diff.column, &diff.file1, &diff.file2])?;
+            }
+            writer.into_inner()?.finish()?;
+            written.push((path, "changed values"));
+            written
+        }
+    };
+    for (path, description) in written {
+        report!(settings, "📄 Wrote {} to {}", description, path);
+    }
+    Ok(())
+}
+
+/// Names of the columns at zero-based `indexes` of `headers`, for options that select columns
+/// by position.
+fn resolve_column_indexes(headers: &[String], indexes: &[usize], option: &str, path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    indexes
+        .iter()
+        .map(|&index| {
+            headers.get(index).cloned().ok_or_else(|| {
+                format!("{} {} is out of range: {} has {} columns (0 to {})", option, index, path, headers.len(), headers.len().saturating_sub(1)).into()
+            })
+        })
+        .collect()
+}
+
+/// The header of the first part of an input, after `--rename`, without reading its rows.
+fn read_headers(paths: &[PathBuf], mapping: &ColumnMapping, args: &Args, settings: &output::Settings) -> Result<Vec<String>, Box<dyn Error>> {
+    let first = paths.first().ok_or("No input file given")?;
+    let (headers, _) = open_records(first, args.format, &args.sheet(), &args.column_widths, args.dialect(), settings)?;
+    Ok(headers
+        .iter()
+        .map(|h| mapping.renames.get(h).cloned().unwrap_or_else(|| h.to_string()))
+        .chain(mapping.copies.iter().map(|(_, to)| to.clone()))
+        .collect())
+}
+
+fn read_tabular_to_map(
+    paths: &[PathBuf],
+    mapping: &ColumnMapping,
+    options: &ReadOptions,
+) -> Result<CsvData, Box<dyn Error>> {
+    let key_columns = options.key_columns;
+    let sampler = options.sampler;
+    let (first, rest) = paths.split_first().ok_or("No input file given")?;
+    let path = describe_paths(paths);
+    let dialect = options.dialect;
+    let settings = options.settings;
+    let (mut headers, records) = open_records(first, options.format, options.sheet, options.fixed_columns, dialect, settings)?;
+    let mut records = in_part(records, 0);
+    if !dialect.strip_trailing_delimiter && headers.len() > 1 && headers.iter().next_back() == Some("") {
+        warning!(settings,
+            "⚠️  The last column of {} has no name, which usually means every line ends with a comma. Consider using --ignore-trailing-delimiter.",
+            path
+        );
+    }
+
+    // Later parts are opened one at a time as the previous one runs out
+    if !rest.is_empty() {
+        let expected = headers.clone();
+        let first = first.clone();
+        let format = options.format;
+        let sheet = options.sheet.clone();
+        let fixed_columns = options.fixed_columns.to_vec();
+        let rest: Vec<PathBuf> = rest.to_vec();
+        let part_settings = settings.clone();
+        let parts = rest.into_iter().zip(1..).flat_map(move |(part, index)| -> PartRecords {
+            match open_records(&part, format, &sheet, &fixed_columns, dialect, &part_settings) {
+                Ok((part_headers, part_records)) if part_headers == expected => in_part(part_records, index),
+                Ok((part_headers, _)) => {
+                    let error = format!(
+                        "Header mismatch: {} has columns [{}] but {} has [{}]",
+                        part.display(),
+                        part_headers.iter().collect::<Vec<_>>().join(", "),
+                        first.display(),
+                        expected.iter().collect::<Vec<_>>().join(", ")
+                    );
+                    Box::new(std::iter::once(Err(error.into())))
+                }
+                Err(e) => Box::new(std::iter::once(Err(e))),
+            }
+        });
+        records = Box::new(records.chain(parts));
+    }
+
+    // A file1 column mapped to several file2 columns is compared once under each name
+    if !mapping.copies.is_empty() {
+        let sources = mapping
+            .copies
+            .iter()
+            .map(|(from, _)| {
+                headers
+                    .iter()
+                    .position(|h| h == from)
+                    .ok_or_else(|| format!("Header mapping column '{}' not found in {}", from, path))
+            })
+            .collect::<Result<Vec<usize>, String>>()?;
+        records = Box::new(records.map(move |record| {
+            let (part, mut record) = record?;
+            for &i in &sources {
+                let value = record.get(i).unwrap_or("").to_string();
+                record.push_field(&value);
+            }
+            Ok((part, record))
+        }));
+    }
+
+    // Apply column aliases so the rest of the comparison sees a single name
+    let renames = &mapping.renames;
+    if !renames.is_empty() {
+        for from in renames.keys() {
+            if !headers.iter().any(|h| h == from) {
+                return Err(format!("Rename source column '{}' not found in {}.{}", from, path, did_you_mean(from, headers.iter())).into());
+            }
+        }
+        headers = headers
+            .iter()
+            .map(|h| renames.get(h).map(String::as_str).unwrap_or(h))
+            .collect();
+    }
+    for (_, to) in &mapping.copies {
+        headers.push_field(to);
+    }
+
+    let mut key_indexes = Vec::new();
+    let mut missing_keys = Vec::new();
+    
+    for key in key_columns {
+        if let Some(index) = headers.iter().position(|h| h == key) {
+            key_indexes.push(index);
+        } else {
+            missing_keys.push(key.clone());
+        }
+    }
+
+    let mut map = HashMap::new();
+    let mut parts = HashMap::new();
+    let mut key_counts: HashMap<String, usize> = HashMap::new();
+    let mut total_rows = 0;
+    let mut reservoir = sampler.and_then(Sampler::reservoir);
+    // Keys of the rows seen so far, by the hash of their fields; only the keys are kept, and
+    // the rows themselves are looked up in `map` when a hash repeats
+    let mut rows_by_hash: HashMap<u64, Vec<String>> = HashMap::new();
+    let row_hash = |record: &StringRecord| {
+        let mut hasher = DefaultHasher::new();
+        record.iter().for_each(|field| field.hash(&mut hasher));
+        hasher.finish()
+    };
+    let mut duplicate_rows = Vec::new();
+    let mut limited = false;
+    let mut warned_columns: HashSet<usize> = HashSet::new();
+    let mut long_keys = 0;
+    
+    // Only process records if we found all key columns
+    if missing_keys.is_empty() {
+        for result in records {
+            let (part, record) = result?;
+            total_rows += 1;
+            // Without key columns rows are identified by their 1-based position
+            let key = if key_indexes.is_empty() {
+                total_rows.to_string()
+            } else {
+                let key_parts: Vec<&str> = key_indexes
+                    .iter()
+                    .map(|&i| record.get(i).unwrap_or(""))
+                    .collect();
+                if let Some(max_length) = options.max_key_length {
+                    for (&index, value) in key_indexes.iter().zip(&key_parts) {
+                        let length = value.chars().count();
+                        if length <= max_length {
+                            continue;
+                        }
+                        let message = format!(
+                            "key column '{}' row {} of {} is {} characters long (--max-key-length {}): {}",
+                            headers.get(index).unwrap_or(""),
+                            total_rows,
+                            path,
+                            length,
+                            max_length,
+                            options.anonymizer.map_or_else(|| truncate_string(value, 40), |anonymizer| anonymizer.hash(value))
+                        );
+                        if options.strict_key_length {
+                            return Err(format!("Key too long: {}", message).into());
+                        }
+                        long_keys += 1;
+                        if long_keys <= LONG_KEY_WARNINGS {
+                            warning!(settings, "⚠️  Warning: {}", message);
+                        }
+                    }
+                }
+                if key_parts.len() > 1 && total_rows <= KEY_SEPARATOR_SCAN_ROWS {
+                    warn_separator_in_key(&path, &headers, &key_indexes, &key_parts, options.key_separator, total_rows, &mut warned_columns, settings);
+                }
+                key_parts.join(options.key_separator)
+            };
+
+            // Sampling decides per key, so duplicates of a sampled key are kept together
+            if sampler.is_some_and(|s| !s.accepts(&key)) {
+                continue;
+            }
+
+            if options.check_duplicates {
+                let hash = row_hash(&record);
+                let same_hash = rows_by_hash.entry(hash).or_default();
+                let is_copy = same_hash.iter().any(|seen| match map.get(seen) {
+                    // Equal hashes are confirmed field by field while the earlier row is stored
+                    Some(row) if row_hash(row) == hash => row.iter().eq(record.iter()),
+                    // A later row under the same key replaced it; the key and the hash decide
+                    _ => *seen == key,
+                });
+                if !is_copy {
+                    same_hash.push(key.clone());
+                } else {
+                    duplicate_rows.push(key.clone());
+                    match options.dedup_strategy {
+                        Some(DedupStrategy::KeepFirst) => continue,
+                        // The copy replaces the stored row without counting as another key occurrence
+                        Some(DedupStrategy::KeepLast) => {
+                            if map.contains_key(&key) {
+                                if part > 0 {
+                                    parts.insert(key.clone(), part);
+                                }
+                                map.insert(key, record);
+                            }
+                            continue;
+                        }
+                        Some(DedupStrategy::Error) | None => {}
+                    }
+                }
+            }
+
+            if let Some(reservoir) = reservoir.as_mut()
+                && !map.contains_key(&key)
+                && let Some(evicted) = reservoir.offer(&key)
+            {
+                if evicted == key {
+                    continue;
+                }
+                map.remove(&evicted);
+                parts.remove(&evicted);
+                key_counts.remove(&evicted);
+            }
+            // The first new key past the limit ends the read; rows after it are never parsed
+            if options.limit_keys.is_some_and(|limit| map.len() >= limit) && !map.contains_key(&key) {
+                total_rows -= 1;
+                limited = true;
+                break;
+            }
+
+            *key_counts.entry(key.clone()).or_insert(0) += 1;
+            if part > 0 {
+                parts.insert(key.clone(), part);
+            }
+            map.insert(key, record);
+        }
+    }
+
+    if long_keys > LONG_KEY_WARNINGS {
+        warning!(settings, "⚠️  Warning: {} key values in {} exceed --max-key-length ({} not shown)", long_keys, path, long_keys - LONG_KEY_WARNINGS);
+    }
+
+    // Most frequent duplicates first, ties broken by key for stable output
+    let mut duplicate_keys: Vec<(String, usize)> = key_counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .collect();
+    duplicate_keys.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    Ok(CsvData {
+        headers: headers.iter().map(|s| s.to_string()).collect(),
+        records: map,
+        missing_keys,
+        duplicate_keys,
+        duplicate_rows,
+        total_rows,
+        limited,
+        parts,
+    })
+}
+
+/// How much of the input the comparison actually looked at.
+#[derive(Debug, Default)]
+struct Coverage {
+    /// Non-key columns compared in every matched row, out of all non-key columns
+    columns_compared: usize,
+    columns_total: usize,
+    /// Rows kept per file (after sampling and duplicate keys), out of rows read
+    rows_compared: [usize; 2],
+    rows_total: [usize; 2],
+    /// Cells compared in matched rows, out of matched rows × non-key columns
+    cells_compared: usize,
+    cells_total: usize,
+}
+
+impl Coverage {
+    fn lines(&self) -> Vec<(&'static str, String)> {
+        let ratio = |part: usize, total: usize| {
+            let pct = if total == 0 { 100.0 } else { part as f64 / total as f64 * 100.0 };
+            format!("{} of {} ({:.1}%)", part, total, pct)
+        };
+        vec![
+            ("Columns compared:", ratio(self.columns_compared, self.columns_total)),
+            ("File 1 rows included:", ratio(self.rows_compared[0], self.rows_total[0])),
+            ("File 2 rows included:", ratio(self.rows_compared[1], self.rows_total[1])),
+            ("Cells compared:", ratio(self.cells_compared, self.cells_total)),
+        ]
+    }
+}
+
+/// How many distinct rows the differences touch.
+struct KeyCounts {
+    /// Keys with at least one difference of any kind
+    keys: usize,
+    missing_in_file1: usize,
+    missing_in_file2: usize,
+    /// Keys present in both files with at least one changed value
+    changed: usize,
+}
+
+impl KeyCounts {
+    fn of(diffs: &[DiffRow]) -> KeyCounts {
+        let mut keys: HashSet<&str> = HashSet::new();
+        let mut changed: HashSet<&str> = HashSet::new();
+        let (mut missing_in_file1, mut missing_in_file2) = (0, 0);
+        for diff in diffs {
+            keys.insert(&diff.key);
+            match diff.column.as_str() {
+                "[missing in file1]" => missing_in_file1 += 1,
+                "[missing in file2]" => missing_in_file2 += 1,
+                // Columns in only one file are schema differences, not changed values
+                _ if diff.is_value_change() => {
+                    changed.insert(&diff.key);
+                }
+                _ => {}
+            }
+        }
+        KeyCounts { keys: keys.len(), missing_in_file1, missing_in_file2, changed: changed.len() }
+    }
+}
+
+#[derive(Tabled)]
+struct ColumnReportRow {
+    column: String,
+    #[tabled(rename = "file1")]
+    in_file1: &'static str,
+    #[tabled(rename = "file2")]
+    in_file2: &'static str,
+    key: &'static str,
+    ignored: &'static str,
+    status: &'static str,
+}
+
+/// `--columns-report`: print how each column will be treated, before any rows are read.
+fn print_columns_report(headers1: &[String], headers2: &[String], args: &Args, style: TableStyle, settings: &output::Settings) {
+    let yes_no = |yes: bool| if yes { "yes" } else { "no" };
+    let columns = headers1.iter().chain(headers2.iter().filter(|h| !headers1.contains(h)));
+    let rows: Vec<ColumnReportRow> = columns
+        .map(|column| {
+            let (in_file1, in_file2) = (headers1.contains(column), headers2.contains(column));
+            let (key, ignored) = (args.key.contains(column), args.ignore.contains(column));
+            let status = match (in_file1, in_file2) {
+                _ if key => "key",
+                _ if ignored => "ignored",
+                (true, true) => "compared",
+                _ if args.diff_mode == DiffMode::CommonColumns => "skipped (not in both files)",
+                (true, false) => "only in file 1",
+                _ => "only in file 2",
+            };
+            ColumnReportRow {
+                column: column.clone(),
+                in_file1: yes_no(in_file1),
+                in_file2: yes_no(in_file2),
+                key: yes_no(key),
+                ignored: yes_no(ignored),
+                status,
+            }
+        })
+        .collect();
+
+    let compared = rows.iter().filter(|row| row.status == "compared").count();
+    report!(settings, "📋 Columns ({} compared of {}):", compared, rows.len());
+    report!(settings, "{}", style.render(Table::new(&rows)));
+    warn_unknown_columns("--key", &args.key, headers1, headers2, settings);
+    warn_unknown_columns("--ignore", &args.ignore, headers1, headers2, settings);
+    report!(settings);
+}
+
+/// List the sheets of each Excel input for `--diff-mode headers`, marking the one compared.
+fn print_sheets_report(file1: &[PathBuf], file2: &[PathBuf], args: &Args, settings: &output::Settings) -> Result<(), Box<dyn Error>> {
+    let sheet = args.sheet();
+    report!(settings, "📑 Sheets:");
+    for path in file1.iter().chain(file2).filter(|path| args.format.resolve(path) == InputFormat::Xlsx) {
+        let reader = input::ExcelReader::open(path)?;
+        let names = reader.sheet_names();
+        let listed: Vec<String> = names
+            .iter()
+            .enumerate()
+            .map(|(index, &name)| {
+                let selected = sheet == Sheet::Index(index) || sheet == Sheet::Name(name.to_string());
+                if selected { format!("'{}' (compared)", name) } else { format!("'{}'", name) }
+            })
+            .collect();
+        report!(settings, "   {}: {}", path.display(), listed.join(", "));
+    }
+    report!(settings);
+    Ok(())
+}
+
+/// Append the edit distance (if computed) to the file2 display value.
+fn with_edit_distance(value: String, edit_distance: Option<usize>) -> String {
+    match edit_distance {
+        Some(distance) => format!("{} (edit distance: {})", value, distance),
+        None => value,
+    }
+}
+
+/// Put each `--diff-limit-per-key` summary row back after the last difference of its key.
+fn with_column_limits(diffs: Vec<DiffRow>, column_limits: &[DiffRow]) -> Vec<DiffRow> {
+    if column_limits.is_empty() {
+        return diffs;
+    }
+    let by_key: HashMap<&str, &DiffRow> = column_limits.iter().map(|row| (row.key.as_str(), row)).collect();
+    let mut rows = Vec::with_capacity(diffs.len() + column_limits.len());
+    let mut diffs = diffs.into_iter().peekable();
+    while let Some(diff) = diffs.next() {
+        let last_of_key = diffs.peek().is_none_or(|next| next.key != diff.key);
+        let summary = by_key.get(diff.key.as_str()).filter(|_| last_of_key).map(|row| (*row).clone());
+        rows.push(diff);
+        rows.extend(summary);
+    }
+    rows
+}
+
+/// `--compact`: collapse the value changes of each key into one row naming the changed columns,
+/// in order of the key's first difference. With `verbose`, the file1 and file2 cells list each
+/// column's values; rows missing from one file and `--diff-limit-per-key` summary rows are
+/// kept as they are.
+fn compact_diffs(diffs: Vec<DiffRow>, verbose: bool) -> Vec<DiffRow> {
+    let mut compacted: Vec<DiffRow> = Vec::new();
+    // Each changed key's value changes, with the position of its row in `compacted`
+    let mut changes: HashMap<String, (usize, Vec<DiffRow>)> = HashMap::new();
+    for diff in diffs {
+        if diff.column.starts_with("[missing in file") || diff.is_truncation() {
+            compacted.push(diff);
+        } else if let Some((_, columns)) = changes.get_mut(&diff.key) {
+            columns.push(diff);
+        } else {
+            changes.insert(diff.key.clone(), (compacted.len(), vec![diff.clone()]));
+            compacted.push(diff);
+        }
+    }
+    for (index, columns) in changes.into_values() {
+        let values = |value: fn(&DiffRow) -> &str| {
+            columns.iter().map(|diff| format!("{}={}", diff.column, value(diff))).collect::<Vec<_>>().join(", ")
+        };
+        let names = columns.iter().map(|diff| diff.column.as_str()).collect::<Vec<_>>().join(", ");
+        compacted[index] = DiffRow {
+            column: format!("changed columns=[{}]", names),
+            file1: if verbose { values(|diff| &diff.file1) } else { String::new() },
+            file2: if verbose { values(|diff| &diff.file2) } else { String::new() },
+            edit_distance: None,
+            ..columns[0].clone()
+        };
+    }
+    compacted
+}
+
+/// Append the row's line number to a display value, for `--show-line-numbers`.
+fn with_line_number(value: String, line: Option<u64>, show: bool) -> String {
+    match line {
+        Some(line) if show => format!("{} (line {})", value, line),
+        _ => value,
+    }
+}
+
+/// How file1's columns line up with file2's (`--rename`, `--header-mapping`).
+#[derive(Default)]
+struct ColumnMapping {
+    /// File1 column name → file2 column name
+    renames: HashMap<String, String>,
+    /// Further file2 names of a file1 column mapped to several file2 columns, as
+    /// (file1 name, file2 name); each becomes an extra copy of the column
+    copies: Vec<(String, String)>,
+}
+
+/// Read a `--header-mapping` CSV: a header row, then one `file1_column,file2_column` pair per
+/// line, checked against both files' header rows.
+fn read_header_mapping(path: &Path, headers1: &[String], headers2: &[String]) -> Result<ColumnMapping, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().trim(csv::Trim::All).from_path(path)?;
+    let mut mapping = ColumnMapping::default();
+    let mut targets: HashMap<String, String> = HashMap::new();
+    for (line, result) in rdr.records().enumerate() {
+        let record = result?;
+        let (Some(from), Some(to), 2) = (record.get(0), record.get(1), record.len()) else {
+            return Err(format!("{} line {}: expected file1_column,file2_column", path.display(), line + 2).into());
+        };
+        for (column, headers, file) in [(from, headers1, "file 1"), (to, headers2, "file 2")] {
+            if !headers.iter().any(|h| h == column) {
+                let hint = did_you_mean(column, headers.iter().map(String::as_str));
+                return Err(format!("{} line {}: column '{}' not found in {}.{}", path.display(), line + 2, column, file, hint).into());
+            }
+        }
+        if let Some(previous) = targets.insert(to.to_string(), from.to_string())
+            && previous != from
+        {
+            return Err(format!("{}: file 2 column '{}' is mapped from both '{}' and '{}'", path.display(), to, previous, from).into());
+        }
+        if mapping.renames.contains_key(from) {
+            mapping.copies.push((from.to_string(), to.to_string()));
+        } else {
+            mapping.renames.insert(from.to_string(), to.to_string());
+        }
+    }
+    Ok(mapping)
+}
+
+/// Parse `--rename old=new` pairs into a map from file1 column name to file2 column name.
+fn parse_renames(renames: &[String]) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut map = HashMap::new();
+    for rename in renames {
+        let (from, to) = rename
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid --rename '{}': expected OLD=NEW", rename))?;
+        if from.is_empty() || to.is_empty() {
+            return Err(format!("Invalid --rename '{}': column names cannot be empty", rename).into());
+        }
+        map.insert(from.to_string(), to.to_string());
+    }
+    Ok(map)
+}
+
+/// Split `--ignore` values on commas, CSV-style, so `"a,b"` names a column containing a comma.
+/// Repeated names are dropped; the order of first appearance is kept.
+fn parse_column_lists(values: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut columns: Vec<String> = Vec::new();
+    for value in values {
+        // The csv reader accepts an unterminated quote; reject it rather than guess
+        if value.matches('"').count() % 2 == 1 {
+            return Err(format!("Invalid --ignore '{}': unbalanced quotes", value).into());
+        }
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .trim(csv::Trim::All)
+            .from_reader(value.as_bytes());
+        let record = reader
+            .records()
+            .next()
+            .transpose()
+            .map_err(|e| format!("Invalid --ignore '{}': {}", value, e))?
+            .unwrap_or_default();
+        for column in &record {
+            if column.is_empty() {
+                return Err(format!("Invalid --ignore '{}': column names cannot be empty", value).into());
+            }
+            if !columns.iter().any(|c| c == column) {
+                columns.push(column.to_string());
+            }
+        }
+    }
+    Ok(columns)
+}
+
+/// Parse `--rule "COLUMN=VALUE:ignore=COL1,COL2"` definitions.
+fn parse_rules(rules: &[String]) -> Result<Vec<ComparisonRule>, Box<dyn Error>> {
+    rules
+        .iter()
+        .map(|rule| {
+            let invalid = || format!("Invalid --rule '{}': expected COLUMN=VALUE:ignore=COL1,COL2", rule);
+            let (condition, action) = rule.split_once(':').ok_or_else(invalid)?;
+            let (column, value) = condition.split_once('=').ok_or_else(invalid)?;
+            let ignore = action.strip_prefix("ignore=").ok_or_else(invalid)?;
+            let ignore: Vec<String> = ignore
+                .split(',')
+                .map(str::trim)
+                .filter(|col| !col.is_empty())
+                .map(String::from)
+                .collect();
+            if column.is_empty() || ignore.is_empty() {
+                return Err(invalid().into());
+            }
+            Ok(ComparisonRule {
+                column: column.to_string(),
+                value: value.to_string(),
+                ignore,
+            })
+        })
+        .collect()
+}
+
+/// Ensure every column a rule refers to exists in at least one of the files.
+fn validate_rules(rules: &[ComparisonRule], headers1: &[String], headers2: &[String]) -> Result<(), Box<dyn Error>> {
+    for rule in rules {
+        for column in std::iter::once(&rule.column).chain(&rule.ignore) {
+            if !headers1.contains(column) && !headers2.contains(column) {
+                return Err(format!("Column '{}' used in --rule was not found in either file", column).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Find columns exclusive to one file whose values line up with a column exclusive to the other.
+///
+/// Each candidate column is fingerprinted by hashing its values for a sample of keys present
+/// in both files; two columns are considered a likely rename when most fingerprints agree.
+fn detect_renamed_columns(
+    headers1: &[String],
+    map1: &HashMap<String, StringRecord>,
+    headers2: &[String],
+    map2: &HashMap<String, StringRecord>,
+) -> Vec<(String, String, f64)> {
+    const SAMPLE_SIZE: usize = 1000;
+    const MIN_MATCH_RATIO: f64 = 0.8;
+
+    let only_in_1: Vec<usize> = (0..headers1.len()).filter(|&i| !headers2.contains(&headers1[i])).collect();
+    let only_in_2: Vec<usize> = (0..headers2.len()).filter(|&i| !headers1.contains(&headers2[i])).collect();
+    if only_in_1.is_empty() || only_in_2.is_empty() {
+        return Vec::new();
+    }
+
+    // Sort shared keys so the sample is the same on every run
+    let mut sample_keys: Vec<&String> = map1.keys().filter(|k| map2.contains_key(*k)).collect();
+    sample_keys.sort();
+    sample_keys.truncate(SAMPLE_SIZE);
+    if sample_keys.is_empty() {
+        return Vec::new();
+    }
+
+    let fingerprint = |map: &HashMap<String, StringRecord>, index: usize| -> Vec<Option<u64>> {
+        sample_keys
+            .iter()
+            .map(|key| {
+                let value = map[*key].get(index).unwrap_or("");
+                if value.is_empty() {
+                    return None;
+                }
+                let mut hasher = DefaultHasher::new();
+                value.hash(&mut hasher);
+                Some(hasher.finish())
+            })
+            .collect()
+    };
+    let fingerprints2: Vec<(usize, Vec<Option<u64>>)> = only_in_2.iter().map(|&i| (i, fingerprint(map2, i))).collect();
+
+    let mut candidates = Vec::new();
+    for &i in &only_in_1 {
+        let fp1 = fingerprint(map1, i);
+        for (j, fp2) in &fingerprints2 {
+            // Ignore positions where both sides are empty so sparse columns don't all look alike
+            let compared = fp1.iter().zip(fp2).filter(|(a, b)| a.is_some() || b.is_some()).count();
+            if compared == 0 {
+                continue;
+            }
+            let matching = fp1.iter().zip(fp2).filter(|(a, b)| a.is_some() && a == b).count();
+            let ratio = matching as f64 / compared as f64;
+            if ratio >= MIN_MATCH_RATIO {
+                candidates.push((i, *j, ratio));
+            }
+        }
+    }
+
+    // Greedily pair the strongest matches so every column is used at most once
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    let mut used1 = HashSet::new();
+    let mut used2 = HashSet::new();
+    let mut renames = Vec::new();
+    for (i, j, ratio) in candidates {
+        if used1.contains(&i) || used2.contains(&j) {
+            continue;
+        }
+        used1.insert(i);
+        used2.insert(j);
+        renames.push((headers1[i].clone(), headers2[j].clone(), ratio));
+    }
+    renames
+}
+
+/// A file1 key paired with a file2 key that differs only slightly.
+struct FuzzyKeyMatch {
+    key1: String,
+    key2: String,
+    score: f64,
+}
+
+/// Match keys present in only one file to their most similar counterpart in the other file.
+///
+/// Every unmatched file1 key is compared against every unmatched file2 key using Jaro-Winkler
+/// similarity; the strongest pairs at or above `threshold` are kept, each key used at most once.
+fn match_fuzzy_keys(
+    map1: &HashMap<String, StringRecord>,
+    map2: &HashMap<String, StringRecord>,
+    threshold: f64,
+) -> Result<Vec<FuzzyKeyMatch>, Box<dyn Error>> {
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(format!("Invalid --fuzzy-keys threshold {}: expected a value between 0.0 and 1.0", threshold).into());
+    }
+
+    let unmatched1: Vec<&String> = map1.keys().filter(|k| !map2.contains_key(*k)).collect();
+    let unmatched2: Vec<&String> = map2.keys().filter(|k| !map1.contains_key(*k)).collect();
+
+    let mut candidates = Vec::new();
+    for key1 in &unmatched1 {
+        for key2 in &unmatched2 {
+            let score = strsim::jaro_winkler(key1, key2);
+            if score >= threshold {
+                candidates.push((*key1, *key2, score));
+            }
+        }
+    }
+
+    // Strongest matches win; ties broken by key for deterministic output
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(b.0)).then_with(|| a.1.cmp(b.1)));
+    let mut used1 = HashSet::new();
+    let mut used2 = HashSet::new();
+    let mut matches = Vec::new();
+    for (key1, key2, score) in candidates {
+        if used1.contains(key1) || used2.contains(key2) {
+            continue;
+        }
+        used1.insert(key1);
+        used2.insert(key2);
+        matches.push(FuzzyKeyMatch {
+            key1: key1.clone(),
+            key2: key2.clone(),
+            score,
+        });
+    }
+    Ok(matches)
+}
+
+/// Row pairs `--diff-mode best-match` compares at most; every pair's differences are counted
+const MAX_BEST_MATCH_PAIRS: usize = 4_000_000;
+
+/// A file1 row paired with the file2 row it differs from least.
+struct RowMatch {
+    key1: String,
+    key2: String,
+    /// Compared columns whose values differ
+    differing: usize,
+}
+
+/// Pair keyless rows by content for `--diff-mode best-match`.
+///
+/// Identical rows are paired first; the rest are paired greedily, fewest differing columns
+/// first. Rows that share no value in the compared columns stay unpaired and are reported as
+/// missing. `columns` holds the position of each compared column in file1 and in file2.
+fn match_best_rows(
+    map1: &HashMap<String, StringRecord>,
+    map2: &HashMap<String, StringRecord>,
+    headers1: &[String],
+    columns: &[(usize, usize)],
+    compare_options: &CompareOptions,
+) -> Result<Vec<RowMatch>, Box<dyn Error>> {
+    let values = |record: &StringRecord, side: fn(&(usize, usize)) -> usize| -> Vec<String> {
+        columns.iter().map(|c| record.get(side(c)).unwrap_or("").to_string()).collect()
+    };
+    // Keys are row numbers; pairing in row order keeps the result deterministic
+    let by_position = |map: &HashMap<String, StringRecord>| -> Vec<String> {
+        let mut keys: Vec<String> = map.keys().cloned().collect();
+        keys.sort_by_key(|key| key.parse::<usize>().unwrap_or(usize::MAX));
+        keys
+    };
+
+    let mut identical: HashMap<Vec<String>, Vec<String>> = HashMap::new();
+    for key2 in by_position(map2).into_iter().rev() {
+        identical.entry(values(&map2[&key2], |c| c.1)).or_default().push(key2);
+    }
+
+    let mut matches = Vec::new();
+    let mut unmatched1 = Vec::new();
+    for key1 in by_position(map1) {
+        match identical.get_mut(&values(&map1[&key1], |c| c.0)).and_then(Vec::pop) {
+            Some(key2) => matches.push(RowMatch { key1, key2, differing: 0 }),
+            None => unmatched1.push(key1),
+        }
+    }
+    let paired2: HashSet<&String> = matches.iter().map(|m| &m.key2).collect();
+    let unmatched2: Vec<String> = by_position(map2).into_iter().filter(|key| !paired2.contains(key)).collect();
+
+    let pair_count = unmatched1.len() * unmatched2.len();
+    if pair_count > MAX_BEST_MATCH_PAIRS {
+        return Err(format!(
+            "--diff-mode best-match would compare {} × {} unmatched rows ({} pairs, limit {}); use --key for files this large",
+            unmatched1.len(),
+            unmatched2.len(),
+            pair_count,
+            MAX_BEST_MATCH_PAIRS
+        )
+        .into());
+    }
+
+    let mut candidates = Vec::new();
+    for (i, key1) in unmatched1.iter().enumerate() {
+        let record1 = &map1[key1];
+        for (j, key2) in unmatched2.iter().enumerate() {
+            let record2 = &map2[key2];
+            let differing = columns
+                .iter()
+                .filter(|&&(c1, c2)| !compare_values(&headers1[c1], record1.get(c1).unwrap_or(""), record2.get(c2).unwrap_or(""), compare_options))
+                .count();
+            if differing < columns.len() {
+                candidates.push((differing, i, j));
+            }
+        }
+    }
+
+    // Closest pairs win; ties go to the earliest rows
+    candidates.sort_unstable();
+    let mut used1 = vec![false; unmatched1.len()];
+    let mut used2 = vec![false; unmatched2.len()];
+    for (differing, i, j) in candidates {
+        if used1[i] || used2[j] {
+            continue;
+        }
+        used1[i] = true;
+        used2[j] = true;
+        matches.push(RowMatch {
+            key1: unmatched1[i].clone(),
+            key2: unmatched2[j].clone(),
+            differing,
+        });
+    }
+    Ok(matches)
+}
+
+/// Cut a string to `max_len` characters, the last three of them "...".
+fn truncate_string(s: &str, max_len: usize) -> String {
+    if s.chars().count() <= max_len {
+        return s.to_string();
+    }
+    
+    let head: String = s.chars().take(max_len.saturating_sub(3)).collect(); // Reserve space for "..."
+    format!("{}...", head)
+}
+
+fn truncate_for_excel(s: &str) -> String {
+    const EXCEL_MAX_CELL_LENGTH: usize = 32767;
+    if s.len() <= EXCEL_MAX_CELL_LENGTH {
+        return s.to_string();
+    }
+    
+    // Find a safe UTF-8 character boundary
+    let mut truncate_at = EXCEL_MAX_CELL_LENGTH.saturating_sub(3); // Reserve space for "..."
+    while truncate_at > 0 && !s.is_char_boundary(truncate_at) {
+        truncate_at -= 1;
+    }
+    
+    format!("{}...", &s[..truncate_at])
+}
+
+/// A column present in both files at different positions.
+struct MovedColumn {
+    name: String,
+    /// 1-based positions in file1 and file2
+    position1: usize,
+    position2: usize,
+}
+
+fn print_schema_status(headers_content_match: bool, schema_identical: bool, settings: &output::Settings) {
+    if schema_identical {
+        report!(settings, "✅ Schema Match: Headers are identical (same columns in same order)");
+    } else if headers_content_match {
+        report!(settings, "⚠️  Schema Partial Match: Same columns but different order");
+    } else {
+        report!(settings, "❌ Schema Mismatch: Different columns between files");
+    }
+}
+
+fn print_moved_columns(moved: &[MovedColumn], settings: &output::Settings) {
+    if moved.is_empty() {
+        report!(settings, "✅ No columns changed position");
+    } else {
+        report!(settings, "🔀 {} columns changed position:", moved.len());
+        for column in moved {
+            report!(settings,
+                "   • Column '{}' moved from position {} in file1 to position {} in file2",
+                column.name, column.position1, column.position2
+            );
+        }
+    }
+    report!(settings);
+}
+
+/// Compare header positions, in file1 order.
+fn find_moved_columns(headers1: &[String], headers2: &[String]) -> Vec<MovedColumn> {
+    headers1
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| {
+            let j = headers2.iter().position(|h| h == name)?;
+            (i != j).then(|| MovedColumn {
+                name: name.clone(),
+                position1: i + 1,
+                position2: j + 1,
+            })
+        })
+        .collect()
+}
+
+/// Reorder a file's columns by name, moving each record's fields with them. The last `copies`
+/// columns (added by --header-mapping) stay at the end.
+fn sort_columns(headers: &mut Vec<String>, records: &mut HashMap<String, StringRecord>, copies: usize) {
+    let mut order: Vec<usize> = (0..headers.len()).collect();
+    order[..headers.len() - copies].sort_by(|&a, &b| headers[a].cmp(&headers[b]));
+    *headers = order.iter().map(|&i| headers[i].clone()).collect();
+    for record in records.values_mut() {
+        let mut reordered: StringRecord = order.iter().map(|&i| record.get(i).unwrap_or("")).collect();
+        reordered.set_position(record.position().cloned());
+        *record = reordered;
+    }
+}
+
+fn check_schema_match(headers1: &[String], headers2: &[String]) -> (bool, bool) {
+    // Check if headers content matches (regardless of order)
+    let set1: HashSet<&String> = headers1.iter().collect();
+    let set2: HashSet<&String> = headers2.iter().collect();
+    let headers_content_match = set1 == set2;
+    
+    // Check if headers and order are identical
+    let schema_identical = headers1 == headers2;
+    
+    (headers_content_match, schema_identical)
+}
+
+/// Jaro-Winkler similarity a column name needs to be suggested for a misspelled one.
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+/// The header most similar to a misspelled column name, if it is similar enough.
+fn closest_column<'a>(name: &str, headers: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    headers
+        .into_iter()
+        .map(|h| (strsim::jaro_winkler(&name, &h.to_lowercase()), h))
+        .filter(|&(similarity, _)| similarity > SUGGESTION_THRESHOLD)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, h)| h)
+}
+
+/// " Did you mean 'x'?" for an unknown column name, or nothing when no header is close.
+fn did_you_mean<'a>(name: &str, headers: impl IntoIterator<Item = &'a str>) -> String {
+    closest_column(name, headers).map_or_else(String::new, |column| format!(" Did you mean '{}'?", column))
+}
+
+/// Warn about columns named with `flag` that neither file has, suggesting the closest header.
+fn warn_unknown_columns(flag: &str, names: &[String], headers1: &[String], headers2: &[String], settings: &output::Settings) {
+    for name in names {
+        if !headers1.contains(name) && !headers2.contains(name) {
+            let headers = headers1.iter().chain(headers2).map(String::as_str);
+            warning!(settings, "⚠️  {} column '{}' is not in either file.{}", flag, name, did_you_mean(name, headers));
+        }
+    }
+}
+
+/// Report key columns missing from either file. The suggestions (the closest header to each
+/// missing key, then up to 5 columns both files have) are printed for people, or with
+/// `--error-format json` only carried in the error's `suggestions` list.
+fn validate_keys_and_suggest_alternatives(
+    (file1, file2): (&[PathBuf], &[PathBuf]),
+    missing_keys1: &[String],
+    missing_keys2: &[String],
+    headers1: &[String],
+    headers2: &[String],
+    error_format: ErrorFormat,
+    settings: &output::Settings,
+) -> Result<(), Box<dyn Error>> {
+    if missing_keys1.is_empty() && missing_keys2.is_empty() {
+        return Ok(());
+    }
+
+    // Suggest common columns that exist in both files
+    let common_columns: Vec<&String> = headers1
+        .iter()
+        .filter(|col| headers2.contains(col))
+        .collect();
+    let mut suggestions: Vec<String> = Vec::new();
+    for (missing_keys, headers) in [(missing_keys1, headers1), (missing_keys2, headers2)] {
+        for key in missing_keys {
+            if let Some(column) = closest_column(key, headers.iter().map(String::as_str))
+                && !suggestions.iter().any(|s| s == column)
+            {
+                suggestions.push(column.to_string());
+            }
+        }
+    }
+    for column in common_columns.iter().take(5) {
+        if !suggestions.contains(column) {
+            suggestions.push(column.to_string());
+        }
+    }
+    let (file, column) = match missing_keys1.first() {
+        Some(column) => (file1, column),
+        None => (file2, &missing_keys2[0]),
+    };
+    let error = CsvDiffError::KeyColumnNotFound { file: describe_paths(file), column: column.clone(), suggestions };
+    if error_format == ErrorFormat::Json {
+        return Err(error.into());
+    }
+
+    warning!(settings, "❌ Error: Missing key columns detected");
+    
+    for (label, missing_keys, headers) in [("File 1", missing_keys1, headers1), ("File 2", missing_keys2, headers2)] {
+        if missing_keys.is_empty() {
+            continue;
+        }
+        warning!(settings, "   {} is missing key columns: {}", label, missing_keys.join(", "));
+        for key in missing_keys {
+            if let Some(column) = closest_column(key, headers.iter().map(String::as_str)) {
+                warning!(settings, "   Key column '{}' not found. Did you mean '{}'?", key, column);
+            }
+        }
+    }
+
+    if !common_columns.is_empty() {
+        warning!(settings, "\n💡 Suggested alternative key columns (present in both files):");
+        for col in common_columns.iter().take(5) { // Limit suggestions to 5
+            warning!(settings, "   • {}", col);
+        }
+        if common_columns.len() > 5 {
+            warning!(settings, "   ... and {} more", common_columns.len() - 5);
         }
     } else {
-        eprintln!("\n⚠️  No common columns found between the files.");
+        warning!(settings, "\n⚠️  No common columns found between the files.");
+    }
+
+    warning!(settings, "\nAvailable columns:");
+    warning!(settings, "   File 1: {}", headers1.join(", "));
+    warning!(settings, "   File 2: {}", headers2.join(", "));
+
+    Err(error.into())
+}
+
+fn report_duplicate_rows(
+    duplicates1: &[String],
+    duplicates2: &[String],
+    strategy: Option<DedupStrategy>,
+    settings: &output::Settings,
+) -> Result<(), Box<dyn Error>> {
+    if duplicates1.is_empty() && duplicates2.is_empty() {
+        report!(settings, "✅ No exact duplicate rows in either file");
+        report!(settings);
+        return Ok(());
+    }
+
+    warning!(settings, "⚠️  Warning: Exact duplicate rows found (all fields identical)");
+    for (label, duplicates) in [("File 1", duplicates1), ("File 2", duplicates2)] {
+        if duplicates.is_empty() {
+            continue;
+        }
+        let mut examples: Vec<&str> = Vec::new();
+        for key in duplicates {
+            if examples.len() == 5 {
+                break;
+            }
+            if !examples.contains(&key.as_str()) {
+                examples.push(key);
+            }
+        }
+        warning!(settings, "   {} has {} duplicate rows (e.g. keys {})", label, duplicates.len(), examples.join(", "));
+    }
+
+    match strategy {
+        Some(DedupStrategy::Error) => {
+            return Err("Exact duplicate rows found with --dedup-strategy error. Fix the export or choose keep-first/keep-last.".into());
+        }
+        Some(DedupStrategy::KeepFirst) => warning!(settings, "   Removed before the diff, keeping the first copy of each row"),
+        Some(DedupStrategy::KeepLast) => warning!(settings, "   Removed before the diff, keeping the last copy of each row"),
+        None => warning!(settings, "   Use --dedup-strategy keep-first|keep-last to remove them before the diff"),
+    }
+    warning!(settings);
+
+    Ok(())
+}
+
+fn report_duplicate_keys(
+    key_columns: &[String],
+    duplicates1: &[(String, usize)],
+    duplicates2: &[(String, usize)],
+    strict_keys: bool,
+    settings: &output::Settings,
+) -> Result<(), Box<dyn Error>> {
+    if duplicates1.is_empty() && duplicates2.is_empty() {
+        return Ok(());
+    }
+
+    warning!(settings, "⚠️  Warning: Key columns ({}) are not unique", key_columns.join(", "));
+
+    for (label, duplicates) in [("File 1", duplicates1), ("File 2", duplicates2)] {
+        if duplicates.is_empty() {
+            continue;
+        }
+        warning!(settings, "   {} has {} duplicated key values:", label, duplicates.len());
+        for (key, count) in duplicates.iter().take(10) { // Limit listing to top 10
+            warning!(settings, "   • {} ({} occurrences)", key, count);
+        }
+        if duplicates.len() > 10 {
+            warning!(settings, "   ... and {} more", duplicates.len() - 10);
+        }
+    }
+
+    if strict_keys {
+        return Err("Duplicate keys found with --strict-keys. Add more key columns to make each row unique.".into());
+    }
+
+    warning!(settings, "   Only the last row for each duplicated key is compared. Use --strict-keys to abort instead.");
+    warning!(settings);
+
+    Ok(())
+}
+
+/// `--diff-format side-by-side`: every column of each differing row, file1's value on the left
+/// and file2's on the right. The gutter marks changed values with `|` and values only on one
+/// side with `<` or `>`, as `diff --side-by-side` does.
+fn create_side_by_side(
+    diffs: &[DiffRow],
+    (headers1, map1): (&[String], &HashMap<String, StringRecord>),
+    (headers2, map2): (&[String], &HashMap<String, StringRecord>),
+    width: usize,
+    max_rows: usize,
+    settings: &output::Settings,
+) -> String {
+    let mut keys: Vec<&str> = Vec::new();
+    let mut changed: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for diff in diffs {
+        if !changed.contains_key(diff.key.as_str()) {
+            keys.push(&diff.key);
+        }
+        changed.entry(&diff.key).or_default().insert(&diff.column);
+    }
+
+    let columns: Vec<&String> = headers1.iter().chain(headers2.iter().filter(|h| !headers1.contains(h))).collect();
+    let name_width = columns.iter().map(|c| c.chars().count()).max().unwrap_or(0).min(width / 4);
+    // Two value halves share what the column names and the " | " separators leave
+    let value_width = (width.saturating_sub(name_width + 6) / 2).max(4);
+    let cell = |value: &str, width: usize| format!("{:<width$}", truncate_string(value, width), width = width);
+
+    let mut out = String::new();
+    for key in keys.iter().take(max_rows) {
+        // Fuzzy matches are displayed as "key1 ≈ key2"
+        let (key1, key2) = key.split_once(" ≈ ").unwrap_or((key, key));
+        let (row1, row2) = (map1.get(key1), map2.get(key2));
+        out.push_str(&format!("🔑 {}\n", key));
+        out.push_str(&format!("{} | {}   {}\n", cell("", name_width), cell(settings.field_name("file1"), value_width), settings.field_name("file2")));
+        out.push_str(&format!("{}\n", "-".repeat(name_width + value_width * 2 + 6)));
+        let differing = &changed[key];
+        for column in &columns {
+            let value = |headers: &[String], row: Option<&StringRecord>| {
+                let index = headers.iter().position(|h| h == *column)?;
+                Some(row?.get(index).unwrap_or("").to_string())
+            };
+            let (left, right) = (value(headers1, row1), value(headers2, row2));
+            let marker = match (&left, &right) {
+                (Some(_), None) => '<',
+                (None, Some(_)) => '>',
+                _ if differing.contains(column.as_str()) => '|',
+                _ => ' ',
+            };
+            out.push_str(&format!(
+                "{} | {} {} {}\n",
+                cell(column, name_width),
+                cell(left.as_deref().unwrap_or(""), value_width),
+                marker,
+                truncate_string(right.as_deref().unwrap_or(""), value_width)
+            ));
+        }
+        out.push('\n');
+    }
+    if keys.len() > max_rows {
+        out.push_str(&format!("📊 Summary: {} rows differ, showing {} (use --max-rows to adjust)\n", keys.len(), max_rows));
     }
+    out.push_str(&format!("📊 Total differences: {}", diffs.iter().filter(|diff| !diff.is_truncation()).count()));
+    out
+}
 
-    eprintln!("\nAvailable columns:");
-    eprintln!("   File 1: {}", headers1.join(", "));
-    eprintln!("   File 2: {}", headers2.join(", "));
-
-    Err("Key validation failed. Please specify valid key columns that exist in both files.".into())
+/// The terminal table of differences, headed by the (renamed) field names. With
+/// `show_similarity` (`--min-diff-pct`), a last column gives each changed row's similarity as
+/// the Excel report's Similarity % column shows it.
+fn diff_table(diffs: &[DiffRow], show_similarity: bool, settings: &output::Settings) -> Table {
+    let mut builder = tabled::builder::Builder::default();
+    let mut header: Vec<&str> = settings.field_names().to_vec();
+    if show_similarity {
+        header.push(settings.field_name(output::SIMILARITY_FIELD));
+    }
+    builder.push_record(header);
+    for diff in diffs {
+        let mut row = vec![diff.key.clone(), diff.column.clone(), diff.file1.clone(), diff.file2.clone()];
+        if show_similarity {
+            row.push(diff.similarity.map(|similarity| format!("{:.1}%", similarity * 100.0)).unwrap_or_default());
+        }
+        builder.push_record(row);
+    }
+    builder.build()
 }
 
-fn create_summary_table(diffs: Vec<DiffRow>, max_rows: usize, max_cell_width: usize, no_truncate: bool) -> String {
+#[allow(clippy::too_many_arguments)]
+fn create_summary_table(
+    diffs: Vec<DiffRow>,
+    max_rows: usize,
+    max_cell_width: usize,
+    no_truncate: bool,
+    show_line_numbers: bool,
+    show_similarity: bool,
+    style: TableStyle,
+    settings: &output::Settings,
+) -> String {
     if no_truncate {
-        return Table::new(diffs).to_string();
+        let annotated: Vec<DiffRow> = diffs
+            .into_iter()
+            .map(|diff| DiffRow {
+                file1: with_line_number(diff.file1.clone(), diff.file1_line, show_line_numbers),
+                file2: with_line_number(with_edit_distance(diff.file2.clone(), diff.edit_distance), diff.file2_line, show_line_numbers),
+                ..diff
+            })
+            .collect();
+        return style.render(diff_table(&annotated, show_similarity, settings));
     }
 
     let total_diffs = diffs.len();
+    // --diff-limit-per-key summary rows take a row in the table but are not differences
+    let differences = diffs.iter().filter(|diff| !diff.is_truncation()).count();
     
     if total_diffs == 0 {
         return "✅ No differences found.".to_string();
     }
 
+    // A cut key no longer identifies its row, so say so separately from the table
+    let long_keys: HashSet<&str> = diffs.iter().filter(|diff| diff.key.chars().count() > max_cell_width).map(|diff| diff.key.as_str()).collect();
+    if !long_keys.is_empty() {
+        warning!(settings,
+            "⚠️  {} keys are longer than --max-cell-width ({}) and shown truncated; use --no-truncate or a larger --max-cell-width to see them in full",
+            long_keys.len(),
+            max_cell_width
+        );
+    }
+
     // Truncate cell content
     let mut truncated_diffs: Vec<DiffRow> = diffs
         .into_iter()
         .map(|diff| DiffRow {
             key: truncate_string(&diff.key, max_cell_width),
             column: truncate_string(&diff.column, max_cell_width),
-            file1: truncate_string(&diff.file1, max_cell_width),
-            file2: truncate_string(&diff.file2, max_cell_width),
+            file1: with_line_number(truncate_string(&diff.file1, max_cell_width), diff.file1_line, show_line_numbers),
+            file2: with_line_number(
+                with_edit_distance(truncate_string(&diff.file2, max_cell_width), diff.edit_distance),
+                diff.file2_line,
+                show_line_numbers,
+            ),
+            ..diff
         })
         .collect();
 
@@ -201,7 +2540,7 @@ fn create_summary_table(diffs: Vec<DiffRow>, max_rows: usize, max_cell_width: us
     let mut result = String::new();
     
     if total_diffs <= max_rows {
-        result.push_str(&Table::new(truncated_diffs).to_string());
+        result.push_str(&style.render(diff_table(&truncated_diffs, show_similarity, settings)));
     } else {
         // Take first half and last few rows, with separator in between
         let head_rows = max_rows / 2;
@@ -218,6 +2557,7 @@ fn create_summary_table(diffs: Vec<DiffRow>, max_rows: usize, max_cell_width: us
             column: format!("... ({} more rows) ...", total_diffs - max_rows),
             file1: "...".to_string(),
             file2: "...".to_string(),
+            ..Default::default()
         });
         
         // Add tail rows
@@ -226,86 +2566,334 @@ fn create_summary_table(diffs: Vec<DiffRow>, max_rows: usize, max_cell_width: us
             display_rows.extend(truncated_diffs.drain(start_index..));
         }
         
-        result.push_str(&Table::new(display_rows).to_string());
+        result.push_str(&style.render(diff_table(&display_rows, show_similarity, settings)));
     }
     
     // Add summary information
     if total_diffs > max_rows {
-        result.push_str(&format!("\n\n📊 Summary: {} total differences found", total_diffs));
+        result.push_str(&format!("\n\n📊 Summary: {} total differences found", differences));
         result.push_str(&format!("\n   Showing {} rows (use --max-rows to adjust or --no-truncate to show all)", max_rows));
     } else {
-        result.push_str(&format!("\n\n📊 Total differences: {}", total_diffs));
+        result.push_str(&format!("\n\n📊 Total differences: {}", differences));
     }
     
     result
 }
 
-fn generate_excel_report(
-    file1_path: &PathBuf,
-    file2_path: &PathBuf,
-    headers1: &[String],
-    headers2: &[String],
-    diffs: &[DiffRow],
-    output_path: &str,
+const SUMMARY_SHEET: &str = "Summary";
+const HEADERS_SHEET: &str = "Headers Comparison";
+const DATA_SHEET: &str = "Data Differences";
+
+/// An in-workbook hyperlink target, e.g. `internal:'Data Differences'!A1`.
+fn sheet_link(sheet_name: &str) -> String {
+    format!("internal:'{}'!A1", sheet_name)
+}
+
+const CHANGED_ROWS_SHEET: &str = "Changed Rows";
+
+/// Background colors for the difference categories in the Excel report.
+#[derive(Clone, Copy)]
+struct ExcelPalette<'a> {
+    added: &'a str,
+    removed: &'a str,
+    changed: &'a str,
+    /// No fills at all (--no-color-excel and --excel-style minimal)
+    monochrome: bool,
+}
+
+impl ExcelPalette<'_> {
+    /// The fill for a Data Differences row
+    fn row_format(&self, diff: &DiffRow) -> Format {
+        if self.monochrome {
+            return Format::new();
+        }
+        let color = match diff.column.as_str() {
+            "[missing in file1]" => self.added,
+            "[missing in file2]" => self.removed,
+            _ => self.changed,
+        };
+        Format::new().set_background_color(color)
+    }
+
+    /// Changed cells of the Changed Rows sheet: filled, or in italics without colors
+    fn highlight_format(&self) -> Format {
+        if self.monochrome { Format::new().set_italic() } else { Format::new().set_background_color(self.changed) }
+    }
+
+    /// Table headers: bold, on grey unless monochrome
+    fn header_format(&self) -> Format {
+        if self.monochrome { Format::new().set_bold() } else { Format::new().set_bold().set_background_color("CCCCCC") }
+    }
+}
+
+/// Write a sheet's title in the first row and return the row its content starts at. Minimal
+/// reports, which have no `title_format`, leave the title out.
+fn write_sheet_title(sheet: &mut Worksheet, title: &str, title_format: Option<&Format>) -> Result<u32, Box<dyn Error>> {
+    match title_format {
+        Some(format) => {
+            sheet.write_with_format(0, 0, title, format)?;
+            Ok(2)
+        }
+        None => Ok(0),
+    }
+}
+
+/// Everything that goes into the Excel report.
+struct ExcelReport<'a> {
+    file1_paths: &'a [PathBuf],
+    file2_paths: &'a [PathBuf],
+    headers1: &'a [String],
+    headers2: &'a [String],
+    /// Differences as displayed (after --detect-transpositions)
+    diffs: &'a [DiffRow],
+    coverage: &'a Coverage,
+    moved_columns: Option<&'a [MovedColumn]>,
+    /// File1 and file2 column types from --infer-types
+    column_types: Option<&'a (ColumnTypes, ColumnTypes)>,
+    /// Numeric Change Distribution section of the Summary sheet (--numeric-histogram)
+    histogram: Option<&'a NumericHistogram>,
+    max_rows: Option<usize>,
     headers_content_match: bool,
     schema_identical: bool,
-) -> Result<(), Box<dyn Error>> {
+    /// Source rows and per-column differences for the Changed Rows sheet, when requested
+    changed_rows: Option<ChangedRows<'a>>,
+    palette: ExcelPalette<'a>,
+    /// --excel-style minimal: no titles, links back to the Summary, tables or charts
+    minimal: bool,
+    /// Populate the worksheets concurrently (off with --no-parallel-excel)
+    parallel: bool,
+    /// Deflate level for the saved file, when not rust_xlsxwriter's default
+    compress_level: Option<u8>,
+    /// Summary sheet title and workbook Title property (--report-title)
+    title: &'a str,
+    /// --report-author, --report-date and --report-tag
+    author: Option<&'a str>,
+    date: Option<&'a str>,
+    tag: Option<&'a str>,
+    /// Distinct keys compared, out of which `diffs` touch some
+    total_keys: usize,
+    /// Field names for the Key, Column and value headers
+    settings: &'a output::Settings,
+}
+
+const DEFAULT_REPORT_TITLE: &str = "CSV Comparison Summary";
+
+/// Input for the before/after Changed Rows sheet.
+struct ChangedRows<'a> {
+    map1: &'a HashMap<String, StringRecord>,
+    map2: &'a HashMap<String, StringRecord>,
+    /// Per-column differences, before any merging for display
+    diffs: &'a [DiffRow],
+}
+
+/// Save a workbook with its parts deflated at `level` (1 = fastest, 9 = smallest).
+///
+/// rust_xlsxwriter always uses the default level, so the finished file is repacked.
+fn save_with_compression(workbook: &mut Workbook, path: &str, level: u8) -> Result<(), Box<dyn Error>> {
+    let buffer = workbook.save_to_buffer()?;
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(buffer))?;
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(path)?);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .compression_level(Some(i64::from(level)));
+    for index in 0..archive.len() {
+        let mut part = archive.by_index(index)?;
+        writer.start_file(part.name().to_string(), options)?;
+        std::io::copy(&mut part, &mut writer)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Fills in one worksheet of the Excel report.
+type SheetBuilder<'a> = Box<dyn FnOnce(&mut Worksheet) -> Result<(), Box<dyn Error>> + Send + 'a>;
+
+/// Populate the report's worksheets, each on its own thread unless `parallel` is off.
+///
+/// Worksheets are independent until they are added to the workbook, so the Data Differences
+/// and Changed Rows sheets (the large ones) are written at the same time; the result keeps
+/// the builders' order.
+fn build_sheets(builders: Vec<(&str, SheetBuilder)>, parallel: bool) -> Result<Vec<Worksheet>, Box<dyn Error>> {
+    let build = |name: &str, builder: SheetBuilder| -> Result<Worksheet, String> {
+        let mut sheet = Worksheet::new();
+        sheet.set_name(name).map_err(|e| e.to_string())?;
+        builder(&mut sheet).map_err(|e| e.to_string())?;
+        Ok(sheet)
+    };
+    if !parallel {
+        return builders.into_iter().map(|(name, builder)| build(name, builder).map_err(Into::into)).collect();
+    }
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = builders
+            .into_iter()
+            .map(|(name, builder)| scope.spawn(move || build(name, builder)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().map_err(|_| "Excel worksheet thread panicked")?.map_err(Into::into))
+            .collect()
+    })
+}
+
+/// Link back to the Summary beside a detail sheet's title; sheets without a title row have none.
+fn write_back_link(sheet: &mut Worksheet, title_format: Option<&Format>) -> Result<(), Box<dyn Error>> {
+    if title_format.is_some() {
+        sheet.write_url_with_text(0, 3, sheet_link(SUMMARY_SHEET).as_str(), "← Back to Summary")?;
+    }
+    Ok(())
+}
+
+fn generate_excel_report(report: &ExcelReport, output_path: &str) -> Result<(), Box<dyn Error>> {
+    let ExcelReport {
+        file1_paths,
+        file2_paths,
+        headers1,
+        headers2,
+        diffs,
+        coverage,
+        moved_columns,
+        column_types,
+        max_rows,
+        headers_content_match,
+        schema_identical,
+        palette,
+        title,
+        total_keys,
+        author,
+        date,
+        tag,
+        histogram,
+        minimal,
+        settings,
+        ..
+    } = *report;
     let mut workbook = Workbook::new();
+    let mut properties = DocProperties::new().set_title(title);
+    if let Some(author) = author {
+        properties = properties.set_author(author);
+    }
+    if let Some(tag) = tag {
+        properties = properties.set_keywords(tag);
+    }
+    workbook.set_properties(&properties);
+    let details: Vec<(&str, &str)> = [("Author:", author), ("Date:", date), ("Tag:", tag)]
+        .into_iter()
+        .filter_map(|(label, value)| Some((label, value?)))
+        .collect();
+    let details = &details;
+
+    // rust_xlsxwriter keeps every cell in memory until the workbook is saved, so
+    // --excel-max-rows is the way to bound memory for very large diffs
+    let data_rows = &diffs[..max_rows.map_or(diffs.len(), |limit| limit.min(diffs.len()))];
+    if data_rows.len() < diffs.len() {
+        warning!(settings,
+            "⚠️  Excel Data Differences sheet limited to {} of {} rows (--excel-max-rows)",
+            data_rows.len(),
+            diffs.len()
+        );
+    }
     
     // Create formats
-    let header_format = Format::new().set_bold().set_background_color("CCCCCC");
+    let header_format = palette.header_format();
     let title_format = Format::new().set_bold().set_font_size(14);
+    let title_format = (!minimal).then_some(&title_format);
     
-    // Sheet 1: General Summary
-    let mut summary_sheet = workbook.add_worksheet();
-    summary_sheet.set_name("Summary")?;
-    
-    create_summary_sheet(&mut summary_sheet, file1_path, file2_path, headers1, headers2, diffs, headers_content_match, schema_identical, &title_format, &header_format)?;
-    
-    // Sheet 2: Headers Comparison  
-    let mut headers_sheet = workbook.add_worksheet();
-    headers_sheet.set_name("Headers Comparison")?;
-    
-    create_headers_sheet(&mut headers_sheet, headers1, headers2, &title_format, &header_format)?;
-    
-    // Sheet 3: Data Differences
-    let mut data_sheet = workbook.add_worksheet();
-    data_sheet.set_name("Data Differences")?;
-    
-    create_data_sheet(&mut data_sheet, diffs, &title_format, &header_format)?;
+    let mut sheet_names = vec![HEADERS_SHEET, DATA_SHEET];
+    if report.changed_rows.is_some() {
+        sheet_names.push(CHANGED_ROWS_SHEET);
+    }
+    let header_format = &header_format;
+    let sheet_names = &sheet_names;
+
+    let mut builders: Vec<(&str, SheetBuilder)> = vec![
+        // Sheet 1: General Summary
+        (SUMMARY_SHEET, Box::new(move |sheet: &mut Worksheet| {
+            create_summary_sheet(sheet, title, details, file1_paths, file2_paths, headers1, headers2, diffs, data_rows.len(), total_keys, coverage, histogram, sheet_names, headers_content_match, schema_identical, title_format, header_format)
+        })),
+        // Sheet 2: Headers Comparison
+        (HEADERS_SHEET, Box::new(move |sheet: &mut Worksheet| {
+            create_headers_sheet(sheet, headers1, headers2, moved_columns, column_types, title_format, header_format)?;
+            write_back_link(sheet, title_format)
+        })),
+        // Sheet 3: Data Differences
+        (DATA_SHEET, Box::new(move |sheet: &mut Worksheet| {
+            write_back_link(sheet, title_format)?;
+            create_data_sheet(sheet, data_rows, palette, title_format, header_format, settings)
+        })),
+    ];
+
+    // Sheet 4: Changed Rows (optional)
+    if let Some(changed_rows) = &report.changed_rows {
+        builders.push((CHANGED_ROWS_SHEET, Box::new(move |sheet: &mut Worksheet| {
+            create_changed_rows_sheet(sheet, headers1, headers2, changed_rows, palette, title_format, header_format, settings)?;
+            write_back_link(sheet, title_format)
+        })));
+    }
+
+    for sheet in build_sheets(builders, report.parallel)? {
+        workbook.push_worksheet(sheet);
+    }
     
-    workbook.save(output_path)?;
-    println!("📄 Excel report generated: {}", output_path);
+    match report.compress_level {
+        Some(level) => save_with_compression(&mut workbook, output_path, level)?,
+        None => workbook.save(output_path)?,
+    }
+    report!(settings, "📄 Excel report generated: {}", output_path);
     
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_summary_sheet(
     sheet: &mut Worksheet,
-    file1_path: &PathBuf,
-    file2_path: &PathBuf,
+    title: &str,
+    details: &[(&str, &str)],
+    file1_paths: &[PathBuf],
+    file2_paths: &[PathBuf],
     headers1: &[String],
     headers2: &[String],
     diffs: &[DiffRow],
+    data_sheet_rows: usize,
+    total_keys: usize,
+    coverage: &Coverage,
+    histogram: Option<&NumericHistogram>,
+    sheet_names: &[&str],
     headers_content_match: bool,
     schema_identical: bool,
-    title_format: &Format,
+    title_format: Option<&Format>,
     header_format: &Format,
 ) -> Result<(), Box<dyn Error>> {
-    let mut row = 0;
-    
-    // Title
-    sheet.write_with_format(row, 0, "CSV Comparison Summary", title_format)?;
-    row += 2;
+    let mut row = write_sheet_title(sheet, title, title_format)?;
     
-    // File information
+    // File information, under the custom title so the report can be identified from this block
+    if title != DEFAULT_REPORT_TITLE {
+        sheet.write_with_format(row, 0, "Report:", header_format)?;
+        sheet.write(row, 1, title)?;
+        row += 1;
+    }
+    // Operator details from --report-author, --report-date and --report-tag
+    for (label, value) in details {
+        sheet.write_with_format(row, 0, *label, header_format)?;
+        sheet.write(row, 1, *value)?;
+        row += 1;
+    }
+
     sheet.write_with_format(row, 0, "File 1:", header_format)?;
-    sheet.write(row, 1, file1_path.to_string_lossy())?;
+    sheet.write(row, 1, describe_paths(file1_paths))?;
     row += 1;
     
     sheet.write_with_format(row, 0, "File 2:", header_format)?;
-    sheet.write(row, 1, file2_path.to_string_lossy())?;
+    sheet.write(row, 1, describe_paths(file2_paths))?;
     row += 2;
+
+    // Links to the detail sheets
+    sheet.write_with_format(row, 0, "Sheets", header_format)?;
+    row += 1;
+    for &name in sheet_names {
+        sheet.write_url_with_text(row, 0, sheet_link(name).as_str(), name)?;
+        row += 1;
+    }
+    row += 1;
     
     // Statistics
     sheet.write_with_format(row, 0, "Comparison Statistics", header_format)?;
@@ -314,6 +2902,17 @@ fn create_summary_sheet(
     sheet.write(row, 0, "Total Differences:")?;
     sheet.write(row, 1, diffs.len() as f64)?;
     row += 1;
+
+    let key_counts = KeyCounts::of(diffs);
+    sheet.write(row, 0, "Keys with Differences:")?;
+    sheet.write(row, 1, format!("{} of {}", key_counts.keys, total_keys))?;
+    row += 1;
+
+    if data_sheet_rows < diffs.len() {
+        sheet.write(row, 0, "Rows in Data Sheet:")?;
+        sheet.write(row, 1, format!("{} of {} (truncated by --excel-max-rows)", data_sheet_rows, diffs.len()))?;
+        row += 1;
+    }
     
     sheet.write(row, 0, "File 1 Columns:")?;
     sheet.write(row, 1, headers1.len() as f64)?;
@@ -335,17 +2934,7 @@ fn create_summary_sheet(
     row += 2;
     
     // Difference breakdown
-    let mut missing_in_file1 = 0;
-    let mut missing_in_file2 = 0;
-    let mut data_differences = 0;
-    
-    for diff in diffs {
-        match diff.column.as_str() {
-            "[missing in file1]" => missing_in_file1 += 1,
-            "[missing in file2]" => missing_in_file2 += 1,
-            _ => data_differences += 1,
-        }
-    }
+    let data_differences = diffs.len() - key_counts.missing_in_file1 - key_counts.missing_in_file2;
     
     sheet.write_with_format(row, 0, "Difference Breakdown", header_format)?;
     row += 1;
@@ -353,14 +2942,62 @@ fn create_summary_sheet(
     sheet.write(row, 0, "Data Differences:")?;
     sheet.write(row, 1, data_differences as f64)?;
     row += 1;
+
+    sheet.write(row, 0, "Rows with Value Changes:")?;
+    sheet.write(row, 1, key_counts.changed as f64)?;
+    row += 1;
     
     sheet.write(row, 0, "Missing in File 1:")?;
-    sheet.write(row, 1, missing_in_file1 as f64)?;
+    sheet.write(row, 1, key_counts.missing_in_file1 as f64)?;
     row += 1;
     
     sheet.write(row, 0, "Missing in File 2:")?;
-    sheet.write(row, 1, missing_in_file2 as f64)?;
+    sheet.write(row, 1, key_counts.missing_in_file2 as f64)?;
+    row += 2;
+
+    if let Some(histogram) = histogram {
+        sheet.write_with_format(row, 0, "Numeric Change Distribution", header_format)?;
+        row += 1;
+        let total = histogram.total();
+        for ((label, _), count) in analysis::HISTOGRAM_BUCKETS.iter().zip(histogram.counts) {
+            sheet.write(row, 0, format!("{} change:", label))?;
+            sheet.write(row, 1, count as f64)?;
+            if total > 0 {
+                sheet.write(row, 2, format!("{:.1}%", count as f64 * 100.0 / total as f64))?;
+            }
+            row += 1;
+        }
+        row += 1;
+    }
+
+    sheet.write_with_format(row, 0, "Coverage Report", header_format)?;
+    row += 1;
+
+    for (label, value) in coverage.lines() {
+        sheet.write(row, 0, label)?;
+        sheet.write(row, 1, value)?;
+        row += 1;
+    }
+    row += 1;
+
+    // Tool version for reproducibility
+    sheet.write_with_format(row, 0, "Generated By", header_format)?;
+    row += 1;
+
+    for (label, value) in [
+        ("csvdiff Version:", env!("CARGO_PKG_VERSION")),
+        ("Git Commit:", env!("CSVDIFF_GIT_HASH")),
+        ("Build Time:", env!("CSVDIFF_BUILD_TIMESTAMP")),
+        ("Compiler:", env!("CSVDIFF_RUSTC_VERSION")),
+        ("Target:", env!("CSVDIFF_TARGET")),
+    ] {
+        sheet.write(row, 0, label)?;
+        sheet.write(row, 1, value)?;
+        row += 1;
+    }
     
+    write_column_breakdown(sheet, diffs, header_format, title_format.is_none())?;
+
     // Auto-fit columns
     sheet.set_column_width(0, 20)?;
     sheet.set_column_width(1, 40)?;
@@ -368,18 +3005,92 @@ fn create_summary_sheet(
     Ok(())
 }
 
+/// First column of the per-column breakdown table on the Summary sheet
+const BREAKDOWN_COLUMN: u16 = 3;
+/// Bars shown in the breakdown chart; the table itself lists every column
+const BREAKDOWN_CHART_BARS: usize = 15;
+
+/// Differences counted per column, most changed first, as an Excel table with a bar chart
+/// beside the summary statistics, or as plain cells when `minimal`. Missing rows are counted
+/// under their `[missing in …]` label.
+fn write_column_breakdown(sheet: &mut Worksheet, diffs: &[DiffRow], header_format: &Format, minimal: bool) -> Result<(), Box<dyn Error>> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for diff in diffs {
+        *counts.entry(diff.column.as_str()).or_default() += 1;
+    }
+    if counts.is_empty() {
+        return Ok(());
+    }
+    let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    let col = BREAKDOWN_COLUMN;
+    sheet.write_with_format(2, col, "Differences by Column", header_format)?;
+    let first_row = 3;
+    let last_row = first_row + counts.len() as u32 + 1; // header and total rows
+    for (i, (column, count)) in counts.iter().enumerate() {
+        let row = first_row + 1 + i as u32;
+        sheet.write(row, col, *column)?;
+        sheet.write(row, col + 1, *count as f64)?;
+        if minimal {
+            sheet.write(row, col + 2, format!("{:.1}%", *count as f64 * 100.0 / diffs.len() as f64))?;
+        }
+    }
+    sheet.set_column_width(col, 30)?;
+    sheet.set_column_width(col + 1, 12)?;
+    sheet.set_column_width(col + 2, 10)?;
+    if minimal {
+        for (offset, header) in ["Column", "Differences", "Share"].into_iter().enumerate() {
+            sheet.write_with_format(first_row, col + offset as u16, header, header_format)?;
+        }
+        sheet.write_with_format(last_row, col, "Total", header_format)?;
+        sheet.write_with_format(last_row, col + 1, diffs.len() as f64, header_format)?;
+        return Ok(());
+    }
+
+    let table = rust_xlsxwriter::Table::new()
+        .set_name("DiffsByColumn")
+        .set_total_row(true)
+        .set_columns(&[
+            TableColumn::new().set_header("Column").set_total_label("Total"),
+            TableColumn::new().set_header("Differences").set_total_function(TableFunction::Sum),
+            TableColumn::new()
+                .set_header("Share")
+                .set_formula("[@Differences]/SUM(DiffsByColumn[Differences])")
+                .set_format(Format::new().set_num_format("0.0%")),
+        ]);
+    sheet.add_table(first_row, col, last_row, col + 2, &table)?;
+
+    let bars = counts.len().min(BREAKDOWN_CHART_BARS) as u32;
+    let mut chart = Chart::new(ChartType::Bar);
+    chart
+        .add_series()
+        .set_categories((SUMMARY_SHEET, first_row + 1, col, first_row + bars, col))
+        .set_values((SUMMARY_SHEET, first_row + 1, col + 1, first_row + bars, col + 1));
+    let title = if counts.len() > BREAKDOWN_CHART_BARS {
+        format!("Top {} Columns by Differences", BREAKDOWN_CHART_BARS)
+    } else {
+        "Differences by Column".to_string()
+    };
+    chart.title().set_name(&title);
+    chart.legend().set_hidden();
+    // Bar charts draw the first category at the bottom; put the most changed column on top
+    chart.y_axis().set_reverse();
+    sheet.insert_chart(2, col + 4, &chart)?;
+
+    Ok(())
+}
+
 fn create_headers_sheet(
     sheet: &mut Worksheet,
     headers1: &[String],
     headers2: &[String],
-    title_format: &Format,
+    moved_columns: Option<&[MovedColumn]>,
+    column_types: Option<&(ColumnTypes, ColumnTypes)>,
+    title_format: Option<&Format>,
     header_format: &Format,
 ) -> Result<(), Box<dyn Error>> {
-    let mut row = 0;
-    
-    // Title
-    sheet.write_with_format(row, 0, "Headers Comparison", title_format)?;
-    row += 2;
+    let mut row = write_sheet_title(sheet, "Headers Comparison", title_format)?;
     
     // Create sets for comparison
     let set1: HashSet<&String> = headers1.iter().collect();
@@ -390,6 +3101,10 @@ fn create_headers_sheet(
     sheet.write_with_format(row, 1, "In File 1", header_format)?;
     sheet.write_with_format(row, 2, "In File 2", header_format)?;
     sheet.write_with_format(row, 3, "Status", header_format)?;
+    if column_types.is_some() {
+        sheet.write_with_format(row, 4, "File 1 Type", header_format)?;
+        sheet.write_with_format(row, 5, "File 2 Type", header_format)?;
+    }
     row += 1;
     
     // Get all unique headers
@@ -412,184 +3127,1450 @@ fn create_headers_sheet(
             (false, false) => unreachable!(),
         };
         sheet.write(row, 3, status)?;
+        if let Some((types1, types2)) = column_types {
+            for (col, types) in [(4, types1), (5, types2)] {
+                if let Some((_, column_type)) = types.iter().find(|(name, _)| name == header) {
+                    sheet.write(row, col, column_type.label())?;
+                }
+            }
+        }
+        row += 1;
+    }
+
+    // Positional comparison from --detect-column-reorder
+    if let Some(moved) = moved_columns {
+        row += 1;
+        sheet.write_with_format(row, 0, "Column Reordering", title_format.unwrap_or(header_format))?;
+        row += 2;
+        if moved.is_empty() {
+            sheet.write(row, 0, "No columns changed position")?;
+        } else {
+            sheet.write_with_format(row, 0, "Column Name", header_format)?;
+            sheet.write_with_format(row, 1, "File 1 Position", header_format)?;
+            sheet.write_with_format(row, 2, "File 2 Position", header_format)?;
+            row += 1;
+            for column in moved {
+                sheet.write(row, 0, &column.name)?;
+                sheet.write(row, 1, column.position1 as f64)?;
+                sheet.write(row, 2, column.position2 as f64)?;
+                row += 1;
+            }
+        }
+    }
+    
+    // Auto-fit columns
+    sheet.set_column_width(0, 25)?;
+    sheet.set_column_width(1, 12)?;
+    sheet.set_column_width(2, 12)?;
+    sheet.set_column_width(3, 15)?;
+    if column_types.is_some() {
+        sheet.set_column_width(4, 12)?;
+        sheet.set_column_width(5, 12)?;
+    }
+    
+    Ok(())
+}
+
+fn create_data_sheet(
+    sheet: &mut Worksheet,
+    diffs: &[DiffRow],
+    palette: ExcelPalette,
+    title_format: Option<&Format>,
+    header_format: &Format,
+    settings: &output::Settings,
+) -> Result<(), Box<dyn Error>> {
+    let mut row = write_sheet_title(sheet, "Data Differences", title_format)?;
+    
+    // Headers
+    let headers = [("key", "Key"), ("column", "Column"), ("file1", "File 1 Value"), ("file2", "File 2 Value"), (output::SIMILARITY_FIELD, "Similarity %")];
+    for (col, (field, header)) in (0..).zip(headers) {
+        sheet.write_with_format(row, col, settings.renamed_field(field).unwrap_or(header), header_format)?;
+    }
+    let show_edit_distance = diffs.iter().any(|diff| diff.edit_distance.is_some());
+    if show_edit_distance {
+        sheet.write_with_format(row, 5, "Edit Distance", header_format)?;
+    }
+    // Line numbers follow the other columns when the input formats have lines
+    let line_col: u16 = if show_edit_distance { 6 } else { 5 };
+    let show_lines = diffs.iter().any(|diff| diff.file1_line.is_some() || diff.file2_line.is_some());
+    if show_lines {
+        sheet.write_with_format(row, line_col, "File 1 Line", header_format)?;
+        sheet.write_with_format(row, line_col + 1, "File 2 Line", header_format)?;
+    }
+    row += 1;
+    
+    // Data rows, filled by category: added, removed or changed
+    for diff in diffs {
+        let format = palette.row_format(diff);
+        sheet.write_with_format(row, 0, truncate_for_excel(&diff.key), &format)?;
+        sheet.write_with_format(row, 1, truncate_for_excel(&diff.column), &format)?;
+        sheet.write_with_format(row, 2, truncate_for_excel(&diff.file1), &format)?;
+        sheet.write_with_format(row, 3, truncate_for_excel(&diff.file2), &format)?;
+        match diff.similarity {
+            Some(similarity) => sheet.write_with_format(row, 4, (similarity * 1000.0).round() / 10.0, &format)?,
+            None => sheet.write_blank(row, 4, &format)?,
+        };
+        if show_edit_distance {
+            match diff.edit_distance {
+                Some(distance) => sheet.write_with_format(row, 5, distance as f64, &format)?,
+                None => sheet.write_blank(row, 5, &format)?,
+            };
+        }
+        if show_lines {
+            for (col, line) in [(line_col, diff.file1_line), (line_col + 1, diff.file2_line)] {
+                match line {
+                    Some(line) => sheet.write_with_format(row, col, line as f64, &format)?,
+                    None => sheet.write_blank(row, col, &format)?,
+                };
+            }
+        }
         row += 1;
     }
-    
-    // Auto-fit columns
-    sheet.set_column_width(0, 25)?;
-    sheet.set_column_width(1, 12)?;
-    sheet.set_column_width(2, 12)?;
-    sheet.set_column_width(3, 15)?;
-    
-    Ok(())
+    
+    // Auto-fit columns
+    sheet.set_column_width(0, 30)?;
+    sheet.set_column_width(1, 20)?;
+    sheet.set_column_width(2, 30)?;
+    sheet.set_column_width(3, 30)?;
+    sheet.set_column_width(4, 14)?;
+    if show_edit_distance {
+        sheet.set_column_width(5, 14)?;
+    }
+    if show_lines {
+        sheet.set_column_width(line_col, 12)?;
+        sheet.set_column_width(line_col + 1, 12)?;
+    }
+    
+    Ok(())
+}
+
+/// Parse the command line, filling in options from --config first.
+fn parse_args(mut argv: Vec<String>) -> Args {
+    let mut command = Args::command();
+    let is_subcommand = argv.get(1).is_some_and(|arg| command.find_subcommand(arg).is_some());
+    if !is_subcommand {
+        let given = argv.iter().skip(1).cloned().collect::<Vec<_>>();
+        let config_path = given.iter().enumerate().find_map(|(i, arg)| match arg.strip_prefix("--config") {
+            Some("") => given.get(i + 1).cloned(),
+            Some(rest) => rest.strip_prefix('=').map(String::from),
+            None => None,
+        });
+        if let Some(path) = config_path {
+            let options = config::load(Path::new(&path))
+                .map_err(|e| e.to_string())
+                .and_then(|document| {
+                    config::to_args(&document.root, &command, &config::given_options(&given, &command))
+                        .map_err(|e| format!("{}: {}", path, e))
+                })
+                .unwrap_or_else(|e| command.error(ErrorKind::InvalidValue, e).exit());
+            argv.splice(1..1, options);
+        }
+    }
+    Args::parse_from(argv)
+}
+
+/// Before/after view: two rows per key with value changes, changed cells highlighted.
+#[allow(clippy::too_many_arguments)]
+fn create_changed_rows_sheet(
+    sheet: &mut Worksheet,
+    headers1: &[String],
+    headers2: &[String],
+    changed_rows: &ChangedRows,
+    palette: ExcelPalette,
+    title_format: Option<&Format>,
+    header_format: &Format,
+    settings: &output::Settings,
+) -> Result<(), Box<dyn Error>> {
+    let highlight_format = palette.highlight_format();
+    let mut row = write_sheet_title(sheet, "Changed Rows", title_format)?;
+
+    // file1's columns in order, then columns only in file2
+    let columns: Vec<&String> = headers1.iter().chain(headers2.iter().filter(|h| !headers1.contains(h))).collect();
+    sheet.write_with_format(row, 0, settings.renamed_field("key").unwrap_or("Key"), header_format)?;
+    sheet.write_with_format(row, 1, "Source", header_format)?;
+    for (i, column) in columns.iter().enumerate() {
+        sheet.write_with_format(row, i as u16 + 2, column.as_str(), header_format)?;
+    }
+    row += 1;
+
+    // Where each displayed column sits in each file's records
+    let positions = |headers: &[String]| -> Vec<Option<usize>> {
+        columns.iter().map(|column| headers.iter().position(|h| h == *column)).collect()
+    };
+    let (positions1, positions2) = (positions(headers1), positions(headers2));
+
+    let mut changed_columns: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for diff in changed_rows.diffs.iter().filter(|diff| diff.is_value_change()) {
+        changed_columns.entry(diff.key.as_str()).or_default().insert(diff.column.as_str());
+    }
+    let mut keys: Vec<&&str> = changed_columns.keys().collect();
+    keys.sort();
+
+    for key in keys {
+        // Fuzzy matches are displayed as "key1 ≈ key2"
+        let (key1, key2) = key.split_once(" ≈ ").unwrap_or((key, key));
+        let changed = &changed_columns[*key];
+        for (label, positions, record) in [
+            ("File 1", &positions1, changed_rows.map1.get(key1)),
+            ("File 2", &positions2, changed_rows.map2.get(key2)),
+        ] {
+            let Some(record) = record else { continue };
+            sheet.write(row, 0, truncate_for_excel(key))?;
+            sheet.write(row, 1, label)?;
+            for (i, column) in columns.iter().enumerate() {
+                let Some(value) = positions[i].and_then(|index| record.get(index)) else {
+                    continue;
+                };
+                let col = i as u16 + 2;
+                if changed.contains(column.as_str()) {
+                    sheet.write_with_format(row, col, truncate_for_excel(value), &highlight_format)?;
+                } else {
+                    sheet.write(row, col, truncate_for_excel(value))?;
+                }
+            }
+            row += 1;
+        }
+    }
+
+    sheet.set_column_width(0, 20)?;
+    sheet.set_column_width(1, 10)?;
+    for i in 0..columns.len() {
+        sheet.set_column_width(i as u16 + 2, 16)?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = parse_args(std::env::args().collect());
+    let error_format = args.error_format;
+    let verbosity = args.verbosity();
+    let result = match &args.command {
+        Some(Command::Apply(apply_args)) => patch::run_apply(apply_args),
+        Some(Command::Profile(profile_args)) => profile::run(profile_args),
+        Some(Command::ConfigSchema) => {
+            print!("{}", config::schema(&Args::command()));
+            Ok(())
+        }
+        None => run_diff(args),
+    };
+    // Exit codes follow diff(1), where 1 means differences were found: 2 and up mean trouble
+    if let Err(e) = result {
+        let error = CsvDiffError::classify(e.as_ref());
+        if verbosity != Verbosity::Silent {
+            match error_format {
+                ErrorFormat::Text => eprintln!("Error: {}", error),
+                ErrorFormat::Json => eprintln!("{}", error.to_json()),
+            }
+        }
+        std::process::exit(error.exit_code());
+    }
+}
+
+fn run_diff(args: Args) -> Result<(), Box<dyn Error>> {
+    if args.all_sheets {
+        return compare_all_sheets(args);
+    }
+    compare(args).map(|_| ())
+}
+
+/// `--all-sheets`: run the comparison once per sheet present in both workbooks, then summarise.
+fn compare_all_sheets(args: Args) -> Result<(), Box<dyn Error>> {
+    let [path1, path2] = [&args.file1, &args.file2].map(|paths| match paths.as_slice() {
+        [path] if args.format.resolve(path) == InputFormat::Xlsx => Ok(path.clone()),
+        _ => Err("--all-sheets needs a single .xlsx workbook for --file1 and for --file2"),
+    });
+    let (path1, path2) = (path1?, path2?);
+    let settings = args.settings()?;
+    let reader1 = input::ExcelReader::open(&path1)?;
+    let reader2 = input::ExcelReader::open(&path2)?;
+    let (sheets1, sheets2) = (reader1.sheet_names(), reader2.sheet_names());
+
+    for (sheets, path, other) in [(&sheets1, &path1, &sheets2), (&sheets2, &path2, &sheets1)] {
+        for name in sheets.iter().filter(|name| !other.contains(name)) {
+            warning!(settings, "⚠️  Sheet '{}' is only in {} and is not compared", name, path.display());
+        }
+    }
+    let common: Vec<String> = sheets1.iter().filter(|name| sheets2.contains(name)).map(|name| name.to_string()).collect();
+    if common.is_empty() {
+        return Err(format!("{} and {} have no sheet names in common", path1.display(), path2.display()).into());
+    }
+
+    let mut results = Vec::new();
+    for name in &common {
+        report!(settings, "📑 Sheet '{}'", name);
+        report!(settings, "{}", "=".repeat(40));
+        let differences = compare(Args { sheet_name: Some(name.clone()), all_sheets: false, ..args.clone() })?;
+        results.push((name, differences));
+        report!(settings);
+    }
+
+    let differing = results.iter().filter(|(_, differences)| *differences > 0).count();
+    report!(settings, "📑 All sheets: {} compared, {} with differences", results.len(), differing);
+    for (name, differences) in results {
+        let status = if differences == 0 { "✅" } else { "❌" };
+        report!(settings, "   {} {}: {} differences", status, name, differences);
+    }
+    Ok(())
+}
+
+/// Both inputs once read: their headers and keyed rows, and what the read stage found out
+/// about their columns.
+struct Inputs {
+    mapping: ColumnMapping,
+    headers1: Vec<String>,
+    headers2: Vec<String>,
+    map1: HashMap<String, StringRecord>,
+    map2: HashMap<String, StringRecord>,
+    /// Each file's [`CsvData::parts`]
+    parts: [HashMap<String, u64>; 2],
+    /// Rows read per file, before sampling and duplicate keys
+    total_rows: [usize; 2],
+    headers1_map: HashMap<String, usize>,
+    headers2_map: HashMap<String, usize>,
+    /// Set with --detect-column-reorder
+    moved_columns: Option<Vec<MovedColumn>>,
+    rules: Vec<ComparisonRule>,
+    headers_content_match: bool,
+    schema_identical: bool,
+}
+
+/// What the diff stage found, borrowing the rows from [`Inputs`].
+struct Differences<'a> {
+    diffs: Vec<DiffRow>,
+    /// --diff-limit-per-key summary rows, kept apart from the counted differences
+    column_limits: Vec<DiffRow>,
+    changed: Vec<(KeyedRecord<'a>, KeyedRecord<'a>)>,
+    removed: Vec<KeyedRecord<'a>>,
+    added: Vec<KeyedRecord<'a>>,
+    truncated: bool,
+    total_keys: usize,
+    coverage: Coverage,
+    column_types: Option<(ColumnTypes, ColumnTypes)>,
+}
+
+/// Compare the two inputs, returning the number of differences reported.
+///
+/// The run has three stages: [`check_options`] and [`read_inputs`] load both files,
+/// [`diff_inputs`] compares their rows and [`report_differences`] prints and writes the results.
+fn compare(args: Args) -> Result<usize, Box<dyn Error>> {
+    let started = std::time::Instant::now();
+    let settings = args.settings()?;
+    let (args, mapping) = check_options(args, &settings)?;
+    // Read before the comparison, so a missing or malformed file fails the run straight away
+    let expected_diffs = match &args.expected_diffs {
+        Some(path) if !args.update_expected => Some(expect::load(path, &settings)?),
+        _ => None,
+    };
+    if args.diff_mode == DiffMode::Headers {
+        return compare_headers(&args, &mapping, &settings);
+    }
+
+    let inputs = read_inputs(&args, mapping, &settings)?;
+    let differences = diff_inputs(&args, &inputs, &settings)?;
+    report_differences(&args, &settings, &inputs, differences, expected_diffs, started)
+}
+
+/// The read stage, part one: check the options against each other and against the header
+/// rows, before any data is loaded. Returns the arguments with `--ignore` and `--key` filled
+/// in from `--ignore-index`, `--no-key` and `--header-mapping`, and the column mapping.
+fn check_options(args: Args, settings: &output::Settings) -> Result<(Args, ColumnMapping), Box<dyn Error>> {
+    // Clap requires both files whenever no subcommand is given
+    if args.file1.is_empty() || args.file2.is_empty() {
+        return Err("--file1 and --file2 are required".into());
+    }
+    let (file1, file2) = (args.file1.clone(), args.file2.clone());
+
+    for path in file1.iter().chain(&file2) {
+        ensure_supported_format(path, args.format.resolve(path))?;
+        if !path.exists() {
+            return Err(CsvDiffError::FileNotFound { file: path.display().to_string() }.into());
+        }
+    }
+    let excel_input = file1.iter().chain(&file2).any(|path| args.format.resolve(path) == InputFormat::Xlsx);
+    if args.auto_detect_encoding {
+        report_encodings(&file1, &file2, args.format, settings)?;
+    }
+    warn_line_ending_mismatch(&file1, &file2, args.format, settings)?;
+    if (args.sheet_name.is_some() || args.sheet_index.is_some()) && !excel_input {
+        warning!(settings, "⚠️  --sheet-name and --sheet-index only apply to Excel input; neither file is an .xlsx workbook");
+    }
+
+    if args.diff_mode == DiffMode::BestMatch && (!args.key.is_empty() || args.fuzzy_keys.is_some() || args.no_key) {
+        return Err("--diff-mode best-match pairs rows by content and cannot be combined with --key, --no-key or --fuzzy-keys".into());
+    }
+
+    let mut args = Args {
+        ignore: parse_column_lists(&args.ignore)?,
+        ..args
+    };
+    if args.output_format == Some(OutputFormat::Patch) && (args.diff_limit.is_some() || args.diff_limit_per_key.is_some()) {
+        return Err("A patch needs every difference and cannot be combined with --diff-limit or --diff-limit-per-key".into());
+    }
+    if let Some(time_column) = &args.time_column
+        && !args.key.contains(time_column)
+    {
+        return Err(format!("--time-column '{}' must be one of the --key columns, so the other key columns identify each series", time_column).into());
+    }
+    if args.output_format == Some(OutputFormat::Patch) && args.anonymize_keys {
+        return Err("A patch identifies rows by their real keys and cannot be combined with --anonymize-keys".into());
+    }
+    if let (Some(format), Some(_)) = (args.output_format, &args.split_output)
+        && !matches!(format, OutputFormat::Json | OutputFormat::Yaml)
+    {
+        return Err(format!("--split-output writes csv, json or yaml files and cannot be combined with --output-format {}", format.to_possible_value().map_or(String::new(), |value| value.get_name().to_string())).into());
+    }
+    if args.output_format == Some(OutputFormat::Patch) && args.normalize_column_order {
+        return Err("A patch records the files' own column order and cannot be combined with --normalize-column-order".into());
+    }
+    let mut mapping = ColumnMapping { renames: parse_renames(&args.rename)?, copies: Vec::new() };
+
+    // Columns left out of the mapping file are ignored, or rejected with --strict-mapping
+    if let Some(mapping_path) = &args.header_mapping {
+        let headers1 = read_headers(&file1, &ColumnMapping::default(), &args, settings)?;
+        let headers2 = read_headers(&file2, &ColumnMapping::default(), &args, settings)?;
+        mapping = read_header_mapping(mapping_path, &headers1, &headers2)?;
+        let mapped2: Vec<&String> = mapping.renames.values().chain(mapping.copies.iter().map(|(_, to)| to)).collect();
+        let unmapped1: Vec<&String> = headers1.iter().filter(|h| !mapping.renames.contains_key(*h) && !args.key.contains(h)).collect();
+        let unmapped2: Vec<&String> = headers2.iter().filter(|h| !mapped2.contains(h) && !args.key.contains(h)).collect();
+        if args.strict_mapping && !(unmapped1.is_empty() && unmapped2.is_empty()) {
+            let list = |columns: &[&String]| columns.iter().map(|c| c.as_str()).collect::<Vec<_>>().join(", ");
+            return Err(format!(
+                "--strict-mapping: columns missing from {}: file 1 [{}], file 2 [{}]",
+                mapping_path.display(),
+                list(&unmapped1),
+                list(&unmapped2)
+            )
+            .into());
+        }
+        report!(settings, "📋 Header mapping: {} file 1 columns mapped to {} file 2 columns", mapping.renames.len(), mapped2.len());
+        if !(unmapped1.is_empty() && unmapped2.is_empty()) {
+            let mut ignored: Vec<String> = Vec::new();
+            for column in unmapped1.into_iter().chain(unmapped2) {
+                if !ignored.contains(column) {
+                    ignored.push(column.clone());
+                }
+            }
+            report!(settings, "   Ignoring {} unmapped columns: {}", ignored.len(), ignored.join(", "));
+            for column in ignored {
+                if !args.ignore.contains(&column) {
+                    args.ignore.push(column);
+                }
+            }
+        }
+        report!(settings);
+    }
+    if !mapping.copies.is_empty() && (args.output_format == Some(OutputFormat::Patch) || args.revert_output.is_some()) {
+        return Err("A patch replays file1's own columns and cannot be combined with a header mapping that maps one column to several".into());
+    }
+
+    // Positions become names up front, so everything after treats them exactly like --ignore
+    if !args.ignore_index.is_empty() {
+        let headers1 = read_headers(&args.file1, &mapping, &args, settings)?;
+        for name in resolve_column_indexes(&headers1, &args.ignore_index, "--ignore-index", &describe_paths(&args.file1))? {
+            let same_name = headers1.iter().filter(|h| **h == name).count();
+            if same_name > 1 && !args.ignore.contains(&name) {
+                warning!(settings, "⚠️  --ignore-index resolves to column '{}', which file1 has {} times; all of them are ignored", name, same_name);
+            }
+            if !args.ignore.contains(&name) {
+                args.ignore.push(name);
+            }
+        }
+    }
+
+    // Every compared column becomes part of the key, so a changed row no longer matches at all
+    if args.no_key {
+        let headers1 = read_headers(&args.file1, &mapping, &args, settings)?;
+        args.key = headers1.into_iter().filter(|h| !args.ignore.contains(h)).collect();
+        report!(settings, "📋 No-key mode: rows are identified by all {} compared columns, so the comparison is a set difference", args.key.len());
+        report!(settings, "   A changed row is reported as missing in file 1 (its new version) and missing in file 2 (its old version)\n");
+    }
+
+    // Key columns are checked against the header rows alone, so a typo fails before any data
+    // is loaded. JSONL, fixed-width and Excel inputs are read whole to find their columns, so
+    // those are checked after loading instead.
+    let delimited = |p: &PathBuf| !matches!(args.format.resolve(p), InputFormat::Jsonl | InputFormat::Fixed | InputFormat::Xlsx);
+    if !args.key.is_empty() && file1.iter().chain(&args.file2).all(delimited) {
+        let headers1 = read_headers(&args.file1, &mapping, &args, settings)?;
+        let headers2 = read_headers(&args.file2, &ColumnMapping::default(), &args, settings)?;
+        let missing = |headers: &[String]| args.key.iter().filter(|k| !headers.contains(k)).cloned().collect::<Vec<_>>();
+        validate_keys_and_suggest_alternatives((&args.file1, &args.file2), &missing(&headers1), &missing(&headers2), &headers1, &headers2, args.error_format, settings)?;
+    }
+
+    if args.columns_report {
+        let headers1 = read_headers(&args.file1, &mapping, &args, settings)?;
+        let headers2 = read_headers(&args.file2, &ColumnMapping::default(), &args, settings)?;
+        print_columns_report(&headers1, &headers2, &args, args.table_style(), settings);
+    }
+    Ok((args, mapping))
 }
 
-fn create_data_sheet(
-    sheet: &mut Worksheet,
-    diffs: &[DiffRow],
-    title_format: &Format,
-    header_format: &Format,
-) -> Result<(), Box<dyn Error>> {
-    let mut row = 0;
-    
-    // Title
-    sheet.write_with_format(row, 0, "Data Differences", title_format)?;
-    row += 2;
-    
-    // Headers
-    sheet.write_with_format(row, 0, "Key", header_format)?;
-    sheet.write_with_format(row, 1, "Column", header_format)?;
-    sheet.write_with_format(row, 2, "File 1 Value", header_format)?;
-    sheet.write_with_format(row, 3, "File 2 Value", header_format)?;
-    row += 1;
-    
-    // Data rows
-    for diff in diffs {
-        sheet.write(row, 0, &truncate_for_excel(&diff.key))?;
-        sheet.write(row, 1, &truncate_for_excel(&diff.column))?;
-        sheet.write(row, 2, &truncate_for_excel(&diff.file1))?;
-        sheet.write(row, 3, &truncate_for_excel(&diff.file2))?;
-        row += 1;
+/// `--diff-mode headers`: compare the header rows alone, and list every sheet of an Excel
+/// input. Exits with status 1 when a column is found in only one file, at every verbosity.
+fn compare_headers(args: &Args, mapping: &ColumnMapping, settings: &output::Settings) -> Result<usize, Box<dyn Error>> {
+    let headers1 = read_headers(&args.file1, mapping, args, settings)?;
+    let headers2 = read_headers(&args.file2, &ColumnMapping::default(), args, settings)?;
+    if args.file1.iter().chain(&args.file2).any(|path| args.format.resolve(path) == InputFormat::Xlsx) {
+        print_sheets_report(&args.file1, &args.file2, args, settings)?;
     }
-    
-    // Auto-fit columns
-    sheet.set_column_width(0, 30)?;
-    sheet.set_column_width(1, 20)?;
-    sheet.set_column_width(2, 30)?;
-    sheet.set_column_width(3, 30)?;
-    
-    Ok(())
+
+    let (headers_content_match, schema_identical) = check_schema_match(&headers1, &headers2);
+    print_schema_status(headers_content_match, schema_identical, settings);
+    report!(settings);
+    if args.detect_column_reorder {
+        print_moved_columns(&find_moved_columns(&headers1, &headers2), settings);
+    }
+
+    let only1: Vec<&str> = headers1.iter().filter(|h| !headers2.contains(h)).map(String::as_str).collect();
+    let only2: Vec<&str> = headers2.iter().filter(|h| !headers1.contains(h)).map(String::as_str).collect();
+    for (only, file) in [(&only1, 1), (&only2, 2)] {
+        if !only.is_empty() {
+            report!(settings, "📋 Columns only in file {}: {}", file, only.join(", "));
+        }
+    }
+    if !only1.is_empty() || !only2.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(0)
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+/// The read stage, part two: load both inputs and report what reading them revealed, from
+/// sampling and duplicate keys to the schema match.
+fn read_inputs(args: &Args, mapping: ColumnMapping, settings: &output::Settings) -> Result<Inputs, Box<dyn Error>> {
+    let sampler = args.sample.map(|size| Sampler::new(size, args.sample_seed));
+
+    let sheet = args.sheet();
+    let anonymizer = args.anonymize_keys.then(|| Anonymizer::new(args.anonymize_salt.clone()));
+    let read_options = ReadOptions {
+        format: args.format,
+        sheet: &sheet,
+        key_columns: &args.key,
+        fixed_columns: &args.column_widths,
+        sampler: sampler.as_ref(),
+        check_duplicates: args.check_duplicates || args.dedup_strategy.is_some(),
+        dedup_strategy: args.dedup_strategy,
+        limit_keys: args.limit_keys,
+        key_separator: &args.key_sep,
+        dialect: args.dialect(),
+        max_key_length: args.max_key_length,
+        strict_key_length: args.strict_key_length,
+        anonymizer: anonymizer.as_ref(),
+        settings,
+    };
+
+    let mut data1 = read_tabular_to_map(&args.file1, &mapping, &read_options)?;
+    let mut data2 = read_tabular_to_map(&args.file2, &ColumnMapping::default(), &read_options)?;
+
+    if let Some(sampler) = &sampler {
+        sampler.retain(&mut data1.records, &mut data2.records);
+        report!(settings,
+            "🎲 Sample (seed {}): comparing {} of {} rows from file 1 and {} of {} rows from file 2",
+            sampler.seed,
+            data1.records.len(),
+            data1.total_rows,
+            data2.records.len(),
+            data2.total_rows
+        );
+        report!(settings, "   Results are approximate and only cover the sampled keys");
+        report!(settings);
+    }
 
-    let (headers1, map1, missing_keys1) = read_csv_to_map(args.file1.clone(), &args.key)?;
-    let (headers2, map2, missing_keys2) = read_csv_to_map(args.file2.clone(), &args.key)?;
+    if let Some(limit) = args.limit_keys
+        && (data1.limited || data2.limited)
+    {
+        warning!(settings, "⚠️  Results limited to first {} keys of each file (--limit-keys)", limit);
+        warning!(settings, "   Keys beyond the limit in either file are reported as missing from the other");
+        warning!(settings);
+    }
 
     // Validate keys and provide helpful error messages if keys are missing
-    validate_keys_and_suggest_alternatives(&args.key, &missing_keys1, &missing_keys2, &headers1, &headers2)?;
+    validate_keys_and_suggest_alternatives((&args.file1, &args.file2), &data1.missing_keys, &data2.missing_keys, &data1.headers, &data2.headers, args.error_format, settings)?;
+    // --columns-report has already listed them
+    if !args.columns_report {
+        warn_unknown_columns("--ignore", &args.ignore, &data1.headers, &data2.headers, settings);
+    }
+
+    if let Some(anonymizer) = &anonymizer {
+        for data in [&mut data1, &mut data2] {
+            let key_indexes: Vec<usize> = args.key.iter().filter_map(|key| data.headers.iter().position(|h| h == key)).collect();
+            anonymizer.anonymize(data, &key_indexes);
+        }
+        if let Some(path) = &args.anonymize_map {
+            anonymizer.write_map(path, settings)?;
+        }
+    }
+
+    if read_options.check_duplicates {
+        report_duplicate_rows(&data1.duplicate_rows, &data2.duplicate_rows, args.dedup_strategy, settings)?;
+    }
+
+    // Warn about non-unique keys before they silently skew the comparison
+    report_duplicate_keys(&args.key, &data1.duplicate_keys, &data2.duplicate_keys, args.strict_keys, settings)?;
+
+    let parts = [data1.parts, data2.parts];
+    let (mut headers1, mut map1, total_rows1) = (data1.headers, data1.records, data1.total_rows);
+    let (mut headers2, mut map2, total_rows2) = (data2.headers, data2.records, data2.total_rows);
+
+    // Positions are compared before --normalize-column-order sorts them away
+    let moved_columns = args.detect_column_reorder.then(|| find_moved_columns(&headers1, &headers2));
+    if args.normalize_column_order {
+        sort_columns(&mut headers1, &mut map1, mapping.copies.len());
+        sort_columns(&mut headers2, &mut map2, 0);
+        report!(settings, "🔤 Columns sorted by name in both files (--normalize-column-order)");
+    }
+
+    let rules = parse_rules(&args.rule)?;
+    validate_rules(&rules, &headers1, &headers2)?;
+    if let Some(column) = args.normalize_phone_numbers.iter().find(|c| !headers1.contains(c) && !headers2.contains(c)) {
+        return Err(format!("Column '{}' given to --normalize-phone-numbers was not found in either file", column).into());
+    }
+
+    if args.detect_renames {
+        let detected = detect_renamed_columns(&headers1, &map1, &headers2, &map2);
+        if detected.is_empty() {
+            report!(settings, "🔍 No renamed columns detected");
+        } else {
+            report!(settings, "🔍 Possible renamed columns detected:");
+            for (from, to, ratio) in &detected {
+                report!(settings, "   • {} → {} ({:.0}% of sampled values match)", from, to, ratio * 100.0);
+            }
+            let flags: Vec<String> = detected.iter().map(|(from, to, _)| format!("--rename {}={}", from, to)).collect();
+            report!(settings, "   Re-run with {} to compare them as the same column", flags.join(" "));
+        }
+        report!(settings);
+    }
 
     // Check schema compatibility
     let (headers_content_match, schema_identical) = check_schema_match(&headers1, &headers2);
     
-    // Report schema status
-    if schema_identical {
-        println!("✅ Schema Match: Headers are identical (same columns in same order)");
-    } else if headers_content_match {
-        println!("⚠️  Schema Partial Match: Same columns but different order");
-    } else {
-        println!("❌ Schema Mismatch: Different columns between files");
+    print_schema_status(headers_content_match, schema_identical, settings);
+    report!(settings); // Add blank line for readability
+
+    if let Some(moved) = &moved_columns {
+        print_moved_columns(moved, settings);
     }
-    println!(); // Add blank line for readability
 
     // Create column index mappings for both files
     let headers1_map: HashMap<String, usize> = headers1.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
     let headers2_map: HashMap<String, usize> = headers2.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
 
-    let mut diffs = Vec::new();
+    Ok(Inputs {
+        mapping,
+        headers1,
+        headers2,
+        map1,
+        map2,
+        parts,
+        total_rows: [total_rows1, total_rows2],
+        headers1_map,
+        headers2_map,
+        moved_columns,
+        rules,
+        headers_content_match,
+        schema_identical,
+    })
+}
+
+/// The diff stage: pair the rows of both inputs and compare them.
+fn diff_inputs<'a>(args: &Args, inputs: &'a Inputs, settings: &output::Settings) -> Result<Differences<'a>, Box<dyn Error>> {
+    let Inputs { headers1, headers2, map1, map2, headers1_map, headers2_map, rules, .. } = inputs;
+    let [total_rows1, total_rows2] = inputs.total_rows;
+    let compare_options = CompareOptions {
+        null_values: args.null_values.clone(),
+        null_equals_empty: args.null_equals_empty,
+        normalize_percentages: args.normalize_percentages,
+        normalize_integers: args.normalize_integers,
+        normalize_uuids: args.normalize_uuids,
+        normalize_ip_addresses: args.normalize_ip_addresses,
+        phone_columns: args.normalize_phone_numbers.clone(),
+        phone_country: args.phone_country.clone(),
+        locale: args.locale,
+        normalize_scientific: args.normalize_scientific,
+        tolerance: args.tolerance,
+        timezone_normalize: args.timezone_normalize.is_some(),
+        assume_timezone: args.assume_timezone.clone(),
+    };
+
+    let value_columns: HashSet<&String> = headers1.iter().chain(headers2.iter()).filter(|h| !args.key.contains(h)).collect();
+    let mut coverage = Coverage {
+        columns_compared: value_columns
+            .iter()
+            .filter(|h| headers1_map.contains_key(**h) && headers2_map.contains_key(**h) && !args.ignore.contains(h))
+            .count(),
+        columns_total: value_columns.len(),
+        rows_compared: [map1.len(), map2.len()],
+        rows_total: [total_rows1, total_rows2],
+        ..Default::default()
+    };
 
     let all_keys: HashSet<_> = map1.keys().chain(map2.keys()).collect();
 
-    for key in all_keys {
-        match (map1.get(key), map2.get(key)) {
-            (Some(r1), Some(r2)) => {
-                // Get all unique column names from both files
-                let all_columns: HashSet<String> = headers1.iter().chain(headers2.iter()).cloned().collect();
-                
-                for col_name in all_columns {
-                    if args.key.contains(&col_name) || args.ignore.contains(&col_name) {
-                        continue;
-                    }
+    // Pair up keys that only differ by typos or formatting
+    let fuzzy_matches = match args.fuzzy_keys {
+        Some(threshold) => match_fuzzy_keys(map1, map2, threshold)?,
+        None => Vec::new(),
+    };
+    if !fuzzy_matches.is_empty() {
+        report!(settings, "🔗 Fuzzy key matches ({}):", fuzzy_matches.len());
+        for m in &fuzzy_matches {
+            report!(settings, "   • {} ≈ {} (similarity {:.3})", m.key1, m.key2, m.score);
+        }
+        report!(settings);
+    }
+    let fuzzy_by_key1: HashMap<&String, &String> = fuzzy_matches.iter().map(|m| (&m.key1, &m.key2)).collect();
+    let fuzzy_keys2: HashSet<&String> = fuzzy_matches.iter().map(|m| &m.key2).collect();
 
-                    let v1 = headers1_map.get(&col_name).and_then(|&i| r1.get(i)).unwrap_or("");
-                    let v2 = headers2_map.get(&col_name).and_then(|&i| r2.get(i)).unwrap_or("");
-                    
-                    // Handle cases where column exists in only one file
-                    let (v1_display, v2_display) = match (headers1_map.contains_key(&col_name), headers2_map.contains_key(&col_name)) {
-                        (true, true) => {
-                            // Column exists in both files, compare values
-                            if v1 != v2 {
-                                (v1.to_string(), v2.to_string())
-                            } else {
-                                continue; // Values are the same, skip
-                            }
-                        },
-                        (true, false) => {
-                            // Column only exists in file1
-                            (v1.to_string(), "[column not in file2]".to_string())
-                        },
-                        (false, true) => {
-                            // Column only exists in file2
-                            ("[column not in file1]".to_string(), v2.to_string())
-                        },
-                        (false, false) => unreachable!(), // Column came from one of the files
-                    };
-
-                    diffs.push(DiffRow {
-                        key: key.clone(),
-                        column: col_name.clone(),
-                        file1: v1_display,
-                        file2: v2_display,
-                    });
-                }
+    // Without a key, rows are paired by content rather than by position
+    let best_matches = if args.diff_mode == DiffMode::BestMatch {
+        let compared: Vec<(usize, usize)> = headers1
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| !args.ignore.contains(h))
+            .filter_map(|(i, h)| headers2_map.get(h).map(|&j| (i, j)))
+            .collect();
+        if compared.is_empty() {
+            return Err("--diff-mode best-match needs at least one column present in both files".into());
+        }
+        Some(match_best_rows(map1, map2, headers1, &compared, &compare_options)?)
+    } else {
+        None
+    };
+
+    // The file1 and file2 keys compared with each other; a missing side means a missing row
+    let mut row_pairs: Vec<(Option<&String>, Option<&String>)> = match &best_matches {
+        Some(matches) => {
+            let paired1: HashSet<&String> = matches.iter().map(|m| &m.key1).collect();
+            let paired2: HashSet<&String> = matches.iter().map(|m| &m.key2).collect();
+            matches
+                .iter()
+                .map(|m| (Some(&m.key1), Some(&m.key2)))
+                .chain(map1.keys().filter(|key| !paired1.contains(key)).map(|key| (Some(key), None)))
+                .chain(map2.keys().filter(|key| !paired2.contains(key)).map(|key| (None, Some(key))))
+                .collect()
+        }
+        None => all_keys
+            .into_iter()
+            // Fuzzy-matched file2 keys are compared together with their file1 partner
+            .filter(|key| !fuzzy_keys2.contains(key) || map1.contains_key(*key))
+            .map(|key| match fuzzy_by_key1.get(key) {
+                Some(key2) => (Some(key), Some(*key2)),
+                None => (map1.contains_key(key).then_some(key), map2.contains_key(key).then_some(key)),
+            })
+            .collect(),
+    };
+
+    // "First" means in file1 order; rows only in file2 follow in file2 order
+    if args.first_diff {
+        let [parts1, parts2] = &inputs.parts;
+        let line = |map: &HashMap<String, StringRecord>, parts, key: Option<&String>| {
+            key.and_then(|k| map.get_key_value(k)).map_or((u64::MAX, 0), |record| input_order(parts, record))
+        };
+        row_pairs.sort_by_key(|&(key1, key2)| (line(map1, parts1, key1), line(map2, parts2, key2)));
+    }
+    // --diff-limit keeps the first differences in key order rather than whichever keys hash first
+    if args.diff_limit.is_some() {
+        if args.sort_keys_desc {
+            row_pairs.sort_by(|a, b| b.0.or(b.1).cmp(&a.0.or(a.1)));
+        } else {
+            row_pairs.sort_by(|a, b| a.0.or(a.1).cmp(&b.0.or(b.1)));
+        }
+    }
+
+    // Existence-only modes show whole rows rather than a short preview
+    let existence_only = matches!(args.diff_mode, DiffMode::LeftOnly | DiffMode::RightOnly);
+    let preview_limit = if existence_only { None } else { Some(args.preview_length) };
+    match args.diff_mode {
+        DiffMode::LeftOnly => report!(settings, "📋 Diff mode left-only: keys in file 1 without a match in file 2\n"),
+        DiffMode::RightOnly => report!(settings, "📋 Diff mode right-only: keys in file 2 without a match in file 1\n"),
+        DiffMode::CommonColumns => {
+            let exclusive: Vec<&String> = headers1
+                .iter()
+                .filter(|h| !headers2_map.contains_key(*h))
+                .chain(headers2.iter().filter(|h| !headers1_map.contains_key(*h)))
+                .collect();
+            if !exclusive.is_empty() {
+                let names: Vec<&str> = exclusive.iter().map(|h| h.as_str()).collect();
+                report!(settings, "ℹ️  Ignoring {} columns not present in both files: {}\n", exclusive.len(), names.join(", "));
             }
-            (Some(r1), None) => {
-                let preview = r1
-                    .iter()
-                    .collect::<Vec<_>>()
-                    .join(",")
-                    .chars()
-                    .take(50)
-                    .collect::<String>();
-                let preview = if preview.len() >= 50 { 
-                    format!("{}...", &preview[..47]) 
-                } else { 
-                    preview 
+        }
+        DiffMode::BestMatch => {
+            let matches = best_matches.as_deref().unwrap_or_default();
+            let exact = matches.iter().filter(|m| m.differing == 0).count();
+            report!(settings,
+                "🧩 Best-match pairing: {} rows paired ({} exact, {} with differences); {} file 1 rows and {} file 2 rows unpaired",
+                matches.len(),
+                exact,
+                matches.len() - exact,
+                map1.len() - matches.len(),
+                map2.len() - matches.len()
+            );
+            report!(settings, "   Keys are row numbers; paired rows are shown as \"file1 row ≈ file2 row\"\n");
+        }
+        DiffMode::Full | DiffMode::Headers => {}
+    }
+
+    let total_keys = row_pairs.len();
+    let report_progress = |index, total| warning!(settings, "⏳ Comparing key {} of {}...", index, total);
+    let config = DiffConfig {
+        key_columns: &args.key,
+        ignore: &args.ignore,
+        rules,
+        compare_options: &compare_options,
+        diff_mode: args.diff_mode,
+        show_edit_distance: args.show_edit_distance,
+        min_diff_pct: args.min_diff_pct,
+        diff_limit: if args.first_diff { Some(1) } else { args.diff_limit },
+        diff_limit_per_key: args.diff_limit_per_key,
+        preview_format: args.preview_format,
+        preview_length: preview_limit,
+        no_missing_row_preview: args.no_missing_row_preview,
+        progress: (!args.no_progress).then_some(&report_progress as &dyn Fn(usize, usize)),
+        progress_interval: args.progress_interval as usize,
+    };
+    // Inferred once, for the json and yaml metadata and the Excel report alike
+    let column_types = args
+        .infer_types
+        .then(|| (types::infer(headers1, map1.values()), types::infer(headers2, map2.values())));
+    let comparison = compare_rows(row_pairs, map1, headers1, map2, headers2, &config);
+    coverage.cells_compared = comparison.cells_compared;
+    coverage.cells_total = comparison.cells_total;
+    let Comparison { diffs, changed, removed, added, truncated, .. } = comparison;
+    // --diff-limit-per-key summary rows are shown in the listing only, never counted or exported
+    let (column_limits, mut diffs): (Vec<DiffRow>, Vec<DiffRow>) = diffs.into_iter().partition(DiffRow::is_truncation);
+
+    // Keys come out in HashMap order; a BTreeMap for the records would keep them sorted at
+    // all times, but the sort is opt-in so scripts reading the current order keep working.
+    // Within a key, columns are ordered as in file1, then the columns only in file2.
+    if args.sort_keys || args.sort_keys_desc {
+        let position = |diff: &DiffRow| {
+            headers1_map.get(&diff.column).copied().or_else(|| headers2_map.get(&diff.column).map(|i| headers1.len() + i))
+        };
+        diffs.sort_by(|a, b| {
+            let keys = if args.sort_keys_desc { b.key.cmp(&a.key) } else { a.key.cmp(&b.key) };
+            keys.then_with(|| position(a).cmp(&position(b)))
+        });
+    }
+
+    Ok(Differences {
+        diffs,
+        column_limits,
+        changed,
+        removed,
+        added,
+        truncated,
+        total_keys,
+        coverage,
+        column_types,
+    })
+}
+
+/// The report stage: print the differences and the analyses asked for, then write the output
+/// files. Returns the number of differences reported.
+fn report_differences(
+    args: &Args,
+    settings: &output::Settings,
+    inputs: &Inputs,
+    differences: Differences,
+    expected_diffs: Option<Vec<expect::Entry>>,
+    started: std::time::Instant,
+) -> Result<usize, Box<dyn Error>> {
+    let Inputs { mapping, headers1, headers2, map1, map2, headers2_map, moved_columns, headers_content_match, schema_identical, .. } = inputs;
+    let (headers_content_match, schema_identical) = (*headers_content_match, *schema_identical);
+    let [total_rows1, total_rows2] = inputs.total_rows;
+    let Differences { diffs, column_limits, changed: changed_records, removed: removed_records, added: added_records, truncated, total_keys, coverage, column_types } = differences;
+    let (file1, file2) = (&args.file1, &args.file2);
+    let table_style = args.table_style();
+
+    if args.first_diff {
+        if diffs.is_empty() {
+            report!(settings, "✅ No differences found.");
+            return Ok(0);
+        }
+        let diff = &diffs[0];
+        report!(settings, "🔍 First difference (comparison stopped here):");
+        report!(settings, "   Key:    {}", diff.key);
+        report!(settings, "   Column: {}", diff.column);
+        report!(settings, "   File 1: {}", diff.file1);
+        report!(settings, "   File 2: {}", diff.file2);
+        std::process::exit(1);
+    }
+
+    if let Some(limit) = args.diff_limit
+        && truncated
+    {
+        warning!(settings, "⚠️  Differences limited to {} (--diff-limit); coverage figures only cover the rows compared", limit);
+    }
+
+    // Swapped values are shown as one row; the patch still needs the individual columns
+    let transposed = args.detect_transpositions.then(|| analysis::merge_transpositions(&diffs));
+    let report_diffs = transposed.as_ref().map_or(&diffs, |(merged, _)| merged);
+    // --check overrides every output, --output-format included
+    if args.check {
+        if !report_diffs.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(0);
+    }
+    let excel_palette = ExcelPalette {
+        added: &args.excel_added_color,
+        removed: &args.excel_removed_color,
+        changed: &args.excel_changed_color,
+        monochrome: args.no_color_excel || args.excel_style == ExcelStyle::Minimal,
+    };
+
+    // Missing rows are hidden from the listing only; every other output still has them
+    let (shown_diffs, hidden) = if args.ignore_missing {
+        let (missing, shown): (Vec<DiffRow>, Vec<DiffRow>) = report_diffs.iter().cloned().partition(|diff| diff.column.starts_with("[missing in file"));
+        (shown, Some(missing))
+    } else {
+        (report_diffs.clone(), None)
+    };
+    let shown_diffs = with_column_limits(shown_diffs, &column_limits);
+    let shown_diffs = if args.compact { compact_diffs(shown_diffs, args.verbose) } else { shown_diffs };
+
+    if shown_diffs.is_empty() {
+        report!(settings, "✅ No differences found.");
+    } else if args.browse {
+        browse::run(&shown_diffs, args.max_cell_width, excel_palette, settings)?;
+    } else if args.diff_format == DiffFormat::SideBySide {
+        let width = args.side_by_side_width as usize;
+        report!(settings, "{}", create_side_by_side(&shown_diffs, (headers1, map1), (headers2, map2), width, args.max_rows, settings));
+    } else {
+        report!(settings, "{}", create_summary_table(shown_diffs, args.max_rows, args.max_cell_width, args.no_truncate, args.show_line_numbers, args.min_diff_pct.is_some(), table_style, settings));
+    }
+    if let Some(hidden) = hidden.filter(|hidden| !hidden.is_empty()) {
+        let only_in_file1 = hidden.iter().filter(|diff| diff.column == "[missing in file2]").count();
+        report!(settings,
+            "ℹ️  {} more differences not shown (--ignore-missing): {} rows only in file 1, {} rows only in file 2",
+            hidden.len(),
+            only_in_file1,
+            hidden.len() - only_in_file1
+        );
+    }
+
+    if !report_diffs.is_empty() {
+        let counts = KeyCounts::of(report_diffs);
+        report!(settings,
+            "🔑 Keys with differences: {} of {} ({} rows missing in file 1, {} rows missing in file 2, {} rows with value changes)",
+            counts.keys, total_keys, counts.missing_in_file1, counts.missing_in_file2, counts.changed
+        );
+    }
+
+    if let Some((_, count)) = &transposed {
+        report!(settings);
+        if *count == 0 {
+            report!(settings, "🔀 No transpositions detected");
+        } else {
+            report!(settings, "🔀 TRANSPOSITION: {} rows have two columns with swapped values", count);
+        }
+    }
+
+    if args.coverage_report {
+        report!(settings);
+        report!(settings, "📊 Coverage Report");
+        for (label, value) in coverage.lines() {
+            report!(settings, "   {:<22} {}", label, value);
+        }
+    }
+
+    if args.detect_splits_merges {
+        let shared_columns: Vec<(usize, usize)> = headers1
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| !args.key.contains(h) && !args.ignore.contains(h))
+            .filter_map(|(i, h)| headers2_map.get(h).map(|&j| (i, j)))
+            .collect();
+        let findings = analysis::detect_splits_merges(map1, map2, &shared_columns);
+        report!(settings);
+        if findings.is_empty() {
+            report!(settings, "🧩 No row splits or merges detected");
+        } else {
+            report!(settings, "🧩 Possible row splits and merges:");
+            for finding in findings.iter().take(10) { // Limit listing to 10
+                let parts = finding.parts.join(", ");
+                let (label, from, to) = match finding.kind {
+                    analysis::RegroupKind::Split => ("ROW SPLIT", finding.key.clone(), parts),
+                    analysis::RegroupKind::Merge => ("ROW MERGE", parts, finding.key.clone()),
                 };
-                
-                diffs.push(DiffRow {
-                    key: key.clone(),
-                    column: "[missing in file2]".into(),
-                    file1: preview,
-                    file2: "".into(),
-                });
+                report!(settings,
+                    "   • {}: {} → {} ({} of {} columns consistent)",
+                    label, from, to, finding.matching, finding.total
+                );
             }
-            (None, Some(r2)) => {
-                let preview = r2
-                    .iter()
-                    .collect::<Vec<_>>()
-                    .join(",")
-                    .chars()
-                    .take(50)
-                    .collect::<String>();
-                let preview = if preview.len() >= 50 { 
-                    format!("{}...", &preview[..47]) 
-                } else { 
-                    preview 
+            if findings.len() > 10 {
+                report!(settings, "   ... and {} more", findings.len() - 10);
+            }
+        }
+    }
+
+    if args.detect_scaling {
+        let findings = analysis::detect_scaling(&diffs, args.scaling_tolerance);
+        report!(settings);
+        if findings.is_empty() {
+            report!(settings, "🔍 No systematic scaling detected");
+        } else {
+            report!(settings, "🔍 Possible scaling errors:");
+            for finding in &findings {
+                report!(settings,
+                    "   • {}: possible scaling factor: {}x ({} of {} numeric changes)",
+                    finding.column,
+                    analysis::format_factor(finding.factor),
+                    finding.matching,
+                    finding.total
+                );
+            }
+        }
+    }
+
+    if args.detect_offsets {
+        let findings = analysis::detect_offsets(&diffs);
+        report!(settings);
+        if findings.is_empty() {
+            report!(settings, "🔍 No constant offsets detected");
+        } else {
+            report!(settings, "🔍 Possible constant offsets:");
+            for finding in &findings {
+                let sign = if finding.offset > 0.0 { "+" } else { "" };
+                report!(settings,
+                    "   • Possible constant offset: {}{} in column `{}` ({} of {} numeric changes)",
+                    sign,
+                    analysis::format_factor(finding.offset),
+                    finding.column,
+                    finding.matching,
+                    finding.total
+                );
+            }
+        }
+    }
+
+    let histogram = args.numeric_histogram.then(|| analysis::numeric_histogram(&diffs));
+    if let Some(histogram) = &histogram {
+        report!(settings);
+        let total = histogram.total();
+        if total == 0 {
+            report!(settings, "📊 No changed numeric values");
+        } else {
+            report!(settings, "📊 Numeric change distribution ({} changed numeric values):", total);
+            let widest = histogram.counts.iter().copied().max().unwrap_or(1);
+            for ((label, _), count) in analysis::HISTOGRAM_BUCKETS.iter().zip(histogram.counts) {
+                let bar = "█".repeat((count * HISTOGRAM_BAR_WIDTH).div_ceil(widest));
+                report!(settings, "   {:<6} {:>8} ({:>5.1}%) {}", label, count, count as f64 * 100.0 / total as f64, bar);
+            }
+        }
+    }
+
+    if let Some(time_column) = &args.time_column {
+        let time_index = args.key.iter().position(|key| key == time_column).unwrap_or_default();
+        let trends = analysis::detect_trends(&diffs, time_index, &args.key_sep);
+        report!(settings);
+        if trends.is_empty() {
+            report!(settings, "📈 No numeric or date changes to follow over {}", time_column);
+        } else {
+            report!(settings, "📈 Direction of changes over {}:", time_column);
+            for trend in trends.iter().take(10) { // Limit listing to 10
+                let series = if trend.series.is_empty() { String::new() } else { format!("{} / ", trend.series) };
+                let period = if trend.first == trend.last { trend.first.clone() } else { format!("{} to {}", trend.first, trend.last) };
+                report!(settings, "   • {}{}: {} ({} changes, {})", series, trend.column, trend.direction.label(), trend.changes, period);
+            }
+            if trends.len() > 10 {
+                report!(settings, "   ... and {} more", trends.len() - 10);
+            }
+            let directions = [Direction::Increase, Direction::Decrease, Direction::Oscillating, Direction::Constant]
+                .map(|direction| format!("{} {}", trends.iter().filter(|trend| trend.direction == direction).count(), direction.label()));
+            report!(settings, "   Series: {}", directions.join(", "));
+        }
+    }
+
+    if args.co_occurrence_report {
+        let findings = analysis::detect_co_occurrences(&diffs);
+        report!(settings);
+        if findings.is_empty() {
+            report!(settings, "🔍 No columns consistently change together");
+        } else {
+            report!(settings, "🔍 Columns that change together:");
+            for finding in findings.iter().take(10) { // Limit listing to 10
+                let (share1, share2) = finding.shares();
+                let shares = if finding.changes1 == finding.changes2 {
+                    format!("{:.0}% of their respective changed rows", share1 * 100.0)
+                } else {
+                    format!("{:.0}% and {:.0}% of their respective changed rows", share1 * 100.0, share2 * 100.0)
                 };
-                
-                diffs.push(DiffRow {
-                    key: key.clone(),
-                    column: "[missing in file1]".into(),
-                    file1: "".into(),
-                    file2: preview,
-                });
+                report!(settings,
+                    "   • '{}' and '{}' both changed in {} ({} rows)",
+                    finding.column1, finding.column2, shares, finding.both
+                );
+            }
+            if findings.len() > 10 {
+                report!(settings, "   ... and {} more", findings.len() - 10);
             }
-            (None, None) => unreachable!(),
         }
     }
 
-    if diffs.is_empty() {
-        println!("✅ No differences found.");
-    } else {
-        println!("{}", create_summary_table(diffs.clone(), args.max_rows, args.max_cell_width, args.no_truncate));
+    if !args.foreign_key.is_empty() {
+        let results = integrity::check_foreign_keys(args.foreign_key.clone(), headers1, map1)?;
+        report!(settings);
+        report!(settings, "🔗 Referential Integrity");
+        for result in &results {
+            let fk = &result.foreign_key;
+            let label = format!("{} → {}:{}", fk.column, fk.ref_file.display(), fk.ref_column);
+            if result.violations.is_empty() {
+                report!(settings, "   ✅ {}: all {} distinct values found", label, result.checked);
+                continue;
+            }
+            report!(settings,
+                "   ❌ {}: {} of {} distinct values not found",
+                label,
+                result.violations.len(),
+                result.checked
+            );
+            for value in result.violations.iter().take(10) { // Limit listing to 10
+                report!(settings, "      • {}", value);
+            }
+            if result.violations.len() > 10 {
+                report!(settings, "      ... and {} more", result.violations.len() - 10);
+            }
+        }
+    }
+
+    let document_source = document::DocumentSource {
+        files1: file1,
+        files2: file2,
+        key_columns: &args.key,
+        total_keys,
+        column_types: column_types.as_ref(),
+        author: args.report_author.as_deref(),
+        date: args.report_date.as_deref(),
+        tag: args.report_tag.as_deref(),
+    };
+    let patch_source = patch::PatchSource {
+        files1: file1,
+        files2: file2,
+        key_columns: &args.key,
+        key_separator: &args.key_sep,
+        headers1,
+        headers2,
+        map1,
+        map2,
+    };
+    match (args.output_format, &args.output_file) {
+        (Some(OutputFormat::Patch), Some(output_file)) => patch::write_patch(output_file, &patch_source, &diffs, false, settings)?,
+        (Some(OutputFormat::Audit), Some(output_file)) => {
+            let context = audit::AuditContext {
+                files1: file1,
+                files2: file2,
+                key_columns: &args.key,
+                operator: args.operator.as_deref(),
+            };
+            audit::write_audit_log(output_file, &context, &diffs, settings)?;
+        }
+        (Some(format @ (OutputFormat::Json | OutputFormat::Yaml)), Some(output_file)) => {
+            document::write_document(output_file, &document_source, &diffs, format == OutputFormat::Yaml, settings)?;
+        }
+        (Some(OutputFormat::Sarif), Some(output_file)) => sarif::write_sarif(output_file, file1, file2, &diffs, settings)?,
+        _ => {}
+    }
+    if let Some(revert_file) = &args.revert_output {
+        patch::write_patch(revert_file, &patch_source, &diffs, true, settings)?;
     }
 
     // Generate Excel report if requested
     if let Some(excel_path) = &args.excel_output {
-        generate_excel_report(&args.file1, &args.file2, &headers1, &headers2, &diffs, excel_path, headers_content_match, schema_identical)?;
+        let report = ExcelReport {
+            file1_paths: file1,
+            file2_paths: file2,
+            headers1,
+            headers2,
+            diffs: report_diffs,
+            coverage: &coverage,
+            moved_columns: moved_columns.as_deref(),
+            column_types: column_types.as_ref(),
+            histogram: histogram.as_ref(),
+            max_rows: args.excel_max_rows,
+            headers_content_match,
+            schema_identical,
+            changed_rows: args.excel_changed_rows.then_some(ChangedRows {
+                map1,
+                map2,
+                diffs: &diffs,
+            }),
+            palette: excel_palette,
+            minimal: args.excel_style == ExcelStyle::Minimal,
+            parallel: !args.no_parallel_excel,
+            compress_level: args.excel_compress_level,
+            title: &args.report_title,
+            author: args.report_author.as_deref(),
+            date: args.report_date.as_deref(),
+            tag: args.report_tag.as_deref(),
+            total_keys,
+            settings,
+        };
+        generate_excel_report(&report, excel_path)?;
     }
 
-    Ok(())
+    // Exported rows keep file1's original columns and names, before --rename and --header-mapping
+    let original_headers1: Vec<String> = headers1[..headers1.len() - mapping.copies.len()]
+        .iter()
+        .map(|h| mapping.renames.iter().find(|(_, to)| *to == h).map_or(h, |(from, _)| from).clone())
+        .collect();
+    if let Some(prefix) = &args.split_output {
+        let split = SplitOutput {
+            prefix,
+            format: args.output_format,
+            headers1: &original_headers1,
+            headers2,
+            added: added_records.clone(),
+            removed: removed_records.clone(),
+            parts: &inputs.parts,
+            diffs: &diffs,
+            settings,
+        };
+        write_split_output(split)?;
+    }
+    let [parts1, parts2] = &inputs.parts;
+    let exports = [
+        (&args.changed_output_file1, &original_headers1, changed_records.iter().map(|(r1, _)| *r1).collect(), parts1, "changed rows from file 1"),
+        (&args.changed_output_file2, headers2, changed_records.iter().map(|(_, r2)| *r2).collect(), parts2, "changed rows from file 2"),
+        (&args.removed_output, &original_headers1, removed_records, parts1, "rows only in file 1"),
+        (&args.added_output, headers2, added_records, parts2, "rows only in file 2"),
+    ];
+    for (path, headers, records, parts, description) in exports {
+        if let Some(path) = path {
+            let path = write_records(path, headers, records, parts, settings)?;
+            report!(settings, "📄 Wrote {} to {}", description, path);
+        }
+    }
+
+    if let Some(metrics_path) = &args.metrics_output {
+        let run_metrics = metrics::RunMetrics {
+            files1: file1,
+            files2: file2,
+            diffs: &diffs,
+            rows: [total_rows1, total_rows2],
+            duration: started.elapsed(),
+        };
+        metrics::write_metrics(metrics_path, &run_metrics)?;
+    }
+
+    let usage = telemetry::Usage {
+        rows: total_rows1 + total_rows2,
+        differences: diffs.len(),
+        output_format: args.output_format.and_then(|format| format.to_possible_value()).map(|value| value.get_name().to_string()),
+        duration: started.elapsed(),
+    };
+    telemetry::report(args.enable_telemetry, &usage, settings);
+
+    if let Some(expected_path) = &args.expected_diffs {
+        let Some(expected) = expected_diffs else {
+            document::write_document(expected_path, &document_source, &diffs, false, settings)?;
+            report!(settings, "📸 Expected differences updated: {} differences", diffs.len());
+            return Ok(report_diffs.len());
+        };
+        let mismatch = expect::check(expected, &diffs);
+        if mismatch.is_empty() {
+            report!(settings, "✅ Differences match {} ({} differences)", expected_path, diffs.len());
+            return Ok(report_diffs.len());
+        }
+        report!(settings);
+        report!(settings,
+            "❌ Differences do not match {}: {} expected differences missing, {} unexpected",
+            expected_path,
+            mismatch.missing.len(),
+            mismatch.unexpected.len()
+        );
+        report!(settings, "{}", expect::render(&mismatch));
+        std::process::exit(1);
+    }
+
+    if args.fingerprint {
+        let fingerprint = fingerprint::compute(&diffs);
+        let name = args
+            .fingerprint_name
+            .clone()
+            .unwrap_or_else(|| fingerprint::default_name(file1, file2, &args.key, &args.key_sep));
+        report!(settings);
+        report!(settings, "🔏 Diff fingerprint: {}", fingerprint);
+        if fingerprint::check_and_store(&name, &fingerprint)? {
+            report!(settings, "Diff changed");
+            std::process::exit(1);
+        }
+        report!(settings, "No change in diff since last run");
+    } else if settings.verbosity != Verbosity::Normal && !report_diffs.is_empty() {
+        std::process::exit(1);
+    }
+
+    Ok(report_diffs.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(key: &str, column: &str) -> DiffRow {
+        DiffRow { key: key.to_string(), column: column.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn truncate_string_counts_characters() {
+        assert_eq!(truncate_string("short", 10), "short");
+        assert_eq!(truncate_string("abcdefghijkl", 8), "abcde...");
+        assert_eq!(truncate_string("ééééé", 5), "ééééé");
+        assert_eq!(truncate_string("éééééé", 5), "éé...");
+        assert_eq!(truncate_string("日本語のテキスト", 6), "日本語...");
+    }
+
+    #[test]
+    fn fixed_columns_parse_and_reject_overflow() {
+        let column = parse_fixed_column("name: 10 :30").unwrap();
+        assert_eq!((column.name.as_str(), column.start, column.length), ("name", 10, 30));
+        let column = parse_fixed_column("a:b:0:5").unwrap();
+        assert_eq!((column.name.as_str(), column.start, column.length), ("a:b", 0, 5));
+        assert!(parse_fixed_column(&format!("x:{}:1", usize::MAX)).is_err());
+        assert!(parse_fixed_column(&format!("x:1:{}", usize::MAX)).is_err());
+        assert!(parse_fixed_column(&format!("x:0:{}", usize::MAX)).is_ok());
+        assert!(parse_fixed_column(":0:5").is_err());
+        assert!(parse_fixed_column("x:0").is_err());
+        assert!(parse_fixed_column("x:-1:5").is_err());
+    }
+
+    /// A CSV file in the temp directory, named after the test that writes it.
+    fn temp_csv(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("csvdiff-test-{}-{}.csv", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn read_options<'a>(key: &'a [String], dedup_strategy: Option<DedupStrategy>) -> ReadOptions<'a> {
+        static SHEET: Sheet = Sheet::Index(0);
+        static SETTINGS: output::Settings = output::Settings { verbosity: Verbosity::Normal, compression: None, field_renames: Vec::new() };
+        ReadOptions {
+            format: InputFormat::Csv,
+            sheet: &SHEET,
+            key_columns: key,
+            fixed_columns: &[],
+            sampler: None,
+            check_duplicates: true,
+            dedup_strategy,
+            limit_keys: None,
+            key_separator: "|",
+            dialect: CsvDialect {
+                quote: b'"',
+                escape: None,
+                strip_trailing_delimiter: false,
+                decoding: Decoding { auto_detect: false, line_ending: LineEnding::Auto },
+            },
+            max_key_length: None,
+            strict_key_length: false,
+            anonymizer: None,
+            settings: &SETTINGS,
+        }
+    }
+
+    #[test]
+    fn exact_duplicate_rows_compare_every_field() {
+        let path = temp_csv("duplicates", "id,v\n1,a\n1,a\n1,b\n2,a\n1,a\n");
+        let key = ["id".to_string()];
+        let data = read_tabular_to_map(std::slice::from_ref(&path), &ColumnMapping::default(), &read_options(&key, None)).unwrap();
+        assert_eq!(data.duplicate_rows, ["1", "1"]);
+        assert_eq!(data.duplicate_keys, [("1".to_string(), 4)]);
+
+        let data = read_tabular_to_map(std::slice::from_ref(&path), &ColumnMapping::default(), &read_options(&key, Some(DedupStrategy::KeepFirst))).unwrap();
+        assert_eq!(data.duplicate_keys, [("1".to_string(), 2)]);
+        assert_eq!(data.records["1"].get(1), Some("b"));
+
+        // Without a key, rows are keyed by position and copies are found by their fields
+        let data = read_tabular_to_map(std::slice::from_ref(&path), &ColumnMapping::default(), &read_options(&[], None)).unwrap();
+        assert_eq!(data.duplicate_rows, ["2", "5"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn exported_rows_keep_the_order_of_the_parts() {
+        let parts = [temp_csv("part1", "id,v\n1,a\n2,b\n"), temp_csv("part2", "id,v\n3,c\n4,d\n")];
+        let key = ["id".to_string()];
+        let data = read_tabular_to_map(&parts, &ColumnMapping::default(), &read_options(&key, None)).unwrap();
+        let output = std::env::temp_dir().join(format!("csvdiff-test-{}-parts-out.csv", std::process::id()));
+        let output = output.to_str().unwrap();
+        write_records(output, &data.headers, data.records.iter().collect(), &data.parts, &output::Settings::default()).unwrap();
+        assert_eq!(std::fs::read_to_string(output).unwrap(), "id,v\n1,a\n2,b\n3,c\n4,d\n");
+        // Record positions keep their own meaning
+        assert_eq!(data.records["3"].position().map(|position| position.record()), Some(1));
+        for path in parts {
+            std::fs::remove_file(path).unwrap();
+        }
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn column_lists_split_like_csv() {
+        let values = ["a, b", "\"x,y\",c", "\"say \"\"hi\"\"\"", "a"].map(String::from);
+        assert_eq!(parse_column_lists(&values).unwrap(), ["a", "b", "x,y", "c", "say \"hi\""]);
+        assert!(parse_column_lists(&["\"open,b".to_string()]).unwrap_err().to_string().contains("unbalanced quotes"));
+        assert!(parse_column_lists(&["a,,b".to_string()]).unwrap_err().to_string().contains("cannot be empty"));
+    }
+
+    #[test]
+    fn escaped_and_doubled_quotes_read_alike() {
+        let read = |quote: u8, escape: Option<u8>, text: &str| -> String {
+            let dialect = CsvDialect {
+                quote,
+                escape,
+                strip_trailing_delimiter: false,
+                decoding: Decoding { auto_detect: false, line_ending: LineEnding::Auto },
+            };
+            let record = dialect.reader().from_reader(text.as_bytes()).records().next().unwrap().unwrap();
+            record[1].to_string()
+        };
+        // An escaped quote and the other quote character inside one field
+        assert_eq!(read(b'\'', Some(b'\\'), "id,v\n1,'it\\'s \"x\"'\n"), "it's \"x\"");
+        // Doubled quotes still work when an escape character is set
+        assert_eq!(read(b'"', Some(b'\\'), "id,v\n1,\"a\"\"b \\\"c\\\"\"\n"), "a\"b \"c\"");
+        assert_eq!(read(b'"', None, "id,v\n1,\"a\"\"b\"\n"), "a\"b");
+    }
+
+    #[test]
+    fn best_match_pairs_identical_rows_then_the_closest() {
+        let rows = |rows: &[&[&str]]| -> HashMap<String, StringRecord> {
+            rows.iter().enumerate().map(|(i, row)| ((i + 1).to_string(), StringRecord::from(row.to_vec()))).collect()
+        };
+        let headers = ["name", "id", "city"].map(String::from);
+        let columns = [(0, 0), (1, 1), (2, 2)];
+        let map1 = rows(&[&["a", "1", "x"], &["b", "2", "y"], &["c", "3", "z"], &["d", "4", "w"]]);
+        let map2 = rows(&[&["b", "2", "y"], &["a", "1", "changed"], &["q", "r", "s"], &["d", "4", "w"]]);
+        let matches = match_best_rows(&map1, &map2, &headers, &columns, &CompareOptions::default()).unwrap();
+        let pairs: Vec<(&str, &str, usize)> = matches.iter().map(|m| (m.key1.as_str(), m.key2.as_str(), m.differing)).collect();
+        // Row 3 of file1 shares no value with row 3 of file2, so both stay unpaired
+        assert_eq!(pairs, [("2", "1", 0), ("4", "4", 0), ("1", "2", 1)]);
+
+        // Repeated identical rows pair up in row order
+        let same = rows(&[&["a", "1", "x"], &["a", "1", "x"]]);
+        let matches = match_best_rows(&same, &same, &headers, &columns, &CompareOptions::default()).unwrap();
+        let pairs: Vec<(&str, &str)> = matches.iter().map(|m| (m.key1.as_str(), m.key2.as_str())).collect();
+        assert_eq!(pairs, [("1", "1"), ("2", "2")]);
+    }
+
+    #[test]
+    fn key_counts_leave_out_schema_only_rows() {
+        let schema = |key: &str| DiffRow { file1: "[column not in file1]".to_string(), ..diff(key, "added") };
+        let diffs = vec![diff("1", "a"), schema("1"), schema("2"), diff("3", "[missing in file2]")];
+        let counts = KeyCounts::of(&diffs);
+        assert_eq!((counts.keys, counts.changed, counts.missing_in_file2), (3, 1, 1));
+    }
+
+    #[test]
+    fn column_limits_follow_their_key() {
+        let diffs = vec![diff("1", "a"), diff("1", "b"), diff("2", "a"), diff("3", "a")];
+        let limits = vec![diff("1", "... (3 more columns changed)"), diff("3", "... (1 more columns changed)")];
+        let rows: Vec<(String, String)> = with_column_limits(diffs, &limits).into_iter().map(|row| (row.key, row.column)).collect();
+        let expected = [("1", "a"), ("1", "b"), ("1", "... (3 more columns changed)"), ("2", "a"), ("3", "a"), ("3", "... (1 more columns changed)")];
+        assert_eq!(rows, expected.map(|(key, column)| (key.to_string(), column.to_string())));
+    }
+
+    #[test]
+    fn compact_rows_keep_the_column_limit_apart() {
+        let diffs = vec![diff("1", "a"), diff("1", "b"), diff("2", "a")];
+        let limits = vec![diff("1", "... (3 more columns changed)")];
+        let rows: Vec<(String, String)> = compact_diffs(with_column_limits(diffs, &limits), false).into_iter().map(|row| (row.key, row.column)).collect();
+        let expected = [("1", "changed columns=[a, b]"), ("1", "... (3 more columns changed)"), ("2", "changed columns=[a]")];
+        assert_eq!(rows, expected.map(|(key, column)| (key.to_string(), column.to_string())));
+    }
 }
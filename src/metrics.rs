@@ -0,0 +1,76 @@
+//! `--metrics-output`: run statistics in the Prometheus text exposition format, for
+//! node_exporter's textfile collector or a push gateway.
+//!
+//! Every metric is a gauge labelled with the input file names, so alerts can fire when the
+//! number of differences between two feeds crosses a threshold.
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::DiffRow;
+
+/// Counts from one comparison.
+pub struct RunMetrics<'a> {
+    pub files1: &'a [PathBuf],
+    pub files2: &'a [PathBuf],
+    pub diffs: &'a [DiffRow],
+    /// Data rows read from each file
+    pub rows: [usize; 2],
+    pub duration: Duration,
+}
+
+/// File names without their directories; parts of a multi-part input are joined with `+`.
+fn basenames(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned()))
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// Escape a label value: backslash, double quote and newline are the only special characters.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Render the metrics in the Prometheus text format.
+pub fn render(metrics: &RunMetrics) -> String {
+    let missing_file1 = metrics.diffs.iter().filter(|diff| diff.column == "[missing in file1]").count();
+    let missing_file2 = metrics.diffs.iter().filter(|diff| diff.column == "[missing in file2]").count();
+    let value_changes = metrics.diffs.iter().filter(|diff| diff.is_value_change()).count();
+    let labels = format!(
+        "file1_name=\"{}\",file2_name=\"{}\"",
+        escape_label(&basenames(metrics.files1)),
+        escape_label(&basenames(metrics.files2))
+    );
+
+    let mut out = String::new();
+    for (name, help, value) in [
+        ("csvdiff_total_diffs", "Total number of differences found", metrics.diffs.len().to_string()),
+        ("csvdiff_missing_file1", "Rows present in file2 but missing from file1", missing_file1.to_string()),
+        ("csvdiff_missing_file2", "Rows present in file1 but missing from file2", missing_file2.to_string()),
+        ("csvdiff_value_changes", "Changed values in columns present in both files", value_changes.to_string()),
+        ("csvdiff_duration_seconds", "Time taken by the comparison in seconds", format!("{:.6}", metrics.duration.as_secs_f64())),
+        ("csvdiff_file1_rows", "Data rows read from file1", metrics.rows[0].to_string()),
+        ("csvdiff_file2_rows", "Data rows read from file2", metrics.rows[1].to_string()),
+    ] {
+        // Writing to a String cannot fail
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+    }
+    out
+}
+
+/// Write the metrics to `path`, or to stderr when `path` is `-`.
+pub fn write_metrics(path: &str, metrics: &RunMetrics) -> Result<(), Box<dyn Error>> {
+    let text = render(metrics);
+    if path == "-" {
+        eprint!("{}", text);
+    } else {
+        std::fs::write(path, text).map_err(|e| format!("Failed to write metrics to {}: {}", path, e))?;
+    }
+    Ok(())
+}
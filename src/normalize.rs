@@ -0,0 +1,569 @@
+//! Value normalization rules applied when comparing two cells.
+//!
+//! Each rule only kicks in when a value has the shape it understands; anything else falls
+//! back to plain string comparison. The diff output always shows the original values.
+
+use std::net::{IpAddr, Ipv4Addr};
+
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+
+use crate::datetime::{Timezone, to_utc};
+
+/// Decimal and digit grouping conventions for `--locale`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum NumberLocale {
+    /// 1,000.50
+    #[cfg_attr(feature = "cli", value(name = "en_US"))]
+    EnUs,
+    /// 1.000,50
+    #[cfg_attr(feature = "cli", value(name = "de_DE"))]
+    DeDe,
+    /// 1 000,50 (space, no-break space or narrow no-break space groups)
+    #[cfg_attr(feature = "cli", value(name = "fr_FR"))]
+    FrFr,
+}
+
+impl NumberLocale {
+    fn decimal_separator(self) -> char {
+        match self {
+            NumberLocale::EnUs => '.',
+            NumberLocale::DeDe | NumberLocale::FrFr => ',',
+        }
+    }
+
+    fn is_group_separator(self, c: char) -> bool {
+        match self {
+            NumberLocale::EnUs => c == ',',
+            NumberLocale::DeDe => c == '.',
+            NumberLocale::FrFr => matches!(c, ' ' | '\u{a0}' | '\u{202f}'),
+        }
+    }
+}
+
+/// Which normalization rules `compare_values` applies.
+#[derive(Debug, Default, Clone)]
+pub struct CompareOptions {
+    /// Markers such as `NULL` or `\N` that all mean "no value" (matched case-insensitively)
+    pub null_values: Vec<String>,
+    /// Also treat the empty string as a null marker
+    pub null_equals_empty: bool,
+    /// Treat `50%` and `0.5` as the same number
+    pub normalize_percentages: bool,
+    /// Compare decimal, hex (`0x`), binary (`0b`) and octal (`0o`) integers by value
+    pub normalize_integers: bool,
+    /// Ignore case, braces and hyphens in UUID-shaped values
+    pub normalize_uuids: bool,
+    /// Parse numbers with this locale's separators and compare them by value
+    pub locale: Option<NumberLocale>,
+    /// Compare IPv4, IPv6 and CIDR values by address, IPv4-mapped IPv6 included
+    pub normalize_ip_addresses: bool,
+    /// Columns holding phone numbers, compared by their digits ignoring punctuation and spacing
+    pub phone_columns: Vec<String>,
+    /// Calling code (`1`, `44`) added to phone numbers written without one
+    pub phone_country: Option<String>,
+    /// Compare plain and scientific notation (`1e3`, `1000.0`, `1.0E+3`) by value
+    pub normalize_scientific: bool,
+    /// Largest absolute difference between two numbers that still counts as equal
+    pub tolerance: Option<f64>,
+    /// Compare ISO 8601 datetimes as instants in time, whatever offset they are written in
+    pub timezone_normalize: bool,
+    /// Zone for datetimes written without an offset; without one they are compared as text
+    pub assume_timezone: Option<Timezone>,
+}
+
+/// Returns `true` when the two values of `column` are considered equal under the enabled rules.
+pub fn compare_values(column: &str, v1: &str, v2: &str, options: &CompareOptions) -> bool {
+    if v1 == v2 {
+        return true;
+    }
+
+    // Null markers are resolved first so no other rule can reinterpret them
+    match (is_null(v1, options), is_null(v2, options)) {
+        (true, true) => return true,
+        (true, false) | (false, true) => return false,
+        (false, false) => {}
+    }
+
+    if options.normalize_percentages
+        && (v1.trim_end().ends_with('%') || v2.trim_end().ends_with('%'))
+        && let (Some(n1), Some(n2)) = (parse_percentage(v1), parse_percentage(v2))
+    {
+        return approx_equal(n1, n2);
+    }
+
+    if options.normalize_integers
+        && let (Some(n1), Some(n2)) = (parse_integer(v1), parse_integer(v2))
+    {
+        return n1 == n2;
+    }
+
+    if options.normalize_uuids
+        && let (Some(u1), Some(u2)) = (parse_uuid(v1), parse_uuid(v2))
+    {
+        return u1 == u2;
+    }
+
+    // An integer only stands for an IPv4 address when the other side is written as one
+    if options.normalize_ip_addresses
+        && (v1.contains(['.', ':']) || v2.contains(['.', ':']))
+        && let (Some(ip1), Some(ip2)) = (parse_ip(v1), parse_ip(v2))
+    {
+        return ip1 == ip2;
+    }
+
+    if options.phone_columns.iter().any(|phone_column| phone_column == column)
+        && let (Some(p1), Some(p2)) = (
+            parse_phone(v1, options.phone_country.as_deref()),
+            parse_phone(v2, options.phone_country.as_deref()),
+        )
+    {
+        return p1 == p2;
+    }
+
+    // Machine-formatted numbers (`1000.5`) are accepted as well, for exports compared with
+    // their locale-formatted originals
+    let parse = |value: &str, locale| {
+        parse_locale_number(value, locale).or_else(|| value.trim().parse::<f64>().ok().filter(|n| n.is_finite()))
+    };
+    if let Some(locale) = options.locale
+        && let (Some(n1), Some(n2)) = (parse(v1, locale), parse(v2, locale))
+    {
+        return approx_equal(n1, n2);
+    }
+
+    if (options.normalize_scientific || options.tolerance.is_some())
+        && let (Some(d1), Some(d2)) = (parse_decimal(v1), parse_decimal(v2))
+    {
+        return d1 == d2 || options.tolerance.is_some_and(|tolerance| (d1.value - d2.value).abs() <= tolerance);
+    }
+
+    if options.timezone_normalize
+        && let (Some(t1), Some(t2)) = (
+            to_utc(v1, options.assume_timezone.as_ref()),
+            to_utc(v2, options.assume_timezone.as_ref()),
+        )
+    {
+        return t1 == t2;
+    }
+
+    false
+}
+
+/// Parse a number written with the locale's decimal separator and optional digit grouping.
+///
+/// Grouping must be regular (`1.234.567`, not `12.34`), only the integer part may be grouped,
+/// and at most one decimal separator may appear, so a value formatted for another locale
+/// (`1,000.50` under `de_DE`) is not a number here and falls back to string comparison.
+fn parse_locale_number(value: &str, locale: NumberLocale) -> Option<f64> {
+    let value = value.trim();
+    let (sign, magnitude) = match value.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (integer, fraction) = match magnitude.split_once(locale.decimal_separator()) {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (magnitude, None),
+    };
+
+    let groups: Vec<&str> = integer.split(|c| locale.is_group_separator(c)).collect();
+    let grouping_valid = groups.len() == 1
+        || (!groups[0].is_empty() && groups[0].len() <= 3 && groups[1..].iter().all(|group| group.len() == 3));
+    let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if !grouping_valid || !groups.iter().all(|group| all_digits(group)) {
+        return None;
+    }
+    let integer: String = groups.concat();
+    let fraction = fraction.unwrap_or("");
+    if (integer.is_empty() && fraction.is_empty()) || !all_digits(fraction) {
+        return None;
+    }
+
+    format!("{}{}.{}", sign, if integer.is_empty() { "0" } else { &integer }, fraction)
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+}
+
+/// Parse an IP address or CIDR block into its canonical address and prefix length.
+///
+/// IPv4-mapped IPv6 addresses (`::ffff:192.168.1.1`) become their IPv4 form, a CIDR block is
+/// reduced to its network address (`10.0.0.7/8` → `10.0.0.0/8`) and a decimal integer up to
+/// 2^32 - 1 is read as an IPv4 address. Anything `std::net` rejects returns `None`.
+fn parse_ip(value: &str) -> Option<(IpAddr, Option<u8>)> {
+    let value = value.trim();
+    let (address, prefix) = match value.split_once('/') {
+        Some((address, prefix)) if !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_digit()) => {
+            (address, Some(prefix.parse::<u8>().ok()?))
+        }
+        Some(_) => return None,
+        None => (value, None),
+    };
+    let address = match address.parse::<IpAddr>() {
+        Ok(address) => address,
+        Err(_) if prefix.is_none() && address.chars().all(|c| c.is_ascii_digit()) => {
+            IpAddr::V4(Ipv4Addr::from(address.parse::<u32>().ok()?))
+        }
+        Err(_) => return None,
+    };
+
+    let Some(prefix) = prefix else {
+        return Some((address.to_canonical(), None));
+    };
+    match address {
+        IpAddr::V4(v4) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            Some((IpAddr::V4(Ipv4Addr::from(u32::from(v4) & mask)), Some(prefix)))
+        }
+        IpAddr::V6(v6) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            let network = IpAddr::V6((u128::from(v6) & mask).into());
+            // ::ffff:10.0.0.0/104 is the mapped form of 10.0.0.0/8
+            match network.to_canonical() {
+                IpAddr::V4(v4) if prefix >= 96 => Some((IpAddr::V4(v4), Some(prefix - 96))),
+                _ => Some((network, Some(prefix))),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Calling codes for `--phone-country`.
+const CALLING_CODES: &[(&str, &str)] = &[
+    ("US", "1"),
+    ("CA", "1"),
+    ("GB", "44"),
+    ("IE", "353"),
+    ("FR", "33"),
+    ("DE", "49"),
+    ("ES", "34"),
+    ("IT", "39"),
+    ("NL", "31"),
+    ("BE", "32"),
+    ("CH", "41"),
+    ("AT", "43"),
+    ("SE", "46"),
+    ("NO", "47"),
+    ("DK", "45"),
+    ("FI", "358"),
+    ("PL", "48"),
+    ("PT", "351"),
+    ("AU", "61"),
+    ("NZ", "64"),
+    ("JP", "81"),
+    ("CN", "86"),
+    ("IN", "91"),
+    ("BR", "55"),
+    ("MX", "52"),
+    ("ZA", "27"),
+    ("MA", "212"),
+];
+
+/// Parse `--phone-country`: an ISO 3166 country code from the table above, or a calling code
+/// such as `+44`.
+pub fn parse_phone_country(value: &str) -> Result<String, String> {
+    let code = value.trim();
+    if let Some(digits) = code.strip_prefix('+')
+        && (1..=3).contains(&digits.len())
+        && digits.chars().all(|c| c.is_ascii_digit())
+    {
+        return Ok(digits.to_string());
+    }
+    CALLING_CODES
+        .iter()
+        .find(|(country, _)| country.eq_ignore_ascii_case(code))
+        .map(|(_, calling)| calling.to_string())
+        .ok_or_else(|| {
+            let known: Vec<&str> = CALLING_CODES.iter().map(|(country, _)| *country).collect();
+            format!("unknown country '{}': use one of {} or a calling code such as +44", value, known.join(", "))
+        })
+}
+
+/// Whether a value is shaped like a date: three digit groups split by one of `- / .`, one
+/// of them a four-digit year and the others a month and a day.
+fn is_date_shaped(value: &str) -> bool {
+    let Some(separator) = value.chars().find(|c| !c.is_ascii_digit()) else {
+        return false;
+    };
+    let parts: Vec<&str> = value.split(separator).collect();
+    let small = |part: &str| (1..=2).contains(&part.len()) && part.parse::<u32>().is_ok_and(|n| (1..=31).contains(&n));
+    "-/.".contains(separator)
+        && parts.len() == 3
+        && parts.iter().all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+        && ((parts[0].len() == 4 && small(parts[1]) && small(parts[2])) || (small(parts[0]) && small(parts[1]) && parts[2].len() == 4))
+}
+
+/// Reduce a phone number to its digits and extension.
+///
+/// Only digits, spaces and `+ ( ) - . /` may appear, followed by an optional extension
+/// (`x123`, `ext. 123`, `#123`), and there must be 7 to 15 digits. A leading `+` or `00`
+/// marks the calling code as present; otherwise a national trunk `0` is dropped and
+/// `country` is prepended unless the number already starts with it. A number without a
+/// `+` or `00` prefix must be grouped (`800 555 1234`, `(800) 555-1234`) and must not read
+/// as a plain number (`1234.5678`, `20240115`); dates (`2024-01-15`) and ZIP+4 codes
+/// (`12345-6789`) are never phone numbers.
+fn parse_phone(value: &str, country: Option<&str>) -> Option<(String, String)> {
+    let value = value.trim();
+    let lower = value.to_ascii_lowercase();
+    let (number, extension) = match lower.find("ext").or_else(|| lower.find(['x', '#'])) {
+        Some(at) => {
+            let extension = lower[at..].trim_start_matches(|c: char| c.is_ascii_alphabetic() || "#.: ".contains(c));
+            if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            (value[..at].trim_end(), extension.to_string())
+        }
+        None => (value, String::new()),
+    };
+
+    if !number.chars().all(|c| c.is_ascii_digit() || " +()-./".contains(c))
+        || number.chars().skip(1).any(|c| c == '+')
+    {
+        return None;
+    }
+    let is_zip4 = number.len() == 10 && number.char_indices().all(|(i, c)| if i == 5 { c == '-' } else { c.is_ascii_digit() });
+    let digits: String = number.chars().filter(char::is_ascii_digit).collect();
+    if is_zip4 || !(7..=15).contains(&digits.len()) {
+        return None;
+    }
+    let prefixed = number.starts_with('+') || number.starts_with("00");
+    let grouped = number.contains(|c: char| !c.is_ascii_digit());
+    if is_date_shaped(number) || (!prefixed && (!grouped || parse_decimal(number).is_some())) {
+        return None;
+    }
+
+    let digits = if number.starts_with('+') {
+        digits
+    } else if let Some(international) = digits.strip_prefix("00") {
+        international.to_string()
+    } else if let Some(code) = country {
+        let national = digits.strip_prefix('0').unwrap_or(&digits);
+        if national.starts_with(code) && national.len() > 10 {
+            national.to_string()
+        } else {
+            format!("{}{}", code, national)
+        }
+    } else {
+        digits
+    };
+    Some((digits, extension))
+}
+
+/// A decimal number reduced to `0.digits × 10^exponent`, so equal values compare equal exactly.
+#[derive(Debug)]
+struct Decimal {
+    negative: bool,
+    /// Significant digits without leading or trailing zeros; empty for zero
+    digits: String,
+    exponent: i64,
+    /// The nearest `f64`, used only for `--tolerance`
+    value: f64,
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        (self.negative, &self.digits, self.exponent) == (other.negative, &other.digits, other.exponent)
+    }
+}
+
+/// Parse a number in plain or scientific notation, `[+-]digits[.digits][e[+-]digits]`.
+///
+/// The digits are kept exactly, so numbers beyond `f64` precision are still told apart.
+/// Incomplete forms such as `1e` or `.`, and `inf` or `NaN`, return `None`.
+fn parse_decimal(value: &str) -> Option<Decimal> {
+    let value = value.trim();
+    let (negative, magnitude) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (mantissa, exponent) = match magnitude.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => {
+            let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            (mantissa, exponent.parse::<i64>().ok()?)
+        }
+        None => (magnitude, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if (integer.is_empty() && fraction.is_empty()) || !all_digits(integer) || !all_digits(fraction) {
+        return None;
+    }
+
+    let all = format!("{}{}", integer, fraction);
+    let leading = all.len() - all.trim_start_matches('0').len();
+    let digits = all.trim_matches('0').to_string();
+    let exponent = if digits.is_empty() {
+        0
+    } else {
+        exponent.checked_add(integer.len() as i64 - leading as i64)?
+    };
+    let value = value.parse::<f64>().ok()?;
+    Some(Decimal {
+        negative: negative && !digits.is_empty(),
+        digits,
+        exponent,
+        value,
+    })
+}
+
+/// Parse `--tolerance`, which must be a non-negative number.
+pub fn parse_tolerance(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(tolerance) if tolerance.is_finite() && tolerance >= 0.0 => Ok(tolerance),
+        _ => Err(format!("invalid tolerance '{}': expected a non-negative number", value)),
+    }
+}
+
+/// Reduce a UUID to its canonical 32 lowercase hex digits.
+///
+/// Accepts the hyphenated `8-4-4-4-12` form or 32 bare hex digits, optionally wrapped in
+/// braces; any other value returns `None`.
+fn parse_uuid(value: &str) -> Option<String> {
+    let value = value.trim();
+    let value = match value.strip_prefix('{') {
+        Some(inner) => inner.strip_suffix('}')?,
+        None => value,
+    };
+
+    let hyphenated = value.len() == 36
+        && value.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        });
+    let bare = value.len() == 32 && value.chars().all(|c| c.is_ascii_hexdigit());
+    if !hyphenated && !bare {
+        return None;
+    }
+
+    Some(value.chars().filter(|c| *c != '-').map(|c| c.to_ascii_lowercase()).collect())
+}
+
+/// Parse an integer written in decimal, hex, binary or octal notation.
+///
+/// Octal needs the explicit `0o` prefix: a zero-padded value such as `010` is decimal 10, so
+/// padded identifiers never compare equal to unrelated numbers. Values outside the `i64` range also return `None` so they fall back to string comparison.
+fn parse_integer(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (negative, magnitude) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+
+    let lower = magnitude.to_ascii_lowercase();
+    let (digits, radix) = if let Some(hex) = lower.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        (binary, 2)
+    } else if let Some(octal) = lower.strip_prefix("0o") {
+        (octal, 8)
+    } else {
+        (lower.as_str(), 10)
+    };
+
+    // from_str_radix accepts a sign of its own, which must not follow a prefix
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+
+    let parsed = i128::from_str_radix(digits, radix).ok()?;
+    i64::try_from(if negative { -parsed } else { parsed }).ok()
+}
+
+fn is_null(value: &str, options: &CompareOptions) -> bool {
+    let value = value.trim();
+    if value.is_empty() {
+        return options.null_equals_empty;
+    }
+    options.null_values.iter().any(|null| null.eq_ignore_ascii_case(value))
+}
+
+/// Parse a number, dividing by 100 when it carries a `%` suffix (`50%` → 0.5).
+fn parse_percentage(value: &str) -> Option<f64> {
+    let value = value.trim();
+    match value.strip_suffix('%') {
+        Some(number) => number.trim_end().parse::<f64>().ok().map(|n| n / 100.0),
+        None => value.parse::<f64>().ok(),
+    }
+    .filter(|n| n.is_finite())
+}
+
+/// Compare floats with a small relative epsilon to absorb rounding from unit conversions.
+fn approx_equal(a: f64, b: f64) -> bool {
+    const EPSILON: f64 = 1e-9;
+    (a - b).abs() <= EPSILON * a.abs().max(b.abs()).max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers_compare_by_value_with_explicit_prefixes() {
+        assert_eq!(parse_integer("0xFF"), Some(255));
+        assert_eq!(parse_integer("0b11111111"), Some(255));
+        assert_eq!(parse_integer("0o377"), Some(255));
+        assert_eq!(parse_integer("-0x10"), Some(-16));
+        assert_eq!(parse_integer("0x-10"), None);
+        assert_eq!(parse_integer("99999999999999999999"), None);
+    }
+
+    #[test]
+    fn leading_zeros_are_decimal() {
+        assert_eq!(parse_integer("010"), Some(10));
+        assert_eq!(parse_integer("0377"), Some(377));
+        assert_eq!(parse_integer("0089"), Some(89));
+        let options = CompareOptions { normalize_integers: true, ..CompareOptions::default() };
+        assert!(!compare_values("id", "010", "8", &options));
+        assert!(compare_values("id", "010", "10", &options));
+    }
+
+    fn phones(columns: &[&str]) -> CompareOptions {
+        CompareOptions { phone_columns: columns.iter().map(|c| c.to_string()).collect(), ..CompareOptions::default() }
+    }
+
+    #[test]
+    fn phone_numbers_compare_by_digits() {
+        let options = phones(&["phone"]);
+        assert!(compare_values("phone", "+1 (800) 555-1234", "+1.800.555.1234", &options));
+        assert!(compare_values("phone", "+1 800 555 1234 x12", "+18005551234 ext. 12", &options));
+        assert!(!compare_values("phone", "+1 800 555 1234 x12", "+1 800 555 1234 x13", &options));
+        assert!(compare_values("phone", "0044 20 7946 0958", "+44 20 7946 0958", &options));
+    }
+
+    #[test]
+    fn phone_numbers_only_in_named_columns() {
+        let options = phones(&["phone"]);
+        assert!(!compare_values("fax", "+1 (800) 555-1234", "+1.800.555.1234", &options));
+    }
+
+    #[test]
+    fn numbers_and_dates_are_not_phone_numbers() {
+        let options = phones(&["phone"]);
+        assert!(!compare_values("phone", "1234.5678", "12345.678", &options));
+        assert!(!compare_values("phone", "2024-01-15", "20240115", &options));
+        assert!(!compare_values("phone", "15/01/2024", "15.01.2024", &options));
+        assert!(!compare_values("phone", "8005551234", "800-555-1234", &options));
+        assert!(!compare_values("phone", "12345-6789", "123456789", &options));
+    }
+
+    #[test]
+    fn phone_country_adds_the_calling_code() {
+        let options = CompareOptions { phone_country: Some("1".to_string()), ..phones(&["phone"]) };
+        assert!(compare_values("phone", "800-555-1234", "+1 (800) 555-1234", &options));
+        assert!(compare_values("phone", "1 800 555 1234", "+1 (800) 555-1234", &options));
+        let options = CompareOptions { phone_country: Some("44".to_string()), ..phones(&["phone"]) };
+        assert!(compare_values("phone", "020 7946 0958", "+44 20 7946 0958", &options));
+    }
+
+    #[test]
+    fn phone_country_codes() {
+        assert_eq!(parse_phone_country("gb").as_deref(), Ok("44"));
+        assert_eq!(parse_phone_country("+353").as_deref(), Ok("353"));
+        assert!(parse_phone_country("+1234").is_err());
+        assert!(parse_phone_country("XX").is_err());
+    }
+}
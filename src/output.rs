@@ -0,0 +1,138 @@
+//! `--quiet` and `--quiet2`: how much a run prints, `--compress-output`: how output files are
+//! written, and `--rename-output-col`: what the fields of a difference are called.
+//!
+//! All three are read from the arguments once into [`Settings`], which is passed to the code
+//! that prints or writes output. Reports go through [`report!`] and warnings through
+//! [`warning!`], which check the settings' verbosity; a plain `println!` always prints.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Print reports to stdout and warnings to stderr
+    #[default]
+    Normal,
+    /// Print nothing but fatal errors
+    Quiet,
+    /// Print nothing at all; the exit code is the only result
+    Silent,
+}
+
+/// `println!` for a report, unless `--quiet` or `--check` silence it: `report!(settings, ...)`.
+macro_rules! report {
+    ($settings:expr) => {
+        if $settings.verbosity == $crate::output::Verbosity::Normal {
+            println!()
+        }
+    };
+    ($settings:expr, $($arg:tt)*) => {
+        if $settings.verbosity == $crate::output::Verbosity::Normal {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// `eprintln!` for a warning or progress message, silenced like [`report!`].
+macro_rules! warning {
+    ($settings:expr) => {
+        if $settings.verbosity == $crate::output::Verbosity::Normal {
+            eprintln!()
+        }
+    };
+    ($settings:expr, $($arg:tt)*) => {
+        if $settings.verbosity == $crate::output::Verbosity::Normal {
+            eprintln!($($arg)*)
+        }
+    };
+}
+
+pub(crate) use {report, warning};
+
+/// The fields of a difference, as named in the terminal table, CSV, json and yaml output.
+pub const DIFF_FIELDS: [&str; 4] = ["key", "column", "file1", "file2"];
+
+/// The terminal table's extra column under `--min-diff-pct`, which can be renamed like the
+/// [`DIFF_FIELDS`].
+pub const SIMILARITY_FIELD: &str = "similarity";
+
+/// How a run reports its results.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub verbosity: Verbosity,
+    /// `--compress-output`: the gzip level for output files
+    pub compression: Option<u32>,
+    /// `--rename-output-col`: `(field, name)` pairs for the renamed [`DIFF_FIELDS`] and
+    /// [`SIMILARITY_FIELD`]
+    pub field_renames: Vec<(String, String)>,
+}
+
+impl Settings {
+    /// The name given to a field with `--rename-output-col`, if any.
+    pub fn renamed_field(&self, field: &str) -> Option<&str> {
+        self.field_renames.iter().find(|(from, _)| from == field).map(|(_, to)| to.as_str())
+    }
+
+    /// The output name of one of the [`DIFF_FIELDS`] or the [`SIMILARITY_FIELD`].
+    pub fn field_name<'a>(&'a self, field: &'a str) -> &'a str {
+        self.renamed_field(field).unwrap_or(field)
+    }
+
+    /// The output names of all [`DIFF_FIELDS`], in order.
+    pub fn field_names(&self) -> [&str; 4] {
+        DIFF_FIELDS.map(|field| self.field_name(field))
+    }
+
+    /// Create an output file, compressed under `--compress-output`; see [`OutputFile::create`].
+    pub fn create_file(&self, path: &str) -> io::Result<(OutputFile, String)> {
+        OutputFile::create(path, self.compression)
+    }
+}
+
+/// A text output file, gzip-compressed under `--compress-output`.
+pub enum OutputFile {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl OutputFile {
+    /// Create the file at `path`, or at `path.gz` when compressing at a gzip level. Returns
+    /// the path written.
+    pub fn create(path: &str, compression: Option<u32>) -> io::Result<(OutputFile, String)> {
+        match compression {
+            None => Ok((OutputFile::Plain(BufWriter::new(File::create(path)?)), path.to_string())),
+            Some(level) => {
+                let path = if path.ends_with(".gz") { path.to_string() } else { format!("{}.gz", path) };
+                let file = BufWriter::new(File::create(&path)?);
+                Ok((OutputFile::Gzip(GzEncoder::new(file, Compression::new(level))), path))
+            }
+        }
+    }
+
+    /// Flush the file, writing the gzip trailer; errors here would otherwise be lost on drop.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(mut file) => file.flush(),
+            OutputFile::Gzip(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            OutputFile::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            OutputFile::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! The patch output format and the `apply` subcommand that replays it.
+//!
+//! A patch is a CSV file with the columns `op,key,column,old,new`, preceded by `#` comment
+//! lines describing where it came from. Operations:
+//!
+//! * `key` – a key column name, in composite-key order
+//! * `key-separator` – the `--key-sep` joining composite key values, when not `|`
+//! * `source-column` / `target-column` – the column layout of file1 and file2, in order
+//! * `change` – set `column` of the row identified by `key` from `old` to `new`
+//! * `delete` – the row `key` is removed; one line per column carrying the old value
+//! * `add` – the row `key` is added; one line per column carrying the new value
+//!
+//! Storing full rows for `delete` and `add` keeps the format symmetric, so swapping `old`
+//! and `new` (and the two column layouts) turns a patch into its own revert; `--revert-output`
+//! writes that revert alongside the diff.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Args as ClapArgs;
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+
+use flate2::read::GzDecoder;
+
+use crate::error::CsvDiffError;
+use crate::output::{Settings, report};
+use crate::{DiffRow, sha256, timestamp};
+
+/// Arguments for `csvdiff apply`.
+#[derive(ClapArgs, Clone, Debug)]
+pub struct ApplyArgs {
+    /// Patch file produced with --output-format patch
+    #[arg(long)]
+    pub patch: PathBuf,
+
+    /// CSV file to apply the patch to (normally the original file1)
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// Where to write the patched CSV
+    #[arg(long, required_unless_present = "dry_run")]
+    pub output: Option<PathBuf>,
+
+    /// Report what would change without writing any output
+    #[arg(long, default_value = "false")]
+    pub dry_run: bool,
+}
+
+/// Everything needed to turn the diff into a patch file.
+pub struct PatchSource<'a> {
+    pub files1: &'a [PathBuf],
+    pub files2: &'a [PathBuf],
+    pub key_columns: &'a [String],
+    pub key_separator: &'a str,
+    pub headers1: &'a [String],
+    pub headers2: &'a [String],
+    pub map1: &'a HashMap<String, StringRecord>,
+    pub map2: &'a HashMap<String, StringRecord>,
+}
+
+const DEFAULT_KEY_SEPARATOR: &str = "|";
+const COLUMN_NOT_IN_FILE1: &str = "[column not in file1]";
+const COLUMN_NOT_IN_FILE2: &str = "[column not in file2]";
+
+fn swap_placeholder(value: &str) -> String {
+    match value {
+        COLUMN_NOT_IN_FILE1 => COLUMN_NOT_IN_FILE2.to_string(),
+        COLUMN_NOT_IN_FILE2 => COLUMN_NOT_IN_FILE1.to_string(),
+        value => value.to_string(),
+    }
+}
+
+/// The same difference seen from file2's side.
+fn reverse_diff(diff: &DiffRow) -> DiffRow {
+    let column = match diff.column.as_str() {
+        "[missing in file2]" => "[missing in file1]".to_string(),
+        "[missing in file1]" => "[missing in file2]".to_string(),
+        column => column.to_string(),
+    };
+    DiffRow {
+        column,
+        file1: swap_placeholder(&diff.file2),
+        file2: swap_placeholder(&diff.file1),
+        file1_line: diff.file2_line,
+        file2_line: diff.file1_line,
+        ..diff.clone()
+    }
+}
+
+/// Write the collected differences as a patch file.
+///
+/// With `revert`, the roles of the two files are swapped, producing a patch that turns the
+/// output of the forward patch back into file1.
+pub fn write_patch(path: &str, source: &PatchSource, diffs: &[DiffRow], revert: bool, settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let (mut file, path) = settings.create_file(path)?;
+    writeln!(file, "# csvdiff patch")?;
+    if revert {
+        writeln!(file, "# revert of the file1 → file2 diff: apply to the patched file to restore file1")?;
+    }
+    writeln!(file, "# generated: {}", timestamp::now())?;
+    for (label, paths) in [("file1", source.files1), ("file2", source.files2)] {
+        for path in paths {
+            writeln!(file, "# {}: {} (sha256 {})", label, path.display(), sha256::file_hex(path)?)?;
+        }
+    }
+
+    let (swapped, reversed): (PatchSource, Vec<DiffRow>);
+    let (source, diffs) = if revert {
+        reversed = diffs.iter().map(reverse_diff).collect();
+        swapped = PatchSource {
+            files1: source.files2,
+            files2: source.files1,
+            key_columns: source.key_columns,
+            key_separator: source.key_separator,
+            headers1: source.headers2,
+            headers2: source.headers1,
+            map1: source.map2,
+            map2: source.map1,
+        };
+        (&swapped, reversed.as_slice())
+    } else {
+        (source, diffs)
+    };
+
+    let mut writer = WriterBuilder::new().from_writer(file);
+    writer.write_record(["op", "key", "column", "old", "new"])?;
+
+    for key in source.key_columns {
+        writer.write_record(["key", "", key, "", ""])?;
+    }
+    if source.key_separator != DEFAULT_KEY_SEPARATOR {
+        writer.write_record(["key-separator", "", source.key_separator, "", ""])?;
+    }
+    for column in source.headers1 {
+        writer.write_record(["source-column", "", column, "", ""])?;
+    }
+    for column in source.headers2 {
+        writer.write_record(["target-column", "", column, "", ""])?;
+    }
+
+    for diff in diffs {
+        match diff.column.as_str() {
+            "[missing in file2]" => {
+                if let Some(record) = source.map1.get(&diff.key) {
+                    for (column, value) in source.headers1.iter().zip(record.iter()) {
+                        writer.write_record(["delete", &diff.key, column, value, ""])?;
+                    }
+                }
+            }
+            "[missing in file1]" => {
+                if let Some(record) = source.map2.get(&diff.key) {
+                    for (column, value) in source.headers2.iter().zip(record.iter()) {
+                        writer.write_record(["add", &diff.key, column, "", value])?;
+                    }
+                }
+            }
+            column => {
+                // A column that only exists in file1 disappears with the target layout
+                if diff.file2 == COLUMN_NOT_IN_FILE2 {
+                    continue;
+                }
+                let old = if diff.file1 == COLUMN_NOT_IN_FILE1 { "" } else { diff.file1.as_str() };
+                // Fuzzy matches are displayed as "key1 ≈ key2"; the input row is found by key1
+                let key = diff.key.split(" ≈ ").next().unwrap_or(&diff.key);
+                writer.write_record(["change", key, column, old, &diff.file2])?;
+            }
+        }
+    }
+
+    writer.into_inner()?.finish()?;
+    if revert {
+        report!(settings, "🩹 Revert patch written: {}", path);
+    } else {
+        report!(settings, "🩹 Patch written: {}", path);
+    }
+    Ok(())
+}
+
+/// A patch file loaded into memory.
+struct Patch {
+    key_columns: Vec<String>,
+    key_separator: String,
+    source_columns: Vec<String>,
+    target_columns: Vec<String>,
+    /// (key, column) → (old, new)
+    changes: HashMap<(String, String), (String, String)>,
+    deletes: HashSet<String>,
+    /// Added rows in patch order, each as column → value
+    adds: Vec<(String, HashMap<String, String>)>,
+}
+
+fn read_patch(path: &Path) -> Result<Patch, Box<dyn Error>> {
+    // Patches written with --compress-output are read as they are
+    let file = BufReader::new(File::open(path)?);
+    let input: Box<dyn Read> = if path.extension().is_some_and(|ext| ext == "gz") { Box::new(GzDecoder::new(file)) } else { Box::new(file) };
+    let mut rdr = ReaderBuilder::new().comment(Some(b'#')).from_reader(input);
+    let mut patch = Patch {
+        key_columns: Vec::new(),
+        key_separator: DEFAULT_KEY_SEPARATOR.to_string(),
+        source_columns: Vec::new(),
+        target_columns: Vec::new(),
+        changes: HashMap::new(),
+        deletes: HashSet::new(),
+        adds: Vec::new(),
+    };
+
+    for (line, result) in rdr.records().enumerate() {
+        let record = result?;
+        let field = |i: usize| record.get(i).unwrap_or("").to_string();
+        let (op, key, column, old, new) = (field(0), field(1), field(2), field(3), field(4));
+        match op.as_str() {
+            "key" => patch.key_columns.push(column),
+            "key-separator" if column.is_empty() => return Err(format!("Empty key separator on line {}", line + 2).into()),
+            "key-separator" => patch.key_separator = column,
+            "source-column" => patch.source_columns.push(column),
+            "target-column" => patch.target_columns.push(column),
+            "change" => {
+                patch.changes.insert((key, column), (old, new));
+            }
+            "delete" => {
+                patch.deletes.insert(key);
+            }
+            "add" => match patch.adds.last_mut() {
+                Some((last_key, values)) if *last_key == key => {
+                    values.insert(column, new);
+                }
+                _ => patch.adds.push((key, HashMap::from([(column, new)]))),
+            },
+            other => return Err(format!("Unknown patch operation '{}' on line {}", other, line + 2).into()),
+        }
+    }
+
+    if patch.key_columns.is_empty() || patch.target_columns.is_empty() {
+        return Err(format!("{} is not a csvdiff patch (missing key or column definitions)", path.display()).into());
+    }
+    Ok(patch)
+}
+
+/// Run `csvdiff apply`.
+pub fn run_apply(args: &ApplyArgs) -> Result<(), Box<dyn Error>> {
+    let patch = read_patch(&args.patch)?;
+
+    let mut rdr = ReaderBuilder::new().from_path(&args.input)?;
+    let input_headers: Vec<String> = rdr.headers()?.iter().map(String::from).collect();
+
+    // Columns are resolved by position so patches made with --rename still line up
+    let source_columns = if patch.source_columns.is_empty() { input_headers.clone() } else { patch.source_columns.clone() };
+    if source_columns.len() != input_headers.len() {
+        return Err(format!(
+            "{} has {} columns but the patch expects {} ({})",
+            args.input.display(),
+            input_headers.len(),
+            source_columns.len(),
+            source_columns.join(", ")
+        )
+        .into());
+    }
+    let source_index: HashMap<&str, usize> = source_columns.iter().enumerate().map(|(i, c)| (c.as_str(), i)).collect();
+
+    let key_indexes = patch
+        .key_columns
+        .iter()
+        .map(|key| {
+            source_index
+                .get(key.as_str())
+                .copied()
+                .ok_or_else(|| CsvDiffError::KeyColumnNotFound { file: args.input.display().to_string(), column: key.clone(), suggestions: Vec::new() })
+        })
+        .collect::<Result<Vec<usize>, CsvDiffError>>()?;
+
+    let mut output_rows: Vec<Vec<String>> = Vec::new();
+    let mut applied_changes = 0;
+    let mut applied_deletes = 0;
+    let mut conflicts = 0;
+
+    for result in rdr.records() {
+        let record = result?;
+        let key = key_indexes.iter().map(|&i| record.get(i).unwrap_or("")).collect::<Vec<_>>().join(&patch.key_separator);
+
+        if patch.deletes.contains(&key) {
+            applied_deletes += 1;
+            if args.dry_run {
+                println!("delete {}", key);
+            }
+            continue;
+        }
+
+        let mut row = Vec::with_capacity(patch.target_columns.len());
+        for column in &patch.target_columns {
+            let current = source_index.get(column.as_str()).and_then(|&i| record.get(i)).unwrap_or("");
+            match patch.changes.get(&(key.clone(), column.clone())) {
+                Some((old, new)) => {
+                    if current != old {
+                        conflicts += 1;
+                        eprintln!("⚠️  Conflict at {}.{}: expected '{}', found '{}'", key, column, old, current);
+                    }
+                    if args.dry_run {
+                        println!("change {}.{}: '{}' → '{}'", key, column, current, new);
+                    }
+                    applied_changes += 1;
+                    row.push(new.clone());
+                }
+                None => row.push(current.to_string()),
+            }
+        }
+        output_rows.push(row);
+    }
+
+    for (key, values) in &patch.adds {
+        if args.dry_run {
+            println!("add {}", key);
+        }
+        output_rows.push(
+            patch
+                .target_columns
+                .iter()
+                .map(|column| values.get(column).cloned().unwrap_or_default())
+                .collect(),
+        );
+    }
+
+    let summary = format!(
+        "{} values changed, {} rows deleted, {} rows added",
+        applied_changes,
+        applied_deletes,
+        patch.adds.len()
+    );
+
+    if args.dry_run {
+        println!("\n🔎 Dry run: {} (nothing written)", summary);
+    } else if let Some(output) = &args.output {
+        let mut writer = WriterBuilder::new().from_path(output)?;
+        writer.write_record(&patch.target_columns)?;
+        for row in &output_rows {
+            writer.write_record(row)?;
+        }
+        writer.flush()?;
+        println!("✅ Patched file written: {} ({})", output.display(), summary);
+    }
+
+    if conflicts > 0 {
+        eprintln!("⚠️  {} values did not match the patch's expected old value", conflicts);
+    }
+
+    Ok(())
+}
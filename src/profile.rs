@@ -0,0 +1,133 @@
+//! `csvdiff profile`: named comparisons saved to `~/.csvdiff/profiles.toml`.
+//!
+//! Each profile is a TOML table in the `--config` format:
+//!
+//! ```toml
+//! [daily-recon]
+//! file1 = "yesterday.csv"
+//! file2 = "today.csv"
+//! key = ["id"]
+//! ```
+
+use std::error::Error;
+use std::path::PathBuf;
+
+use clap::error::ErrorKind;
+use clap::{Args as ClapArgs, CommandFactory, Parser, Subcommand};
+
+use crate::config::{self, Document};
+use crate::{Args, parse_args};
+
+/// Arguments for `csvdiff profile`.
+#[derive(ClapArgs, Clone, Debug)]
+pub struct ProfileArgs {
+    #[command(subcommand)]
+    action: ProfileAction,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum ProfileAction {
+    /// Save the given comparison options under a name
+    Save {
+        /// Profile name
+        #[arg(long)]
+        name: String,
+
+        /// Comparison options to save, e.g. --file1 a.csv --file2 b.csv -k id
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        options: Vec<String>,
+    },
+    /// Run a saved profile; options given here override the saved ones
+    Run {
+        /// Profile name
+        name: String,
+
+        /// Options that replace the saved values for this run
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        overrides: Vec<String>,
+    },
+    /// List saved profiles
+    List,
+}
+
+fn profiles_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(config::data_dir()?.join("profiles.toml"))
+}
+
+fn load_profiles() -> Result<(PathBuf, Document), Box<dyn Error>> {
+    let path = profiles_path()?;
+    let document = if path.exists() { config::load(&path)? } else { Document::default() };
+    Ok((path, document))
+}
+
+/// Run `csvdiff profile`.
+pub fn run(args: &ProfileArgs) -> Result<(), Box<dyn Error>> {
+    match &args.action {
+        ProfileAction::Save { name, options } => save(name, options),
+        ProfileAction::Run { name, overrides } => run_profile(name, overrides),
+        ProfileAction::List => list(),
+    }
+}
+
+fn save(name: &str, options: &[String]) -> Result<(), Box<dyn Error>> {
+    let argv: Vec<String> = std::iter::once("csvdiff".to_string()).chain(options.iter().cloned()).collect();
+    // Validate the options now; the files may still be supplied when the profile is run
+    if let Err(e) = Args::try_parse_from(&argv)
+        && e.kind() != ErrorKind::MissingRequiredArgument
+    {
+        e.exit();
+    }
+
+    let table = config::from_args(options, &Args::command());
+    if table.is_empty() {
+        return Err("No options to save (pass them after the profile name, e.g. --file1 a.csv -k id)".into());
+    }
+
+    let (path, mut document) = load_profiles()?;
+    match document.tables.iter_mut().find(|(existing, _)| existing == name) {
+        Some((_, existing)) => *existing = table,
+        None => document.tables.push((name.to_string(), table)),
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, document.to_string())?;
+    println!("✅ Profile '{}' saved to {}", name, path.display());
+    Ok(())
+}
+
+fn run_profile(name: &str, overrides: &[String]) -> Result<(), Box<dyn Error>> {
+    let (path, document) = load_profiles()?;
+    let table = document
+        .table(name)
+        .ok_or_else(|| format!("No profile named '{}' in {} (see `csvdiff profile list`)", name, path.display()))?;
+
+    let command = Args::command();
+    let given = config::given_options(overrides, &command);
+    let saved = config::to_args(table, &command, &given).map_err(|e| format!("Profile '{}': {}", name, e))?;
+
+    let mut argv: Vec<String> = std::iter::once("csvdiff".to_string())
+        .chain(saved)
+        .chain(overrides.iter().cloned())
+        .collect();
+    // Fingerprints of a profile's runs are stored under the profile name
+    if !given.contains("fingerprint-name") {
+        argv.push(format!("--fingerprint-name={}", name));
+    }
+    crate::run_diff(parse_args(argv))
+}
+
+fn list() -> Result<(), Box<dyn Error>> {
+    let (path, document) = load_profiles()?;
+    if document.tables.is_empty() {
+        println!("No saved profiles ({})", path.display());
+        return Ok(());
+    }
+    println!("📋 Profiles in {}:", path.display());
+    for (name, table) in &document.tables {
+        let options: Vec<String> = table.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+        println!("   • {}: {}", name, options.join(" "));
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+//! Key-based row sampling for quick approximate comparisons.
+//!
+//! Rows are selected by hashing their key together with a seed, so the same keys are picked
+//! in both files and a given seed always produces the same sample.
+
+use std::collections::{BinaryHeap, HashMap};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use csv::StringRecord;
+
+/// How much of each file `--sample` keeps.
+#[derive(Debug, Clone, Copy)]
+pub enum SampleSize {
+    /// Keep each key with this probability (`--sample 1%`)
+    Fraction(f64),
+    /// Keep roughly this many keys (`--sample 50000`)
+    Count(usize),
+}
+
+/// Parse `--sample` values such as `1%`, `0.5%` or `50000`.
+pub fn parse_sample_size(value: &str) -> Result<SampleSize, String> {
+    match value.trim().strip_suffix('%') {
+        Some(percent) => {
+            let percent: f64 = percent
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid sample percentage '{}'", value))?;
+            if !(percent > 0.0 && percent <= 100.0) {
+                return Err(format!("sample percentage must be between 0 and 100, got '{}'", value));
+            }
+            Ok(SampleSize::Fraction(percent / 100.0))
+        }
+        None => match value.trim().parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!("invalid sample size '{}': expected a row count or a percentage like 1%", value)),
+            Ok(count) => Ok(SampleSize::Count(count)),
+        },
+    }
+}
+
+pub struct Sampler {
+    pub size: SampleSize,
+    pub seed: u64,
+}
+
+impl Sampler {
+    /// Create a sampler, picking a time-based seed when none is given.
+    pub fn new(size: SampleSize, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default()
+                ^ u64::from(std::process::id())
+        });
+        Sampler { size, seed }
+    }
+
+    fn key_hash(&self, key: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.seed.hash(&mut hasher);
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether fraction-based sampling keeps this key (count-based sampling uses a reservoir).
+    pub fn accepts(&self, key: &str) -> bool {
+        match self.size {
+            SampleSize::Fraction(p) => (self.key_hash(key) as f64) < p * u64::MAX as f64,
+            SampleSize::Count(_) => true,
+        }
+    }
+
+    /// A bounded candidate set for count-based sampling, `None` for fraction-based sampling.
+    pub fn reservoir(&self) -> Option<KeyReservoir<'_>> {
+        match self.size {
+            SampleSize::Count(count) => Some(KeyReservoir {
+                sampler: self,
+                count,
+                heap: BinaryHeap::new(),
+            }),
+            SampleSize::Fraction(_) => None,
+        }
+    }
+
+    /// Trim both files to the same keys for count-based sampling.
+    ///
+    /// Each file keeps the keys with the smallest hashes; cutting both at the lower of the two
+    /// boundaries ensures a key kept in one file is never dropped from the other.
+    pub fn retain(&self, map1: &mut HashMap<String, StringRecord>, map2: &mut HashMap<String, StringRecord>) {
+        let SampleSize::Count(count) = self.size else {
+            return;
+        };
+        let boundary = |map: &HashMap<String, StringRecord>| -> u64 {
+            if map.len() < count {
+                return u64::MAX;
+            }
+            map.keys().map(|k| self.key_hash(k)).max().unwrap_or(u64::MAX)
+        };
+        let threshold = boundary(map1).min(boundary(map2));
+        map1.retain(|k, _| self.key_hash(k) <= threshold);
+        map2.retain(|k, _| self.key_hash(k) <= threshold);
+    }
+}
+
+/// Keeps the `count` keys with the smallest hashes seen so far.
+pub struct KeyReservoir<'a> {
+    sampler: &'a Sampler,
+    count: usize,
+    heap: BinaryHeap<(u64, String)>,
+}
+
+impl KeyReservoir<'_> {
+    /// Offer a new key, returning the key that no longer fits (possibly the offered one).
+    pub fn offer(&mut self, key: &str) -> Option<String> {
+        let hash = self.sampler.key_hash(key);
+        if self.heap.len() < self.count {
+            self.heap.push((hash, key.to_string()));
+            return None;
+        }
+        match self.heap.peek() {
+            Some((largest, _)) if hash < *largest => {
+                let evicted = self.heap.pop().map(|(_, k)| k);
+                self.heap.push((hash, key.to_string()));
+                evicted
+            }
+            _ => Some(key.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(keys: impl Iterator<Item = usize>) -> HashMap<String, StringRecord> {
+        keys.map(|key| (key.to_string(), StringRecord::new())).collect()
+    }
+
+    #[test]
+    fn sizes_are_percentages_or_counts() {
+        assert!(matches!(parse_sample_size("0.5%"), Ok(SampleSize::Fraction(f)) if (f - 0.005).abs() < 1e-12));
+        assert!(matches!(parse_sample_size(" 50000 "), Ok(SampleSize::Count(50_000))));
+        for invalid in ["0%", "101%", "x%", "0", "-5", "ten"] {
+            assert!(parse_sample_size(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn a_seed_always_picks_the_same_keys() {
+        let sampler = Sampler::new(SampleSize::Fraction(0.3), Some(42));
+        let picked: Vec<usize> = (0..1_000).filter(|key| sampler.accepts(&key.to_string())).collect();
+        let again = Sampler::new(SampleSize::Fraction(0.3), Some(42));
+        assert!(picked.iter().all(|key| again.accepts(&key.to_string())));
+        assert!((200..400).contains(&picked.len()), "{} keys picked", picked.len());
+        let other = Sampler::new(SampleSize::Fraction(0.3), Some(43));
+        assert_ne!(picked, (0..1_000).filter(|key| other.accepts(&key.to_string())).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn the_reservoir_keeps_the_smallest_hashes() {
+        let sampler = Sampler::new(SampleSize::Count(10), Some(7));
+        let mut reservoir = sampler.reservoir().unwrap();
+        let evicted: Vec<String> = (0..100).filter_map(|key| reservoir.offer(&key.to_string())).collect();
+        assert_eq!(evicted.len(), 90);
+        let mut hashes: Vec<u64> = (0..100).map(|key| sampler.key_hash(&key.to_string())).collect();
+        hashes.sort_unstable();
+        let kept: Vec<u64> = reservoir.heap.into_sorted_vec().into_iter().map(|(hash, _)| hash).collect();
+        assert_eq!(kept, hashes[..10]);
+        assert!(Sampler::new(SampleSize::Fraction(0.1), Some(7)).reservoir().is_none());
+    }
+
+    #[test]
+    fn both_files_keep_the_same_keys() {
+        let sampler = Sampler::new(SampleSize::Count(20), Some(1));
+        // Each file is read through its own reservoir, keeping its 20 smallest hashes
+        let read = |keys: std::ops::Range<usize>| {
+            let mut map = rows(keys);
+            let mut reservoir = sampler.reservoir().unwrap();
+            let evicted: Vec<String> = map.keys().filter_map(|key| reservoir.offer(key)).collect();
+            map.retain(|key, _| !evicted.contains(key));
+            map
+        };
+        let (mut map1, mut map2) = (read(0..200), read(100..300));
+        sampler.retain(&mut map1, &mut map2);
+        for key in (100..200).map(|key| key.to_string()) {
+            assert_eq!(map1.contains_key(&key), map2.contains_key(&key), "key {}", key);
+        }
+        assert!(map1.len() <= 20 && map2.len() <= 20);
+    }
+}
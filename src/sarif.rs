@@ -0,0 +1,106 @@
+//! The SARIF output format: differences as SARIF 2.1.0 results for code scanning tools.
+//!
+//! GitHub code scanning, Azure DevOps and the VS Code SARIF viewer show each result at the
+//! line it points to. Changed values and added rows point into file2, removed rows into file1;
+//! line numbers are only given for single-file inputs, as parts of a concatenated input each
+//! number their lines from 1.
+
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::DiffRow;
+use crate::json::Json;
+use crate::output::{Settings, report};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const VALUE_CHANGED: &str = "csv-value-changed";
+const ROW_MISSING: &str = "row-missing";
+
+/// Rule ids with their descriptions, in `ruleIndex` order.
+const RULES: [(&str, &str); 2] = [
+    (VALUE_CHANGED, "A value differs between the two files"),
+    (ROW_MISSING, "A row is present in only one of the two files"),
+];
+
+fn string(value: &str) -> Json {
+    Json::String(value.to_string())
+}
+
+fn object(entries: Vec<(&str, Json)>) -> Json {
+    Json::Object(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+/// A relative URI reference for a local path.
+fn uri(path: &Path) -> String {
+    path.display().to_string().replace('\\', "/").replace('%', "%25").replace(' ', "%20")
+}
+
+/// Where a result points: the file holding the value, and its line when that is known.
+fn location(paths: &[PathBuf], line: Option<u64>) -> Option<Json> {
+    let first = paths.first()?;
+    let mut physical = vec![("artifactLocation", object(vec![("uri", string(&uri(first)))]))];
+    if let (1, Some(line)) = (paths.len(), line) {
+        physical.push(("region", object(vec![("startLine", Json::Number(line.to_string()))])));
+    }
+    Some(object(vec![("physicalLocation", object(physical))]))
+}
+
+fn result(diff: &DiffRow, files1: &[PathBuf], files2: &[PathBuf]) -> Json {
+    let (rule, message, location) = match diff.column.as_str() {
+        "[missing in file2]" => (
+            ROW_MISSING,
+            format!("Row '{}' is missing from file2: {}", diff.key, diff.file1),
+            location(files1, diff.file1_line),
+        ),
+        "[missing in file1]" => (
+            ROW_MISSING,
+            format!("Row '{}' is missing from file1: {}", diff.key, diff.file2),
+            location(files2, diff.file2_line),
+        ),
+        column => (
+            VALUE_CHANGED,
+            format!("Row '{}', column '{}': '{}' changed to '{}'", diff.key, column, diff.file1, diff.file2),
+            location(files2, diff.file2_line),
+        ),
+    };
+    let rule_index = RULES.iter().position(|(id, _)| *id == rule).unwrap_or(0);
+    object(vec![
+        ("ruleId", string(rule)),
+        ("ruleIndex", Json::Number(rule_index.to_string())),
+        ("level", string("warning")),
+        ("message", object(vec![("text", string(&message))])),
+        ("locations", Json::Array(location.into_iter().collect())),
+    ])
+}
+
+/// Write the differences as a SARIF log with a single run.
+pub fn write_sarif(path: &str, files1: &[PathBuf], files2: &[PathBuf], diffs: &[DiffRow], settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let rules = RULES
+        .iter()
+        .map(|(id, description)| object(vec![("id", string(id)), ("shortDescription", object(vec![("text", string(description))]))]))
+        .collect();
+    let driver = object(vec![
+        ("name", string(env!("CARGO_PKG_NAME"))),
+        ("version", string(env!("CARGO_PKG_VERSION"))),
+        ("informationUri", string(env!("CARGO_PKG_REPOSITORY"))),
+        ("rules", Json::Array(rules)),
+    ]);
+    let artifacts = files1
+        .iter()
+        .chain(files2)
+        .map(|path| object(vec![("location", object(vec![("uri", string(&uri(path)))]))]))
+        .collect();
+    let run = object(vec![
+        ("tool", object(vec![("driver", driver)])),
+        ("artifacts", Json::Array(artifacts)),
+        ("results", Json::Array(diffs.iter().map(|diff| result(diff, files1, files2)).collect())),
+    ]);
+    let log = object(vec![("$schema", string(SCHEMA)), ("version", string("2.1.0")), ("runs", Json::Array(vec![run]))]);
+
+    let (mut out, path) = settings.create_file(path)?;
+    writeln!(out, "{}", log)?;
+    out.finish()?;
+    report!(settings, "🧾 SARIF log written: {}", path);
+    Ok(())
+}
@@ -0,0 +1,172 @@
+//! `--enable-telemetry`: opt-in usage statistics, sent after each comparison.
+//!
+//! Telemetry is off unless `--enable-telemetry` is given or `~/.csvdiff/telemetry.toml` sets
+//! `enabled = true`. The endpoint comes from the same file:
+//!
+//! ```toml
+//! enabled = true
+//! endpoint = "http://telemetry.example.com/csvdiff"
+//! ```
+//!
+//! Each run POSTs one JSON object with the csvdiff version, the number of rows read, the
+//! number of differences, the `--output-format` used (or `null`) and the duration in seconds.
+//! File contents, paths, column names and keys are never sent. The build bundles no TLS
+//! client, so only `http://` endpoints are supported; a failed send prints a warning and
+//! never fails the run.
+
+use std::error::Error;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::{self, Value};
+use crate::json::Json;
+use crate::output::{self, warning};
+
+/// How long connecting to, writing to and reading from the endpoint may each take
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What one run reports.
+pub struct Usage {
+    /// Data rows read from both files
+    pub rows: usize,
+    pub differences: usize,
+    /// The `--output-format` value, if any
+    pub output_format: Option<String>,
+    pub duration: Duration,
+}
+
+/// The settings in `telemetry.toml`.
+#[derive(Default)]
+struct Settings {
+    enabled: bool,
+    endpoint: Option<String>,
+}
+
+/// The settings file, when the home directory is known and the file exists.
+fn settings_path() -> Option<PathBuf> {
+    config::data_dir().ok().map(|dir| dir.join("telemetry.toml")).filter(|path| path.exists())
+}
+
+fn load_settings() -> Result<Settings, Box<dyn Error>> {
+    let Some(path) = settings_path() else {
+        return Ok(Settings::default());
+    };
+    let mut settings = Settings::default();
+    for (key, value) in config::load(&path)?.root {
+        match (key.as_str(), value) {
+            ("enabled", Value::Bool(enabled)) => settings.enabled = enabled,
+            ("endpoint", Value::String(endpoint)) => settings.endpoint = Some(endpoint),
+            (key, _) => return Err(format!("{}: unknown or invalid setting '{}'", path.display(), key).into()),
+        }
+    }
+    Ok(settings)
+}
+
+/// The JSON object sent for a run.
+pub fn payload(usage: &Usage) -> Json {
+    Json::Object(vec![
+        ("version".to_string(), Json::String(env!("CARGO_PKG_VERSION").to_string())),
+        ("rows_processed".to_string(), Json::Number(usage.rows.to_string())),
+        ("differences".to_string(), Json::Number(usage.differences.to_string())),
+        ("output_format".to_string(), usage.output_format.clone().map_or(Json::Null, Json::String)),
+        ("duration_seconds".to_string(), Json::Number(format!("{:.3}", usage.duration.as_secs_f64()))),
+    ])
+}
+
+/// Split an `http://host[:port][/path]` URL into its address and path.
+fn parse_endpoint(endpoint: &str) -> Result<(String, String), String> {
+    let rest = endpoint.strip_prefix("http://").ok_or_else(|| {
+        if endpoint.starts_with("https://") {
+            format!("HTTPS endpoints are not supported by this build of csvdiff: {}", endpoint)
+        } else {
+            format!("Telemetry endpoint must be an http:// URL: {}", endpoint)
+        }
+    })?;
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if host.is_empty() {
+        return Err(format!("Telemetry endpoint has no host: {}", endpoint));
+    }
+    let path = if path.is_empty() { "/" } else { path };
+    Ok((host.to_string(), path.to_string()))
+}
+
+/// POST the payload and check for a 2xx response.
+fn send(endpoint: &str, host: &str, path: &str, body: &str) -> Result<(), Box<dyn Error>> {
+    let address = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+    let socket = address.to_socket_addrs()?.next().ok_or_else(|| format!("Cannot resolve {}", host))?;
+    let mut stream = TcpStream::connect_timeout(&socket, TIMEOUT)?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: csvdiff/{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        env!("CARGO_PKG_VERSION"),
+        body.len(),
+        body
+    )?;
+    let mut status = [0u8; 12];
+    stream.read_exact(&mut status)?;
+    // "HTTP/1.1 204"
+    match &status[9..10] {
+        b"2" => Ok(()),
+        _ => Err(format!("{} answered {}", endpoint, String::from_utf8_lossy(&status[9..]).trim()).into()),
+    }
+}
+
+/// Send the run's usage statistics when telemetry is enabled. Problems are warnings only,
+/// and only for a user who opted in: without the flag, a missing or unreadable settings file
+/// means telemetry is off.
+pub fn report(enabled_by_flag: bool, usage: &Usage, output_settings: &output::Settings) {
+    let settings = match load_settings() {
+        Ok(settings) => settings,
+        Err(e) if enabled_by_flag => {
+            warning!(output_settings, "⚠️  Telemetry disabled: {}", e);
+            return;
+        }
+        Err(_) => return,
+    };
+    if !enabled_by_flag && !settings.enabled {
+        return;
+    }
+    let Some(endpoint) = settings.endpoint else {
+        warning!(output_settings, "⚠️  Telemetry is enabled but no endpoint is set; add endpoint = \"http://...\" to ~/.csvdiff/telemetry.toml");
+        return;
+    };
+    let (host, path) = match parse_endpoint(&endpoint) {
+        Ok(parts) => parts,
+        Err(e) => {
+            warning!(output_settings, "⚠️  Telemetry disabled: {}", e);
+            return;
+        }
+    };
+    let body = payload(usage).to_string();
+    warning!(output_settings, "📡 Telemetry: sending {} to {} (no file contents or paths)", body, endpoint);
+    if let Err(e) = send(&endpoint, &host, &path, &body) {
+        warning!(output_settings, "⚠️  Failed to send telemetry: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoints_split_into_host_and_path() {
+        assert_eq!(parse_endpoint("http://example.com").unwrap(), ("example.com".to_string(), "/".to_string()));
+        assert_eq!(parse_endpoint("http://example.com:8080/csvdiff/v1").unwrap(), ("example.com:8080".to_string(), "/csvdiff/v1".to_string()));
+        assert!(parse_endpoint("https://example.com").unwrap_err().contains("HTTPS"));
+        assert!(parse_endpoint("ftp://example.com").is_err());
+        assert!(parse_endpoint("http:///path").is_err());
+    }
+
+    #[test]
+    fn payload_holds_counts_only() {
+        let usage = Usage { rows: 10, differences: 2, output_format: None, duration: Duration::from_millis(1500) };
+        let payload = payload(&usage).to_string();
+        assert!(payload.contains(r#""rows_processed":10,"differences":2,"output_format":null,"duration_seconds":1.500"#), "{}", payload);
+    }
+}
@@ -0,0 +1,90 @@
+//! `--infer-types`: the type of each column, guessed from its values.
+//!
+//! A column gets the narrowest type every non-empty value fits: integer, float, boolean,
+//! date (`YYYY-MM-DD`) or datetime (ISO 8601), and string otherwise. A column with no values
+//! at all is a string.
+
+use csv::StringRecord;
+
+use crate::datetime;
+use crate::timestamp::days_in_month;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Boolean,
+    Date,
+    Datetime,
+    String,
+}
+
+/// Candidate types, narrowest first; an integer column also fits float.
+const CANDIDATES: [ColumnType; 5] = [ColumnType::Integer, ColumnType::Float, ColumnType::Boolean, ColumnType::Date, ColumnType::Datetime];
+
+impl ColumnType {
+    pub fn label(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Date => "date",
+            ColumnType::Datetime => "datetime",
+            ColumnType::String => "string",
+        }
+    }
+
+    fn fits(self, value: &str) -> bool {
+        match self {
+            ColumnType::Integer => value.parse::<i64>().is_ok(),
+            ColumnType::Float => value.parse::<f64>().is_ok_and(f64::is_finite),
+            ColumnType::Boolean => matches!(value.to_ascii_lowercase().as_str(), "true" | "false"),
+            ColumnType::Date => is_date(value),
+            ColumnType::Datetime => datetime::is_datetime(value),
+            ColumnType::String => true,
+        }
+    }
+}
+
+/// Column names with their types, in header order.
+pub type ColumnTypes = Vec<(String, ColumnType)>;
+
+/// `YYYY-MM-DD` with a valid month and day.
+pub fn is_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return false;
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 || !value.bytes().all(|b| b.is_ascii_digit() || b == b'-') {
+        return false;
+    }
+    let (Ok(year), Ok(month), Ok(day)) = (year.parse::<i64>(), month.parse::<i64>(), day.parse::<i64>()) else {
+        return false;
+    };
+    (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month)
+}
+
+/// Infer the type of every column from the records of one file.
+pub fn infer<'a>(headers: &[String], records: impl Iterator<Item = &'a StringRecord>) -> ColumnTypes {
+    // The candidates each column still fits, and whether it has held a value
+    let mut remaining = vec![CANDIDATES.to_vec(); headers.len()];
+    let mut seen = vec![false; headers.len()];
+    for record in records {
+        for (index, value) in record.iter().enumerate().take(headers.len()) {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            seen[index] = true;
+            remaining[index].retain(|candidate| candidate.fits(value));
+        }
+    }
+    headers
+        .iter()
+        .zip(remaining.into_iter().zip(seen))
+        .map(|(header, (remaining, seen))| {
+            let column_type = if seen { remaining.first().copied().unwrap_or(ColumnType::String) } else { ColumnType::String };
+            (header.clone(), column_type)
+        })
+        .collect()
+}
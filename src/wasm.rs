@@ -0,0 +1,231 @@
+//! The comparison on CSV text held in memory, for builds without file access such as
+//! WebAssembly in a browser or Node.js.
+//!
+//! [`compare_csv_strings`] takes both files' text and a JSON config and returns the
+//! differences as JSON, in the same entry form as the json output format:
+//!
+//! ```
+//! let result = csvdiff::wasm::compare_csv_strings("id,name\n1,Ada\n", "id,name\n1,Grace\n", r#"{"key": ["id"]}"#);
+//! assert_eq!(result, r#"{"diffs":[{"key":"1","column":"name","file1":"Ada","file2":"Grace"}]}"#);
+//! ```
+//!
+//! The config accepts `key` (a column name or a list of them, required), `ignore`,
+//! `key_separator` (default `|`), `tolerance` (non-negative, as for `--tolerance`),
+//! `diff_limit` and `preview_length`; a run that fails returns `{"error": "..."}` instead.
+//!
+//! With the `wasm` feature the function is also exported over the plain C ABI, as the build
+//! bundles no `wasm-bindgen`: the caller copies each input into memory from
+//! `csvdiff_alloc`, calls `csvdiff_compare` and reads back a buffer that starts with the
+//! result's length as a little-endian `u32`, then releases every buffer with `csvdiff_free`.
+
+use std::collections::HashSet;
+
+use csv::StringRecord;
+
+use crate::json::Json;
+use crate::normalize::{CompareOptions, parse_tolerance};
+use crate::{DiffConfig, diff_from_records};
+
+/// The options a JSON config can set.
+struct Config {
+    key: Vec<String>,
+    ignore: Vec<String>,
+    key_separator: String,
+    tolerance: Option<f64>,
+    diff_limit: Option<usize>,
+    preview_length: Option<usize>,
+}
+
+fn strings(value: &Json, option: &str) -> Result<Vec<String>, String> {
+    match value {
+        Json::String(value) => Ok(vec![value.clone()]),
+        Json::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                Json::String(value) => Ok(value.clone()),
+                _ => Err(format!("'{}' must list column names", option)),
+            })
+            .collect(),
+        _ => Err(format!("'{}' must be a column name or a list of them", option)),
+    }
+}
+
+fn number<T: std::str::FromStr>(value: &Json, option: &str) -> Result<T, String> {
+    match value {
+        Json::Number(number) => number.parse().map_err(|_| format!("'{}' is out of range: {}", option, number)),
+        _ => Err(format!("'{}' must be a number", option)),
+    }
+}
+
+impl Config {
+    fn parse(text: &str) -> Result<Config, String> {
+        let Json::Object(entries) = Json::parse(text).map_err(|e| format!("Invalid config: {}", e))? else {
+            return Err("Invalid config: expected a JSON object".into());
+        };
+        let mut config = Config {
+            key: Vec::new(),
+            ignore: Vec::new(),
+            key_separator: "|".to_string(),
+            tolerance: None,
+            diff_limit: None,
+            preview_length: Some(50),
+        };
+        for (option, value) in &entries {
+            match option.as_str() {
+                "key" => config.key = strings(value, option)?,
+                "ignore" => config.ignore = strings(value, option)?,
+                "key_separator" => match value {
+                    Json::String(separator) if separator.is_empty() => return Err("'key_separator' cannot be empty".into()),
+                    Json::String(separator) => config.key_separator = separator.clone(),
+                    _ => return Err("'key_separator' must be a string".into()),
+                },
+                "tolerance" => match value {
+                    Json::Number(number) => config.tolerance = Some(parse_tolerance(number)?),
+                    _ => return Err("'tolerance' must be a number".into()),
+                },
+                "diff_limit" => config.diff_limit = Some(number(value, option)?),
+                "preview_length" => config.preview_length = Some(number(value, option)?),
+                _ => return Err(format!("Unknown config option '{}'", option)),
+            }
+        }
+        if config.key.is_empty() {
+            return Err("The config must name at least one 'key' column".into());
+        }
+        Ok(config)
+    }
+}
+
+/// One input's headers and keyed records.
+type Rows = (Vec<String>, Vec<(String, StringRecord)>);
+
+fn read(text: &str, name: &str, config: &Config) -> Result<Rows, String> {
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let headers: Vec<String> = reader.headers().map_err(|e| format!("{}: {}", name, e))?.iter().map(String::from).collect();
+    let key_indexes = config
+        .key
+        .iter()
+        .map(|column| headers.iter().position(|h| h == column).ok_or_else(|| format!("Key column '{}' not found in {}", column, name)))
+        .collect::<Result<Vec<usize>, String>>()?;
+    let records = reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| format!("{}: {}", name, e))?;
+            let key: Vec<&str> = key_indexes.iter().map(|&i| record.get(i).unwrap_or("")).collect();
+            Ok((key.join(&config.key_separator), record))
+        })
+        .collect::<Result<_, String>>()?;
+    Ok((headers, records))
+}
+
+fn compare(csv1: &str, csv2: &str, config_json: &str) -> Result<Json, String> {
+    let config = Config::parse(config_json)?;
+    let (headers1, records1) = read(csv1, "csv1", &config)?;
+    let (headers2, records2) = read(csv2, "csv2", &config)?;
+    let known: HashSet<&String> = headers1.iter().chain(&headers2).collect();
+    if let Some(column) = config.ignore.iter().find(|column| !known.contains(column)) {
+        return Err(format!("Ignored column '{}' not found in either input", column));
+    }
+    let compare_options = CompareOptions {
+        tolerance: config.tolerance,
+        ..CompareOptions::default()
+    };
+    let diff_config = DiffConfig {
+        key_columns: &config.key,
+        ignore: &config.ignore,
+        compare_options: &compare_options,
+        diff_limit: config.diff_limit,
+        preview_length: config.preview_length,
+        ..DiffConfig::default()
+    };
+    let diffs = diff_from_records(records1, headers1, records2, headers2, &diff_config);
+    let entries = diffs
+        .iter()
+        .map(|diff| {
+            let values = [("key", &diff.key), ("column", &diff.column), ("file1", &diff.file1), ("file2", &diff.file2)];
+            Json::Object(values.iter().map(|(field, value)| (field.to_string(), Json::String(value.to_string()))).collect())
+        })
+        .collect();
+    Ok(Json::Object(vec![("diffs".to_string(), Json::Array(entries))]))
+}
+
+/// Compare two CSV documents by key; see the module documentation for the config and result.
+pub fn compare_csv_strings(csv1: &str, csv2: &str, config_json: &str) -> String {
+    compare(csv1, csv2, config_json)
+        .unwrap_or_else(|e| Json::Object(vec![("error".to_string(), Json::String(e))]))
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tolerance_must_be_a_non_negative_number() {
+        let compare = |config: &str| compare_csv_strings("id,n\n1,1.0\n", "id,n\n1,1.2\n", config);
+        assert_eq!(compare(r#"{"key": "id", "tolerance": 0.5}"#), r#"{"diffs":[]}"#);
+        for tolerance in ["-1", "1e999", r#""1""#] {
+            let result = compare(&format!(r#"{{"key": "id", "tolerance": {}}}"#, tolerance));
+            assert!(result.starts_with(r#"{"error":"#), "{}", result);
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+mod exports {
+    use super::compare_csv_strings;
+    use crate::json::Json;
+
+    /// Move a buffer of exactly `len` bytes out to the caller.
+    fn leak(buffer: Box<[u8]>) -> *mut u8 {
+        Box::into_raw(buffer).cast()
+    }
+
+    /// Reserve `len` bytes for the caller to copy an input into.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn csvdiff_alloc(len: usize) -> *mut u8 {
+        leak(vec![0; len].into_boxed_slice())
+    }
+
+    /// Release a buffer from `csvdiff_alloc` or `csvdiff_compare`, given its full length.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` and `len` must describe a buffer returned by this module that was not freed yet.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn csvdiff_free(ptr: *mut u8, len: usize) {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)) });
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` and `len` must describe an initialized buffer from `csvdiff_alloc`.
+    unsafe fn text<'a>(ptr: *const u8, len: usize) -> Result<&'a str, String> {
+        std::str::from_utf8(unsafe { std::slice::from_raw_parts(ptr, len) }).map_err(|e| format!("Input is not valid UTF-8: {}", e))
+    }
+
+    /// Run [`compare_csv_strings`] on three UTF-8 buffers. The result buffer holds a
+    /// little-endian `u32` length followed by that many bytes of JSON; free it with a length
+    /// of 4 plus the result's length.
+    ///
+    /// # Safety
+    ///
+    /// Each pointer and length must describe an initialized buffer from `csvdiff_alloc`.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn csvdiff_compare(
+        csv1: *const u8,
+        csv1_len: usize,
+        csv2: *const u8,
+        csv2_len: usize,
+        config: *const u8,
+        config_len: usize,
+    ) -> *mut u8 {
+        let inputs = unsafe { (text(csv1, csv1_len), text(csv2, csv2_len), text(config, config_len)) };
+        let result = match inputs {
+            (Ok(csv1), Ok(csv2), Ok(config)) => compare_csv_strings(csv1, csv2, config),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => Json::Object(vec![("error".to_string(), Json::String(e))]).to_string(),
+        };
+        let mut buffer = (result.len() as u32).to_le_bytes().to_vec();
+        buffer.extend_from_slice(result.as_bytes());
+        leak(buffer.into_boxed_slice())
+    }
+}
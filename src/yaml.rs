@@ -0,0 +1,253 @@
+//! Render a [`Json`] value as a block-style YAML document.
+//!
+//! Strings are written plain where YAML reads them back unchanged, as `|` literal blocks when
+//! they span lines, and double-quoted otherwise: anything that would parse as a number
+//! (including `1_000`, `0b101`, `1:30` and `+.inf`), bool, null or timestamp, starts with an
+//! indicator character, or contains `: ` or ` #`.
+
+use crate::json::Json;
+
+/// Bare words YAML 1.1 or 1.2 parsers read as something other than a string
+const RESERVED_WORDS: [&str; 12] = ["null", "~", "true", "false", "yes", "no", "on", "off", "y", "n", ".nan", ".inf"];
+
+/// Serialize a value; the result ends with a newline.
+pub fn to_string(value: &Json) -> String {
+    let mut out = String::from("---\n");
+    if is_collection(value) {
+        write_block(&mut out, value, 0);
+    } else {
+        out.push_str(&scalar_text(value, 0));
+        out.push('\n');
+    }
+    out
+}
+
+fn is_collection(value: &Json) -> bool {
+    match value {
+        Json::Object(entries) => !entries.is_empty(),
+        Json::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+/// Write a non-empty object or array, every line indented by `indent` spaces.
+fn write_block(out: &mut String, value: &Json, indent: usize) {
+    let padding = " ".repeat(indent);
+    match value {
+        Json::Object(entries) => {
+            for (key, value) in entries {
+                out.push_str(&padding);
+                out.push_str(&quote_key(key));
+                out.push(':');
+                if is_collection(value) {
+                    out.push('\n');
+                    write_block(out, value, indent + 2);
+                } else {
+                    out.push(' ');
+                    out.push_str(&scalar_text(value, indent + 2));
+                    out.push('\n');
+                }
+            }
+        }
+        Json::Array(items) => {
+            for item in items {
+                out.push_str(&padding);
+                out.push_str("- ");
+                if is_collection(item) {
+                    // The item's first line moves up beside its dash
+                    let mut nested = String::new();
+                    write_block(&mut nested, item, indent + 2);
+                    out.push_str(&nested[indent + 2..]);
+                } else {
+                    out.push_str(&scalar_text(item, indent + 2));
+                    out.push('\n');
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A scalar (or empty collection); multi-line strings become literal blocks indented to `indent`.
+fn scalar_text(value: &Json, indent: usize) -> String {
+    match value {
+        Json::Null => "null".to_string(),
+        Json::Bool(b) => b.to_string(),
+        Json::Number(n) => n.clone(),
+        Json::String(s) if is_block_safe(s) => literal_block(s, indent),
+        Json::String(s) if needs_quotes(s) => double_quoted(s),
+        Json::String(s) => s.clone(),
+        Json::Array(_) => "[]".to_string(),
+        Json::Object(_) => "{}".to_string(),
+    }
+}
+
+fn quote_key(key: &str) -> String {
+    if needs_quotes(key) || key.contains('\n') { double_quoted(key) } else { key.to_string() }
+}
+
+/// Multi-line text that a literal block reproduces exactly: no other control characters,
+/// no trailing whitespace on a line, and a first line that does not start with a space.
+fn is_block_safe(value: &str) -> bool {
+    value.contains('\n')
+        && !value.starts_with([' ', '\n'])
+        && !value.chars().any(|c| c.is_control() && c != '\n')
+        && value.lines().all(|line| !line.ends_with([' ', '\t']))
+}
+
+fn literal_block(value: &str, indent: usize) -> String {
+    let content = value.trim_end_matches('\n');
+    let chomping = match value.len() - content.len() {
+        0 => "-",
+        1 => "",
+        _ => "+",
+    };
+    let padding = " ".repeat(indent);
+    let mut out = format!("|{}", chomping);
+    for line in value.split_terminator('\n') {
+        out.push('\n');
+        if !line.is_empty() {
+            out.push_str(&padding);
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+fn needs_quotes(value: &str) -> bool {
+    let starts_with_indicator = value.starts_with([
+        '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%', '@', '`',
+    ]);
+    value.is_empty()
+        || value.trim() != value
+        || starts_with_indicator
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.ends_with(':')
+        || value.chars().any(char::is_control)
+        || RESERVED_WORDS.contains(&value.to_ascii_lowercase().as_str())
+        || is_number(value)
+        || is_timestamp(value)
+}
+
+/// Whether YAML 1.1 or 1.2 would read a value as a number: decimal or float with optional
+/// `_` separators (`1_000`), `0b`/`0o`/`0x` integers, sexagesimal (`1:30`, `190:20:30.15`)
+/// or a signed `.inf`/`.nan`.
+fn is_number(value: &str) -> bool {
+    let unsigned = value.strip_prefix(['+', '-']).unwrap_or(value);
+    let lower = unsigned.to_ascii_lowercase();
+    if matches!(lower.as_str(), ".inf" | ".nan") || value.parse::<f64>().is_ok() {
+        return true;
+    }
+    let digits_in = |part: &str, radix: u32| !part.is_empty() && part.chars().all(|c| c == '_' || c.is_digit(radix));
+    if let Some((radix, digits)) = [(2, "0b"), (8, "0o"), (16, "0x")].iter().find_map(|(radix, prefix)| lower.strip_prefix(prefix).map(|digits| (*radix, digits))) {
+        return digits_in(digits, radix);
+    }
+    if unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.') && unsigned.contains('_') {
+        return unsigned.replace('_', "").parse::<f64>().is_ok();
+    }
+    // Base 60: digits, then one or more `:` groups of one or two digits, then a fraction
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let mut groups = whole.split(':');
+    let first = groups.next().unwrap_or("");
+    let sixties: Vec<&str> = groups.collect();
+    !sixties.is_empty()
+        && digits_in(first, 10)
+        && sixties.iter().all(|group| (1..=2).contains(&group.len()) && group.bytes().all(|b| b.is_ascii_digit()))
+        && fraction.chars().all(|c| c == '_' || c.is_ascii_digit())
+}
+
+/// Whether YAML 1.1 would read a value as a timestamp: it starts with a `YYYY-M-D` date.
+/// Anything after the day is not checked, so `2024-01-15 10:30` and `2024-01-15T10:30:00Z`
+/// are quoted along with the odd value that merely starts like a date.
+fn is_timestamp(value: &str) -> bool {
+    let mut parts = value.splitn(3, '-');
+    let (Some(year), Some(month), Some(rest)) = (parts.next(), parts.next(), parts.next()) else {
+        return false;
+    };
+    let day_len = rest.bytes().take_while(u8::is_ascii_digit).count();
+    let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    year.len() == 4 && digits(year) && (1..=2).contains(&month.len()) && digits(month) && (1..=2).contains(&day_len)
+}
+
+/// A double-quoted scalar; YAML's escapes are a superset of JSON's.
+fn double_quoted(value: &str) -> String {
+    crate::json::quote(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read back a top-level scalar written by `to_string`: double-quoted text is a JSON
+    /// string, plain text is taken as it is, and `|` blocks follow their chomping indicator.
+    fn read_back(document: &str) -> String {
+        let text = document.strip_prefix("---\n").unwrap().strip_suffix('\n').unwrap();
+        if text.starts_with('"') {
+            match Json::parse(text).unwrap() {
+                Json::String(value) => value,
+                _ => unreachable!(),
+            }
+        } else if let Some(block) = text.strip_prefix('|') {
+            // A top-level block is not indented; only `-` chomping drops the final line break
+            let (chomping, body) = block.split_once('\n').unwrap();
+            if chomping == "-" { body.to_string() } else { format!("{}\n", body) }
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn written(value: &str) -> String {
+        to_string(&Json::String(value.to_string()))
+    }
+
+    #[test]
+    fn numbers_in_every_yaml_notation_are_quoted() {
+        for value in ["1_000", "1_000.5", "0b101", "0B11", "0o17", "017", "0x1F", "1:30", "-1:30", "190:20:30.15", "+.inf", "-.inf", ".NaN", "1e3", "+12", ".5"] {
+            assert!(written(value).starts_with("---\n\""), "{} is written plain", value);
+            assert_eq!(read_back(&written(value)), value);
+        }
+    }
+
+    #[test]
+    fn timestamps_are_quoted() {
+        for value in ["2024-01-15", "2024-1-5", "2024-01-15T10:30:00Z", "2024-01-15 10:30:00"] {
+            assert!(written(value).starts_with("---\n\""), "{} is written plain", value);
+            assert_eq!(read_back(&written(value)), value);
+        }
+    }
+
+    #[test]
+    fn reserved_words_and_indicators_are_quoted() {
+        for value in ["", "yes", "No", "~", "null", "- item", "#tag", "a: b", "a #b", "key:", " padded", "tab\there"] {
+            assert!(written(value).starts_with("---\n\""), "{:?} is written plain", value);
+            assert_eq!(read_back(&written(value)), value);
+        }
+    }
+
+    #[test]
+    fn ordinary_text_stays_plain() {
+        for value in ["hello", "ID-42", "a_b", "12:345", "1:2:3a", "2024-01", "x:1", "v1.2.3", "_1"] {
+            assert_eq!(written(value), format!("---\n{}\n", value));
+        }
+    }
+
+    #[test]
+    fn multi_line_text_becomes_a_literal_block() {
+        for value in ["one\ntwo", "one\ntwo\n", "one\n\ntwo\n\n"] {
+            assert!(written(value).starts_with("---\n|"));
+            assert_eq!(read_back(&written(value)), value);
+        }
+        assert!(written(" lead\ntrail").starts_with("---\n\""));
+    }
+
+    #[test]
+    fn collections_are_block_style() {
+        let value = Json::Object(vec![
+            ("id".to_string(), Json::String("007".to_string())),
+            ("tags".to_string(), Json::Array(vec![Json::String("a".to_string()), Json::Object(vec![("count".to_string(), Json::Number("1".to_string()))])])),
+            ("empty".to_string(), Json::Array(Vec::new())),
+        ]);
+        assert_eq!(to_string(&value), "---\nid: \"007\"\ntags:\n  - a\n  - count: 1\nempty: []\n");
+    }
+}